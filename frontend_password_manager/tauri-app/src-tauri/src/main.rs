@@ -2,18 +2,35 @@
     all(not(debug_assertions), target_os = "windows"),
     windows_subsystem = "windows"
 )]
+use password_manager_backend::attachment::{self, DecryptedAttachment, ATTACHMENTS_DIR};
 use password_manager_backend::config::UserSettings;
 use password_manager_backend::error_handling::CryptoError;
-use password_manager_backend::password_entry::DecryptedPasswordEntry;
+use password_manager_backend::hidden_vault::HiddenVaultKey;
+use password_manager_backend::export;
+use password_manager_backend::expiry::{self, ExpiryReminder};
+use password_manager_backend::folders::{self, Folder, TagCount};
+use password_manager_backend::export::plaintext::PlaintextFormat;
+use password_manager_backend::import::csv::{CsvImportOptions, CsvPreview};
+use password_manager_backend::import::dedupe::{self, MergeOptions, StagedImport};
+use password_manager_backend::import::pass::GpgDecryptor;
+use password_manager_backend::import::{bitwarden, csv, kdbx, onepux, pass, portable, ImportOutcome, ImportReport, SkippedItem};
+use password_manager_backend::key_file::{self, KEY_FILE_HASH_LEN};
+use password_manager_backend::key_slot::KeySlotInfo;
+use password_manager_backend::entry_kind::EntryKind;
+use password_manager_backend::password_entry::{self, DecryptedPasswordEntry};
+use password_manager_backend::password_history::PasswordHistoryItem;
 use password_manager_backend::storage::ApplicationData;
+use password_manager_backend::vault_merge::{self, VaultMergeReport};
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
 use tauri::api::path::app_data_dir;
-use tauri::{command, State};
+use tauri::{command, AppHandle, Manager, State};
 use tauri::Config;
-use zeroize::Zeroize;
+use zeroize::{Zeroize, Zeroizing};
+
+// sent after a successful login with the entries whose passwords are expired or expire soon
+const EXPIRY_REMINDERS_EVENT: &str = "expiry-reminders";
 
 #[derive(Serialize, Debug)]
 struct ErrorResponse {
@@ -48,6 +65,11 @@ impl AppConfig {
         ))?;
         Ok(app_data_path.join(&self.data_filename))
     }
+
+    // encrypted attachment blobs live next to the vault file
+    fn attachments_dir(&self, config: &Config) -> Result<PathBuf, std::io::Error> {
+        Ok(self.data_file_path(config)?.with_file_name(ATTACHMENTS_DIR))
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -57,18 +79,24 @@ struct SessionState {
     mek: Vec<u8>,
     user_settings: UserSettings,
     password_entries: Vec<DecryptedPasswordEntry>,
+    #[serde(skip)]
+    hidden_vault_key: Option<HiddenVaultKey>, // set when the session is for a vault hidden behind a decoy
+    #[serde(skip)]
+    pending_import: Option<PendingImport>, // read by an import command, added by commit_import
+}
+
+#[derive(Debug, Clone)]
+struct PendingImport {
+    entries: Vec<DecryptedPasswordEntry>,
+    skipped: Vec<SkippedItem>,
 }
 
 impl SessionState {
-    //***********only use on successful login!!!************
-    fn new(app_data: ApplicationData, master_password: &[u8]) -> Self {
-        let mek = match app_data.decrypt_mek_data(master_password) {
-            Ok(mek) => mek,
-            Err(_) => panic!("Failed to decrypt MEK data"),
-        };
+    //***********only use on successful login!!!************ (the MEK has to come from unlocking the vault)
+    fn new(app_data: &ApplicationData, mek: Vec<u8>) -> Self {
         SessionState {
             mek: mek.clone(),
-            user_settings: app_data.user_settings,
+            user_settings: app_data.user_settings.clone(),
             password_entries: app_data
                 .password_entries
                 .iter()
@@ -78,20 +106,47 @@ impl SessionState {
                         Err(_) => panic!("Failed to decrypt password entry"),
                     },
                 )
-                .collect()
+                .collect(),
+            hidden_vault_key: None,
+            pending_import: None,
         }
     }
 
 
-    fn add_decrypted_password_entry(&mut self, entry: DecryptedPasswordEntry) {
+    fn expiry_reminders(&self) -> Vec<ExpiryReminder> {
+        expiry::reminders(&self.password_entries, self.user_settings.expiry_warning_days)
+    }
+
+    fn add_decrypted_password_entry(&mut self, mut entry: DecryptedPasswordEntry) -> Result<(), CryptoError> {
+        expiry::check_days(entry.rotation_days)?;
+        entry.migrate_category();
+        entry.tidy_folder_and_tags();
+        entry.urls.retain(|url| !url.url.trim().is_empty());
         match self.password_entries.iter_mut().find(|e| e.id == entry.id) {
-            Some(existing_entry) => *existing_entry = entry,
-            None => self.password_entries.push(entry),
+            Some(existing_entry) => {
+                // attachments, password history and dates are the backend's, whatever the frontend sent
+                entry.attachments = existing_entry.attachments.clone();
+                entry.keep_history_from(existing_entry);
+                entry.keep_dates_from(existing_entry);
+                if !existing_entry.same_content(&entry) {
+                    entry.touch();
+                }
+                *existing_entry = entry
+            }
+            None => {
+                entry.attachments.clear();
+                entry.password_history.clear();
+                entry.deleted = None;
+                self.password_entries.push(entry)
+            }
         }
+        Ok(())
     }
 
-    fn update_user_settings(&mut self, user_settings: UserSettings) {
+    fn update_user_settings(&mut self, user_settings: UserSettings) -> Result<(), CryptoError> {
+        expiry::check_days(Some(user_settings.expiry_warning_days))?;
         self.user_settings = user_settings;
+        Ok(())
     }
 
 }
@@ -102,6 +157,8 @@ impl Default for SessionState {
             mek: vec![],
             user_settings: UserSettings::default(),
             password_entries: vec![],
+            hidden_vault_key: None,
+            pending_import: None,
         }
     }
 }
@@ -110,6 +167,10 @@ impl Zeroize for SessionState {
     fn zeroize(&mut self) {
         self.mek.zeroize();
         self.password_entries.zeroize();
+        self.hidden_vault_key = None; // HiddenVaultKey zeroizes itself on drop
+        if let Some(mut pending) = self.pending_import.take() {
+            pending.entries.zeroize();
+        }
     }
 }
 
@@ -146,13 +207,14 @@ async fn check_application_data_existence(
 }
 
 
-// command to create the master password after finding the file doesn't exist
+// command to create the master password after finding the file doesn't exist. If the user opted into a recovery key we hand it back here; this is the only time it is ever shown.
 #[command]
 async fn create_master_password(
     state: State<'_, AppConfig>,
     config: State<'_, Config>,
     password: String,
-) -> Result<(), ErrorResponse> {
+    enable_recovery_key: Option<bool>,
+) -> Result<Option<String>, ErrorResponse> {
     let app_data_path = state
         .data_file_path(config.inner())
         .map_err(|e| ErrorResponse::from(CryptoError::IO(e)))?;
@@ -163,19 +225,47 @@ async fn create_master_password(
         Err(e) => return Err(ErrorResponse::from(e)),
     }
 
+    let recovery_key = if enable_recovery_key.unwrap_or(false) {
+        Some(app_data.add_recovery_key(password.as_bytes())?)
+    } else {
+        None
+    };
+
     app_data
         .save_to_file(&app_data_path)
         .await
         .map_err(|e| ErrorResponse::from(e))?;
-    Ok(())
+    Ok(recovery_key)
 }
 
-/// command to change the master password (accessed through a button on the login screen). Need to verify old password first. We don't need to update session state here as we will not intialize the session until the user logs in with the new password. After setting the new password, the user will be redirected to the login screen.
+// command to (re)generate the recovery key for an existing vault. Any previous recovery key stops working. Not offered for a hidden vault: its section only opens with its own master password, so a recovery key inside it could never be used.
 #[command]
-async fn change_master_password(
+async fn create_recovery_key(
     state: State<'_, AppConfig>,
     config: State<'_, Config>,
-    old_password: String,
+    password: String,
+) -> Result<String, ErrorResponse> {
+    let app_data_path = state
+        .data_file_path(config.inner())
+        .map_err(|e| ErrorResponse::from(CryptoError::IO(e)))?;
+    let (mut app_data, mut hidden) = load_vault_for_password(&app_data_path, password.as_bytes()).await?;
+    if hidden.is_some() {
+        return Err(ErrorResponse::from(CryptoError::RecoveryUnavailableForHiddenVault));
+    }
+
+    let recovery_key = selected_vault(&mut app_data, &mut hidden)
+        .add_recovery_key(password.as_bytes())
+        .map_err(|e| ErrorResponse::from(e))?;
+    save_vault(&app_data_path, app_data, hidden).await?;
+    Ok(recovery_key)
+}
+
+/// command for the forgotten master password flow: unwraps the MEK with the recovery key and sets a new master password so no entries are lost. Like change_master_password, the user is sent back to the login screen afterwards. Only the visible vault is recovered; a vault hidden behind it is left as it is and still opens with its own master password.
+#[command]
+async fn recover_with_key(
+    state: State<'_, AppConfig>,
+    config: State<'_, Config>,
+    recovery_key: String,
     new_password: String,
 ) -> Result<(), ErrorResponse> {
     let app_data_path = state
@@ -191,7 +281,7 @@ async fn change_master_password(
     };
 
     app_data
-        .update_master_password_data(old_password.as_bytes(), new_password.as_bytes())
+        .recover_with_key(&recovery_key, new_password.as_bytes())
         .map_err(|e| ErrorResponse::from(e))?;
     app_data
         .save_to_file(&app_data_path)
@@ -200,172 +290,879 @@ async fn change_master_password(
     Ok(())
 }
 
-// command to handle logging in. This will not initialize the session but instead will be used before initializing the session to verify the password. If the password is correct, the session will be initialized and the user will be redirected to the main screen. If the password is incorrect, the user will be prompted to try again.
+/// command to change the master password (accessed through a button on the login screen). Need to verify old password first. We don't need to update session state here as we will not intialize the session until the user logs in with the new password. After setting the new password, the user will be redirected to the login screen.
 #[command]
-async fn login(
+async fn change_master_password(
     state: State<'_, AppConfig>,
     config: State<'_, Config>,
-    session: State<'_, Mutex<SessionState>>,
-    password: String,
+    old_password: String,
+    new_password: String,
+    key_file_path: Option<String>,
 ) -> Result<(), ErrorResponse> {
     let app_data_path = state
         .data_file_path(config.inner())
         .map_err(|e| ErrorResponse::from(CryptoError::IO(e)))?;
+    let (mut app_data, mut hidden) = load_vault_for_password(&app_data_path, old_password.as_bytes()).await?;
+
+    let key_file_hash = read_key_file_hash(key_file_path.as_deref()).await?;
+    selected_vault(&mut app_data, &mut hidden)
+        .update_master_password_data_with_key_file(
+            old_password.as_bytes(),
+            new_password.as_bytes(),
+            key_file_hash.as_ref().map(|hash| hash.as_slice()),
+        )
+        .map_err(|e| ErrorResponse::from(e))?;
+    // a hidden vault's key comes from its master password, so it has to be sealed under the new one
+    if let Some((vault, _)) = hidden.take() {
+        let key = HiddenVaultKey::new(new_password.as_bytes())?;
+        hidden = Some((vault, key));
+    }
+    save_vault(&app_data_path, app_data, hidden).await?;
+    Ok(())
+}
 
-    match ApplicationData::load_from_file(&app_data_path).await {
-        Ok(Some(app_data)) => {
-            // Verify the master password
-            if app_data.verify_master_password(password.as_bytes()) {
-                // On successful verification, directly initialize the session state
-                let mut session_state = session.lock().map_err(|_| ErrorResponse {
-                    error: "Failed to lock session state".into(),
-                })?;
-                *session_state = SessionState::new(app_data, password.as_bytes());
-
-                Ok(())
-            } else {
-                Err(ErrorResponse {
-                    error: "Invalid master password.".into(),
-                })
-            }
-        },
-        _ => Err(ErrorResponse {
-            error: "Application data not found; Create a password.".into(),
-        }),
+// command to split a new recovery secret into `share_count` printable shares, any `threshold` of which can unlock the vault together. Shares are only returned here, never stored. Like recovery keys, they're refused for a hidden vault.
+#[command]
+async fn create_secret_shares(
+    state: State<'_, AppConfig>,
+    config: State<'_, Config>,
+    password: String,
+    threshold: u8,
+    share_count: u8,
+) -> Result<Vec<String>, ErrorResponse> {
+    let app_data_path = state
+        .data_file_path(config.inner())
+        .map_err(|e| ErrorResponse::from(CryptoError::IO(e)))?;
+    let (mut app_data, mut hidden) = load_vault_for_password(&app_data_path, password.as_bytes()).await?;
+    if hidden.is_some() {
+        return Err(ErrorResponse::from(CryptoError::RecoveryUnavailableForHiddenVault));
     }
+
+    let shares = selected_vault(&mut app_data, &mut hidden)
+        .create_secret_shares(password.as_bytes(), threshold, share_count)
+        .map_err(|e| ErrorResponse::from(e))?;
+    save_vault(&app_data_path, app_data, hidden).await?;
+    Ok(shares)
 }
 
+// command for the shared admin vault: enough shareholders together open a session without the master password
 #[command]
-async fn logout(
-    session: State<'_, Mutex<SessionState>>) -> Result<(), ErrorResponse> {
+async fn login_with_shares(
+    app: AppHandle,
+    state: State<'_, AppConfig>,
+    config: State<'_, Config>,
+    session: State<'_, Mutex<SessionState>>,
+    shares: Vec<String>,
+) -> Result<(), ErrorResponse> {
+    let app_data_path = state
+        .data_file_path(config.inner())
+        .map_err(|e| ErrorResponse::from(CryptoError::IO(e)))?;
+    let mut app_data = match ApplicationData::load_from_file(&app_data_path).await? {
+        Some(data) => data,
+        None => {
+            return Err(ErrorResponse {
+                error: "Application data not found; Create a password.".into(),
+            })
+        }
+    };
+
+    let mek = Zeroizing::new(app_data
+        .decrypt_mek_with_shares(&shares)
+        .map_err(|e| ErrorResponse::from(e))?);
+    let attachments_dir = state
+        .attachments_dir(config.inner())
+        .map_err(|e| ErrorResponse::from(CryptoError::IO(e)))?;
+    let purged = purge_expired_trash(&mut app_data, &attachments_dir)?;
+    let new_session = SessionState::new(&app_data, mek.to_vec());
+    if app_data.encrypt_plaintext_fields(&mek)? || purged {
+        app_data.save_to_file(&app_data_path).await?;
+    }
     let mut session_state = session.lock().map_err(|_| ErrorResponse {
         error: "Failed to lock session state".into(),
     })?;
-    session_state.zeroize();
+    *session_state = new_session;
+    emit_expiry_reminders(&app, &session_state);
     Ok(())
 }
 
-
+// command to set a new master password with enough secret shares, same flow as recover_with_key (so a hidden vault is left untouched)
 #[command]
-async fn get_password_entries(
-    session: State<'_, Mutex<SessionState>>,
-) -> Result<Vec<DecryptedPasswordEntry>, ErrorResponse> {
-    let session_state = session.lock().map_err(|_| ErrorResponse {
-        error: "Failed to lock session state".into(),
-    })?;
+async fn recover_with_shares(
+    state: State<'_, AppConfig>,
+    config: State<'_, Config>,
+    shares: Vec<String>,
+    new_password: String,
+) -> Result<(), ErrorResponse> {
+    let app_data_path = state
+        .data_file_path(config.inner())
+        .map_err(|e| ErrorResponse::from(CryptoError::IO(e)))?;
+    let mut app_data = match ApplicationData::load_from_file(&app_data_path).await? {
+        Some(data) => data,
+        None => {
+            return Err(ErrorResponse {
+                error: "Application data not found.".into(),
+            })
+        }
+    };
 
+    app_data
+        .recover_with_shares(&shares, new_password.as_bytes())
+        .map_err(|e| ErrorResponse::from(e))?;
+    app_data
+        .save_to_file(&app_data_path)
+        .await
+        .map_err(|e| ErrorResponse::from(e))?;
+    Ok(())
+}
 
-    
+// Hashes the key file the user picked in the file dialog, if any
+async fn read_key_file_hash(key_file_path: Option<&str>) -> Result<Option<[u8; KEY_FILE_HASH_LEN]>, ErrorResponse> {
+    match key_file_path {
+        Some(path) => Ok(Some(key_file::hash_key_file(Path::new(path)).await?)),
+        None => Ok(None),
+    }
+}
 
+// The visible vault, plus the vault hidden behind it when `password` belongs to that one instead
+type HiddenVaultAccess = Option<(ApplicationData, HiddenVaultKey)>;
 
-    Ok(session_state.password_entries.clone())
+async fn load_vault_for_password(app_data_path: &Path, password: &[u8]) -> Result<(ApplicationData, HiddenVaultAccess), ErrorResponse> {
+    let app_data = match ApplicationData::load_from_file(app_data_path).await? {
+        Some(data) => data,
+        None => {
+            return Err(ErrorResponse {
+                error: "Application data not found.".into(),
+            })
+        }
+    };
+    if app_data.verify_master_password(password) {
+        return Ok((app_data, None));
+    }
+    let hidden = app_data.open_hidden_vault(password)?;
+    Ok((app_data, hidden))
 }
 
-
-#[command]
-async fn get_user_settings(
-    session: State<'_, Mutex<SessionState>>,
-) -> Result<UserSettings, ErrorResponse> {
-    let session_state = session.lock().map_err(|_| ErrorResponse {
-        error: "Failed to lock session state".into(),
-    })?;
-    Ok(session_state.user_settings.clone())
+// whichever vault the password opened is the one a command should change
+fn selected_vault<'a>(app_data: &'a mut ApplicationData, hidden: &'a mut HiddenVaultAccess) -> &'a mut ApplicationData {
+    match hidden {
+        Some((vault, _)) => vault,
+        None => app_data,
+    }
 }
 
+// Drops entries that have been in the trash longer than the user's retention period, with their
+// attachments. Returns whether anything was purged, i.e. whether the vault needs saving.
+fn purge_expired_trash(vault: &mut ApplicationData, attachments_dir: &Path) -> Result<bool, ErrorResponse> {
+    let purged = vault.purge_trash()?;
+    for entry in &purged {
+        for entry_attachment in &entry.attachments {
+            attachment::delete_attachment(attachments_dir, &entry_attachment.id)?;
+        }
+    }
+    Ok(!purged.is_empty())
+}
 
+async fn save_vault(app_data_path: &Path, mut app_data: ApplicationData, hidden: HiddenVaultAccess) -> Result<(), ErrorResponse> {
+    if let Some((vault, key)) = &hidden {
+        app_data.seal_hidden_vault(vault, key)?;
+    }
+    app_data.save_to_file(app_data_path).await?;
+    Ok(())
+}
 
+// command to set up (or replace) the duress password. The vault the master password opens moves into the hidden section and an empty decoy vault, opened by the duress password, takes its place. The session is cleared so the user logs in again.
 #[command]
-async fn add_password_entry(
+async fn enable_duress_password(
+    state: State<'_, AppConfig>,
+    config: State<'_, Config>,
     session: State<'_, Mutex<SessionState>>,
-    password_entry: DecryptedPasswordEntry,
+    password: String,
+    duress_password: String,
 ) -> Result<(), ErrorResponse> {
+    let app_data_path = state
+        .data_file_path(config.inner())
+        .map_err(|e| ErrorResponse::from(CryptoError::IO(e)))?;
+    let mut app_data = match ApplicationData::load_from_file(&app_data_path).await? {
+        Some(data) => data,
+        None => {
+            return Err(ErrorResponse {
+                error: "Application data not found.".into(),
+            })
+        }
+    };
+
+    let attachments_dir = state
+        .attachments_dir(config.inner())
+        .map_err(|e| ErrorResponse::from(CryptoError::IO(e)))?;
+    // when the decoy is being replaced rather than the visible vault hidden, the old decoy's blobs go with it
+    let replaced_decoy_attachments = if app_data.verify_master_password(password.as_bytes()) {
+        Vec::new()
+    } else {
+        app_data.attachment_ids()
+    };
+
+    app_data
+        .enable_duress_password(password.as_bytes(), duress_password.as_bytes())
+        .map_err(|e| ErrorResponse::from(e))?;
+    app_data
+        .save_to_file(&app_data_path)
+        .await
+        .map_err(|e| ErrorResponse::from(e))?;
+
+    // every vault's blobs share the one attachments directory under random ids, so hiding a vault
+    // leaves the file layout as it was
+    for id in &replaced_decoy_attachments {
+        attachment::delete_attachment(&attachments_dir, id)?;
+    }
+
     let mut session_state = session.lock().map_err(|_| ErrorResponse {
         error: "Failed to lock session state".into(),
     })?;
-
-    session_state.add_decrypted_password_entry(password_entry);
+    session_state.zeroize();
     Ok(())
 }
 
+// command to remove the duress password: the hidden vault comes back to the front and the decoy entries are discarded
 #[command]
-fn get_password_entry(
-    entry_id: String,
+async fn disable_duress_password(
+    state: State<'_, AppConfig>,
+    config: State<'_, Config>,
     session: State<'_, Mutex<SessionState>>,
-) -> Result<DecryptedPasswordEntry, ErrorResponse> {
-    
-    let session_state = session.lock().map_err(|_| ErrorResponse {
-        error: "Failed to lock session state".into(),
-    })?;
-    match session_state.password_entries.iter().find(|e| e.id == entry_id) {
-        Some(entry) => Ok(entry.clone()),
-        None => Err(ErrorResponse {
-            error: "Password entry not found".into(),
-        }),
-    }
-}
+    password: String,
+) -> Result<(), ErrorResponse> {
+    let app_data_path = state
+        .data_file_path(config.inner())
+        .map_err(|e| ErrorResponse::from(CryptoError::IO(e)))?;
+    let mut app_data = match ApplicationData::load_from_file(&app_data_path).await? {
+        Some(data) => data,
+        None => {
+            return Err(ErrorResponse {
+                error: "Application data not found.".into(),
+            })
+        }
+    };
 
-#[command]
+    let attachments_dir = state
+        .attachments_dir(config.inner())
+        .map_err(|e| ErrorResponse::from(CryptoError::IO(e)))?;
+    let decoy_attachments = app_data.attachment_ids();
 
-fn create_new_decrypted_password_entry() -> DecryptedPasswordEntry {
-    DecryptedPasswordEntry::default()
-}
+    app_data
+        .disable_duress_password(password.as_bytes())
+        .map_err(|e| ErrorResponse::from(e))?;
+    app_data
+        .save_to_file(&app_data_path)
+        .await
+        .map_err(|e| ErrorResponse::from(e))?;
 
+    for id in &decoy_attachments {
+        attachment::delete_attachment(&attachments_dir, id)?;
+    }
 
-#[command]
-async fn update_user_settings(
-    session: State<'_, Mutex<SessionState>>,
-    user_settings: UserSettings,
-) -> Result<(), ErrorResponse> {
     let mut session_state = session.lock().map_err(|_| ErrorResponse {
         error: "Failed to lock session state".into(),
     })?;
-    session_state.update_user_settings(user_settings);
-
-
+    session_state.zeroize();
     Ok(())
 }
 
+// command to write a fresh random key file to the location chosen in the save dialog
+#[command]
+async fn generate_key_file(file_path: String) -> Result<(), ErrorResponse> {
+    key_file::generate_key_file(Path::new(&file_path))
+        .await
+        .map_err(|e| ErrorResponse::from(e))
+}
 
+// command to require the key file (in addition to the master password) from now on
 #[command]
-fn generate_password(session: State<'_, Mutex<SessionState>>) -> Result<String, ErrorResponse> {
-    let session_state = session.lock().map_err(|_| ErrorResponse {
-        error: "Failed to lock session state".into(),
-    })?;
+async fn enable_key_file(
+    state: State<'_, AppConfig>,
+    config: State<'_, Config>,
+    password: String,
+    key_file_path: String,
+) -> Result<(), ErrorResponse> {
+    let app_data_path = state
+        .data_file_path(config.inner())
+        .map_err(|e| ErrorResponse::from(CryptoError::IO(e)))?;
+    let (mut app_data, mut hidden) = load_vault_for_password(&app_data_path, password.as_bytes()).await?;
 
-    Ok(session_state.user_settings.generate_password())
+    let key_file_hash = key_file::hash_key_file(Path::new(&key_file_path)).await?;
+    selected_vault(&mut app_data, &mut hidden)
+        .enable_key_file(password.as_bytes(), &key_file_hash)
+        .map_err(|e| ErrorResponse::from(e))?;
+    save_vault(&app_data_path, app_data, hidden).await?;
+    Ok(())
 }
 
+// command to drop the key file requirement; needs the current key file as well as the password
 #[command]
-async fn save_session_state(
-    session: State<'_, Mutex<SessionState>>,
+async fn disable_key_file(
     state: State<'_, AppConfig>,
     config: State<'_, Config>,
+    password: String,
+    key_file_path: String,
 ) -> Result<(), ErrorResponse> {
     let app_data_path = state
         .data_file_path(config.inner())
         .map_err(|e| ErrorResponse::from(CryptoError::IO(e)))?;
+    let (mut app_data, mut hidden) = load_vault_for_password(&app_data_path, password.as_bytes()).await?;
 
-    
-    let (user_settings, mut password_entries, mut mek) = {
-        let session_state = session.lock().map_err(|_| ErrorResponse {
-            error: "Failed to lock session state".into(),
-        })?;
-
-        (session_state.user_settings.clone(), session_state.password_entries.clone(), session_state.mek.clone())
-    };
+    let key_file_hash = key_file::hash_key_file(Path::new(&key_file_path)).await?;
+    selected_vault(&mut app_data, &mut hidden)
+        .disable_key_file(password.as_bytes(), &key_file_hash)
+        .map_err(|e| ErrorResponse::from(e))?;
+    save_vault(&app_data_path, app_data, hidden).await?;
+    Ok(())
+}
 
-    // Spawn a new task to save the session state to file because I want to run this each time we return our useSessionState hook and it could get called a lot
-    tokio::spawn(async move {
-        let app_data = match ApplicationData::load_from_file(&app_data_path).await {
-            Ok(Some(mut data)) => {
-                data.user_settings = user_settings;
-                data.password_entries = password_entries
-                    .iter()
-                    .map(|entry| entry.convert_to_encrypted(&mek).unwrap())
+// command to list the key slots that can unlock the vault (labels and kinds only, nothing secret). Works without logging in so the login screen can offer the recovery path.
+#[command]
+async fn list_key_slots(
+    state: State<'_, AppConfig>,
+    config: State<'_, Config>,
+) -> Result<Vec<KeySlotInfo>, ErrorResponse> {
+    let app_data_path = state
+        .data_file_path(config.inner())
+        .map_err(|e| ErrorResponse::from(CryptoError::IO(e)))?;
+    match ApplicationData::load_from_file(&app_data_path).await? {
+        Some(app_data) => Ok(app_data.list_key_slots()),
+        None => Err(ErrorResponse {
+            error: "Application data not found.".into(),
+        }),
+    }
+}
+
+// command to remove a key slot; the backend refuses to remove the last one (or the last master password slot)
+#[command]
+async fn remove_key_slot(
+    state: State<'_, AppConfig>,
+    config: State<'_, Config>,
+    password: String,
+    slot_id: String,
+) -> Result<(), ErrorResponse> {
+    let app_data_path = state
+        .data_file_path(config.inner())
+        .map_err(|e| ErrorResponse::from(CryptoError::IO(e)))?;
+    let (mut app_data, mut hidden) = load_vault_for_password(&app_data_path, password.as_bytes()).await?;
+
+    selected_vault(&mut app_data, &mut hidden)
+        .remove_key_slot(password.as_bytes(), &slot_id)
+        .map_err(|e| ErrorResponse::from(e))?;
+    save_vault(&app_data_path, app_data, hidden).await?;
+    Ok(())
+}
+
+// command to handle logging in. This will not initialize the session but instead will be used before initializing the session to verify the password. If the password is correct, the session will be initialized and the user will be redirected to the main screen. If the password is incorrect, the user will be prompted to try again.
+#[command]
+async fn login(
+    app: AppHandle,
+    state: State<'_, AppConfig>,
+    config: State<'_, Config>,
+    session: State<'_, Mutex<SessionState>>,
+    password: String,
+    key_file_path: Option<String>,
+) -> Result<(), ErrorResponse> {
+    let app_data_path = state
+        .data_file_path(config.inner())
+        .map_err(|e| ErrorResponse::from(CryptoError::IO(e)))?;
+    let attachments_dir = state
+        .attachments_dir(config.inner())
+        .map_err(|e| ErrorResponse::from(CryptoError::IO(e)))?;
+    let key_file_hash = read_key_file_hash(key_file_path.as_deref()).await?;
+
+    match ApplicationData::load_from_file(&app_data_path).await {
+        Ok(Some(mut app_data)) => {
+            // Verify the master password; failing that, it may be the password of a vault hidden behind this one
+            let mut hidden: HiddenVaultAccess = if app_data.verify_master_password(password.as_bytes()) {
+                None
+            } else {
+                match app_data.open_hidden_vault(password.as_bytes()) {
+                    Ok(Some(hidden)) => Some(hidden),
+                    _ => {
+                        return Err(ErrorResponse {
+                            error: "Invalid master password.".into(),
+                        })
+                    }
+                }
+            };
+            let hidden_vault_key = hidden.as_ref().map(|(_, key)| key.clone());
+            let vault = selected_vault(&mut app_data, &mut hidden);
+
+            // The MEK may additionally need the key file, so unlock before touching the session
+            let mek = Zeroizing::new(vault
+                .decrypt_mek_data_with_key_file(password.as_bytes(), key_file_hash.as_ref().map(|hash| hash.as_slice()))
+                .map_err(|e| ErrorResponse::from(e))?);
+            let purged = purge_expired_trash(vault, &attachments_dir)?;
+            let new_session = SessionState::new(vault, mek.to_vec());
+            // vaults from before usernames and notes were encrypted are upgraded on first unlock
+            if vault.encrypt_plaintext_fields(&mek)? || purged {
+                save_vault(&app_data_path, app_data, hidden).await?;
+            }
+
+            let mut session_state = session.lock().map_err(|_| ErrorResponse {
+                error: "Failed to lock session state".into(),
+            })?;
+            *session_state = new_session;
+            session_state.hidden_vault_key = hidden_vault_key;
+            emit_expiry_reminders(&app, &session_state);
+
+            Ok(())
+        },
+        _ => Err(ErrorResponse {
+            error: "Application data not found; Create a password.".into(),
+        }),
+    }
+}
+
+#[command]
+async fn logout(
+    session: State<'_, Mutex<SessionState>>) -> Result<(), ErrorResponse> {
+    let mut session_state = session.lock().map_err(|_| ErrorResponse {
+        error: "Failed to lock session state".into(),
+    })?;
+    session_state.zeroize();
+    Ok(())
+}
+
+
+#[command]
+async fn get_password_entries(
+    session: State<'_, Mutex<SessionState>>,
+) -> Result<Vec<DecryptedPasswordEntry>, ErrorResponse> {
+    let session_state = session.lock().map_err(|_| ErrorResponse {
+        error: "Failed to lock session state".into(),
+    })?;
+    Ok(session_state
+        .password_entries
+        .iter()
+        .filter(|entry| !entry.is_trashed())
+        .cloned()
+        .collect())
+}
+
+
+#[command]
+async fn get_user_settings(
+    session: State<'_, Mutex<SessionState>>,
+) -> Result<UserSettings, ErrorResponse> {
+    let session_state = session.lock().map_err(|_| ErrorResponse {
+        error: "Failed to lock session state".into(),
+    })?;
+    Ok(session_state.user_settings.clone())
+}
+
+
+
+#[command]
+async fn add_password_entry(
+    session: State<'_, Mutex<SessionState>>,
+    password_entry: DecryptedPasswordEntry,
+) -> Result<(), ErrorResponse> {
+    let mut session_state = session.lock().map_err(|_| ErrorResponse {
+        error: "Failed to lock session state".into(),
+    })?;
+
+    session_state.add_decrypted_password_entry(password_entry)?;
+    Ok(())
+}
+
+// Decrypts freshly imported entries and holds them in the session until commit_import; nothing is added to the vault yet
+fn add_imported_entries(
+    session: &State<'_, Mutex<SessionState>>,
+    outcome: ImportOutcome,
+    mek: &[u8],
+) -> Result<StagedImport, ErrorResponse> {
+    let entries = outcome
+        .entries
+        .iter()
+        .map(|entry| entry.to_decrypted(mek))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| ErrorResponse::from(e))?;
+    stage_import(session, entries, outcome.report.skipped)
+}
+
+fn stage_import(
+    session: &State<'_, Mutex<SessionState>>,
+    entries: Vec<DecryptedPasswordEntry>,
+    skipped: Vec<SkippedItem>,
+) -> Result<StagedImport, ErrorResponse> {
+    let mut session_state = session.lock().map_err(|_| ErrorResponse {
+        error: "Failed to lock session state".into(),
+    })?;
+    let staged = StagedImport {
+        pending: entries.len(),
+        conflicts: dedupe::find_conflicts(&session_state.password_entries, &entries),
+        skipped: skipped.clone(),
+    };
+    if let Some(mut previous) = session_state.pending_import.replace(PendingImport { entries, skipped }) {
+        previous.entries.zeroize();
+    }
+    Ok(staged)
+}
+
+// command to add the staged import to the open vault. Conflicts are resolved per entry (keyed by the imported entry's id) or with the default mode.
+#[command]
+fn commit_import(
+    session: State<'_, Mutex<SessionState>>,
+    options: MergeOptions,
+) -> Result<ImportReport, ErrorResponse> {
+    let mut session_state = session.lock().map_err(|_| ErrorResponse {
+        error: "Failed to lock session state".into(),
+    })?;
+    let pending = session_state.pending_import.take().ok_or(ErrorResponse {
+        error: "No import is waiting to be committed.".into(),
+    })?;
+
+    let mut report = dedupe::merge_entries(&mut session_state.password_entries, pending.entries, &options);
+    report.skipped.splice(0..0, pending.skipped);
+    Ok(report)
+}
+
+// command to throw away a staged import
+#[command]
+fn cancel_import(session: State<'_, Mutex<SessionState>>) -> Result<(), ErrorResponse> {
+    let mut session_state = session.lock().map_err(|_| ErrorResponse {
+        error: "Failed to lock session state".into(),
+    })?;
+    if let Some(mut pending) = session_state.pending_import.take() {
+        pending.entries.zeroize();
+    }
+    Ok(())
+}
+
+fn session_mek(session: &State<'_, Mutex<SessionState>>) -> Result<Zeroizing<Vec<u8>>, ErrorResponse> {
+    let session_state = session.lock().map_err(|_| ErrorResponse {
+        error: "Failed to lock session state".into(),
+    })?;
+    if session_state.mek.is_empty() {
+        return Err(ErrorResponse {
+            error: "Not logged in.".into(),
+        });
+    }
+    Ok(Zeroizing::new(session_state.mek.clone()))
+}
+
+// command to read an unencrypted Bitwarden JSON export and stage its entries for commit_import, along with any duplicates of vault entries and what was skipped.
+#[command]
+async fn import_bitwarden(
+    session: State<'_, Mutex<SessionState>>,
+    file_path: String,
+) -> Result<StagedImport, ErrorResponse> {
+    let mut content = tokio::fs::read_to_string(&file_path)
+        .await
+        .map_err(|e| ErrorResponse::from(CryptoError::IO(e)))?;
+    let mek = session_mek(&session)?;
+    let outcome = bitwarden::import_bitwarden_json(&content, &mek);
+    content.zeroize();
+
+    add_imported_entries(&session, outcome.map_err(|e| ErrorResponse::from(e))?, &mek)
+}
+
+// command to import a KeePass KDBX 4 database, opened with its own password. Groups become categories.
+#[command]
+async fn import_kdbx(
+    session: State<'_, Mutex<SessionState>>,
+    file_path: String,
+    password: String,
+) -> Result<StagedImport, ErrorResponse> {
+    let content = tokio::fs::read(&file_path)
+        .await
+        .map_err(|e| ErrorResponse::from(CryptoError::IO(e)))?;
+    let mek = session_mek(&session)?;
+    let outcome = kdbx::import_kdbx(&content, password.as_bytes(), &mek).map_err(|e| ErrorResponse::from(e))?;
+
+    add_imported_entries(&session, outcome, &mek)
+}
+
+// command to import a 1Password .1pux export. Vault names become categories; item types other than logins, passwords and secure notes are reported as skipped.
+#[command]
+async fn import_1pux(
+    session: State<'_, Mutex<SessionState>>,
+    file_path: String,
+) -> Result<StagedImport, ErrorResponse> {
+    let mut content = tokio::fs::read(&file_path)
+        .await
+        .map_err(|e| ErrorResponse::from(CryptoError::IO(e)))?;
+    let mek = session_mek(&session)?;
+    let outcome = onepux::import_1pux(&content, &mek);
+    content.zeroize();
+
+    add_imported_entries(&session, outcome.map_err(|e| ErrorResponse::from(e))?, &mek)
+}
+
+// command to import a `pass` password store directory. Files are decrypted with the gpg found by the backend, which prompts for the key passphrase through gpg-agent.
+#[command]
+async fn import_pass(
+    session: State<'_, Mutex<SessionState>>,
+    directory_path: String,
+) -> Result<StagedImport, ErrorResponse> {
+    let mek = session_mek(&session)?;
+    let decryptor = GpgDecryptor::new();
+    let outcome = {
+        let mek = mek.clone();
+        tokio::task::spawn_blocking(move || pass::import_pass_store(Path::new(&directory_path), &decryptor, &mek))
+            .await
+            .map_err(|e| ErrorResponse {
+                error: e.to_string(),
+            })?
+    };
+
+    add_imported_entries(&session, outcome.map_err(|e| ErrorResponse::from(e))?, &mek)
+}
+
+// command to merge another PawPass vault file (e.g. a copy from another machine) into the open vault. Entries are matched by id and the later edit wins; the report lists every entry edited on both sides and the other vault's attachments, whose blobs can't be brought over.
+#[command]
+async fn merge_vault_file(
+    session: State<'_, Mutex<SessionState>>,
+    file_path: String,
+    password: String,
+) -> Result<VaultMergeReport, ErrorResponse> {
+    session_mek(&session)?;
+    let (mut other, mut other_hidden) = load_vault_for_password(Path::new(&file_path), password.as_bytes()).await?;
+    let other = selected_vault(&mut other, &mut other_hidden);
+    let other_mek = Zeroizing::new(other.decrypt_mek_data(password.as_bytes())?);
+    let other_entries = other
+        .password_entries
+        .iter()
+        .map(|entry| entry.to_decrypted(&other_mek))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| ErrorResponse::from(e))?;
+
+    let mut session_state = session.lock().map_err(|_| ErrorResponse {
+        error: "Failed to lock session state".into(),
+    })?;
+    Ok(vault_merge::merge_decrypted_entries(&mut session_state.password_entries, other_entries))
+}
+
+// command to show what a CSV export would import with the given preset or column mapping, without touching the vault. Passwords are left out.
+#[command]
+async fn preview_csv_import(file_path: String, options: CsvImportOptions) -> Result<CsvPreview, ErrorResponse> {
+    let mut content = tokio::fs::read(&file_path)
+        .await
+        .map_err(|e| ErrorResponse::from(CryptoError::IO(e)))?;
+    let preview = csv::preview_csv(&content, &options);
+    content.zeroize();
+
+    preview.map_err(|e| ErrorResponse::from(e))
+}
+
+// command to import a CSV export (Chrome, Firefox, Edge, LastPass or a custom column mapping) into the open vault.
+#[command]
+async fn import_csv(
+    session: State<'_, Mutex<SessionState>>,
+    file_path: String,
+    options: CsvImportOptions,
+) -> Result<StagedImport, ErrorResponse> {
+    let mut content = tokio::fs::read(&file_path)
+        .await
+        .map_err(|e| ErrorResponse::from(CryptoError::IO(e)))?;
+    let mek = session_mek(&session)?;
+    let outcome = csv::import_csv(&content, &options, &mek);
+    content.zeroize();
+
+    add_imported_entries(&session, outcome.map_err(|e| ErrorResponse::from(e))?, &mek)
+}
+
+// command to export the open vault as a KeePass KDBX 4 file protected by `password`. Categories become groups and favorites get a "Favorite" tag.
+#[command]
+async fn export_kdbx(
+    session: State<'_, Mutex<SessionState>>,
+    file_path: String,
+    password: String,
+) -> Result<(), ErrorResponse> {
+    let exported = {
+        let session_state = session.lock().map_err(|_| ErrorResponse {
+            error: "Failed to lock session state".into(),
+        })?;
+        if session_state.mek.is_empty() {
+            return Err(ErrorResponse {
+                error: "Not logged in.".into(),
+            });
+        }
+        export::kdbx::export_kdbx(&session_state.password_entries, password.as_bytes())
+            .map_err(|e| ErrorResponse::from(e))?
+    };
+
+    tokio::fs::write(&file_path, exported)
+        .await
+        .map_err(|e| ErrorResponse::from(CryptoError::IO(e)))
+}
+
+// command to export the open vault to a single file encrypted under a separate export passphrase, for moving it to another install.
+#[command]
+async fn export_portable(
+    session: State<'_, Mutex<SessionState>>,
+    file_path: String,
+    passphrase: String,
+) -> Result<(), ErrorResponse> {
+    let exported = {
+        let session_state = session.lock().map_err(|_| ErrorResponse {
+            error: "Failed to lock session state".into(),
+        })?;
+        if session_state.mek.is_empty() {
+            return Err(ErrorResponse {
+                error: "Not logged in.".into(),
+            });
+        }
+        export::portable::export_portable(&session_state.password_entries, passphrase.as_bytes())
+            .map_err(|e| ErrorResponse::from(e))?
+    };
+
+    tokio::fs::write(&file_path, exported)
+        .await
+        .map_err(|e| ErrorResponse::from(CryptoError::IO(e)))
+}
+
+// command to write the open vault to an unencrypted JSON or CSV file for other tools. The master password has to be entered again and is checked here, against the vault this session opened.
+#[command]
+async fn export_plaintext(
+    state: State<'_, AppConfig>,
+    config: State<'_, Config>,
+    session: State<'_, Mutex<SessionState>>,
+    file_path: String,
+    format: PlaintextFormat,
+    password: String,
+) -> Result<(), ErrorResponse> {
+    let app_data_path = state
+        .data_file_path(config.inner())
+        .map_err(|e| ErrorResponse::from(CryptoError::IO(e)))?;
+    let (app_data, hidden) = load_vault_for_password(&app_data_path, password.as_bytes()).await?;
+
+    let session_state = session.lock().map_err(|_| ErrorResponse {
+        error: "Failed to lock session state".into(),
+    })?;
+    // a password for the other vault in the file (decoy or hidden) doesn't count
+    let confirmed = match (&hidden, &session_state.hidden_vault_key) {
+        (Some(_), Some(_)) => true,
+        (None, None) => app_data.verify_master_password(password.as_bytes()),
+        _ => false,
+    };
+    if !confirmed || session_state.mek.is_empty() {
+        return Err(ErrorResponse::from(CryptoError::AuthenticationFailed));
+    }
+
+    export::plaintext::export_plaintext_to_file(&session_state.password_entries, format, Path::new(&file_path))
+        .map_err(|e| ErrorResponse::from(e))
+}
+
+// command to read a portable export and stage its entries for commit_import
+#[command]
+async fn import_portable(
+    session: State<'_, Mutex<SessionState>>,
+    file_path: String,
+    passphrase: String,
+) -> Result<StagedImport, ErrorResponse> {
+    let content = tokio::fs::read(&file_path)
+        .await
+        .map_err(|e| ErrorResponse::from(CryptoError::IO(e)))?;
+    session_mek(&session)?;
+    let entries = portable::read_portable(&content, passphrase.as_bytes()).map_err(|e| ErrorResponse::from(e))?;
+
+    stage_import(&session, entries, Vec::new())
+}
+
+#[command]
+fn get_password_entry(
+    entry_id: String,
+    session: State<'_, Mutex<SessionState>>,
+) -> Result<DecryptedPasswordEntry, ErrorResponse> {
+    
+    let session_state = session.lock().map_err(|_| ErrorResponse {
+        error: "Failed to lock session state".into(),
+    })?;
+    match session_state.password_entries.iter().find(|e| e.id == entry_id) {
+        Some(entry) => Ok(entry.clone()),
+        None => Err(ErrorResponse {
+            error: "Password entry not found".into(),
+        }),
+    }
+}
+
+#[command]
+
+fn create_new_decrypted_password_entry() -> DecryptedPasswordEntry {
+    DecryptedPasswordEntry::default()
+}
+
+
+#[command]
+async fn update_user_settings(
+    session: State<'_, Mutex<SessionState>>,
+    user_settings: UserSettings,
+) -> Result<(), ErrorResponse> {
+    let mut session_state = session.lock().map_err(|_| ErrorResponse {
+        error: "Failed to lock session state".into(),
+    })?;
+    session_state.update_user_settings(user_settings)?;
+
+
+    Ok(())
+}
+
+
+#[command]
+fn generate_password(session: State<'_, Mutex<SessionState>>) -> Result<String, ErrorResponse> {
+    let session_state = session.lock().map_err(|_| ErrorResponse {
+        error: "Failed to lock session state".into(),
+    })?;
+
+    Ok(session_state.user_settings.generate_password())
+}
+
+#[command]
+async fn save_session_state(
+    session: State<'_, Mutex<SessionState>>,
+    state: State<'_, AppConfig>,
+    config: State<'_, Config>,
+) -> Result<(), ErrorResponse> {
+    let app_data_path = state
+        .data_file_path(config.inner())
+        .map_err(|e| ErrorResponse::from(CryptoError::IO(e)))?;
+
+    
+    let (user_settings, mut password_entries, mut mek, hidden_vault_key) = {
+        let session_state = session.lock().map_err(|_| ErrorResponse {
+            error: "Failed to lock session state".into(),
+        })?;
+
+        (
+            session_state.user_settings.clone(),
+            session_state.password_entries.clone(),
+            session_state.mek.clone(),
+            session_state.hidden_vault_key.clone(),
+        )
+    };
+
+    // Spawn a new task to save the session state to file because I want to run this each time we return our useSessionState hook and it could get called a lot
+    tokio::spawn(async move {
+        let app_data = match ApplicationData::load_from_file(&app_data_path).await {
+            Ok(Some(mut outer)) => {
+                // a hidden vault is written back into the outer file rather than over it
+                let mut data = match &hidden_vault_key {
+                    Some(key) => match outer.open_hidden_vault_with_key(key) {
+                        Ok(Some(vault)) => vault,
+                        Ok(None) => return Err(ErrorResponse {
+                            error: "Hidden vault not found".into(),
+                        }),
+                        Err(e) => return Err(ErrorResponse::from(e)),
+                    },
+                    None => std::mem::replace(&mut outer, ApplicationData::new()),
+                };
+                data.user_settings = user_settings;
+                data.password_entries = password_entries
+                    .iter()
+                    .map(|entry| entry.convert_to_encrypted(&mek).unwrap())
                     .collect();
                 (password_entries.zeroize(), mek.zeroize()); 
                 let _ = data.update_hashes();
-                data
+                match &hidden_vault_key {
+                    Some(key) => {
+                        outer.seal_hidden_vault(&data, key).map_err(|e| ErrorResponse::from(e))?;
+                        outer
+                    },
+                    None => data,
+                }
             },
             Ok(None) => return Err(ErrorResponse {
                 error: "Application data not found".into(),
@@ -388,20 +1185,64 @@ async fn save_session_state(
     Ok(())
 }
 
+// command to list the folders entries outside the trash are filed in, as a tree
+#[command]
+fn get_folders(session: State<'_, Mutex<SessionState>>) -> Result<Vec<Folder>, ErrorResponse> {
+    let session_state = session.lock().map_err(|_| ErrorResponse {
+        error: "Failed to lock session state".into(),
+    })?;
+    Ok(folders::folder_tree(
+        session_state.password_entries.iter().filter(|entry| !entry.is_trashed()),
+    ))
+}
+
+// command to list the tags on entries outside the trash, with how many entries have each
+#[command]
+fn get_tags(session: State<'_, Mutex<SessionState>>) -> Result<Vec<TagCount>, ErrorResponse> {
+    let session_state = session.lock().map_err(|_| ErrorResponse {
+        error: "Failed to lock session state".into(),
+    })?;
+    Ok(folders::tag_counts(
+        session_state.password_entries.iter().filter(|entry| !entry.is_trashed()),
+    ))
+}
+
+// command to rename a folder; every entry in it or its subfolders is updated. Returns how many entries changed.
 #[command]
-fn get_categories(
+fn rename_folder(
+    path: String,
+    new_name: String,
     session: State<'_, Mutex<SessionState>>,
-) -> Vec<String> {
-    let session_state = match session.lock().map_err(|_| ErrorResponse {error: "Failed to lock session state".into(),}) {
-        Ok(state) => state,
-        Err(_) => return vec![]
-    };
+) -> Result<usize, ErrorResponse> {
+    change_session_entries(&session, |entries| folders::rename_folder(entries, &path, &new_name))
+}
 
-    let categories: HashSet<String> = session_state.password_entries.iter()
-        .filter_map(|entry| entry.category.as_ref().map(|s| s.clone()))
-        .collect();
+// command to move a folder with its subfolders into another folder, or to the top level when `new_parent` is None
+#[command]
+fn move_folder(
+    path: String,
+    new_parent: Option<String>,
+    session: State<'_, Mutex<SessionState>>,
+) -> Result<usize, ErrorResponse> {
+    change_session_entries(&session, |entries| folders::move_folder(entries, &path, new_parent.as_deref()))
+}
 
-    categories.into_iter().collect()
+// command to remove a folder; its entries and subfolders move up into its parent
+#[command]
+fn delete_folder(path: String, session: State<'_, Mutex<SessionState>>) -> Result<usize, ErrorResponse> {
+    change_session_entries(&session, |entries| folders::delete_folder(entries, &path))
+}
+
+// command to rename a tag on every entry that has it
+#[command]
+fn rename_tag(tag: String, new_tag: String, session: State<'_, Mutex<SessionState>>) -> Result<usize, ErrorResponse> {
+    change_session_entries(&session, |entries| folders::rename_tag(entries, &tag, &new_tag))
+}
+
+// command to take a tag off every entry that has it
+#[command]
+fn delete_tag(tag: String, session: State<'_, Mutex<SessionState>>) -> Result<usize, ErrorResponse> {
+    change_session_entries(&session, |entries| Ok(folders::delete_tag(entries, &tag)))
 }
 
 #[command]
@@ -416,13 +1257,67 @@ fn get_favorites(
 
     let favorites: Vec<DecryptedPasswordEntry> = session_state.password_entries
         .iter()
-        .filter(|entry| entry.favorite)
+        .filter(|entry| entry.favorite && !entry.is_trashed())
         .cloned()
         .collect();
 
     favorites
 }
 
+// Matches titles, usernames, urls, notes and the non-sensitive fields of cards, identities and
+// accounts; `kind` narrows the results to one entry type
+#[command]
+fn search_password_entries(
+    query: String,
+    kind: Option<EntryKind>,
+    session: State<'_, Mutex<SessionState>>,
+) -> Vec<DecryptedPasswordEntry> {
+
+    let session_state = match session.lock() {
+        Ok(state) => state,
+        Err(_) => return vec![],
+    };
+
+    password_entry::search_entries(&session_state.password_entries, &query, kind)
+        .into_iter()
+        .cloned()
+        .collect()
+}
+
+// command to find the entries whose URLs match a page by their match rules, e.g. to offer them for filling in a login
+#[command]
+fn find_entries_for_url(
+    url: String,
+    session: State<'_, Mutex<SessionState>>,
+) -> Result<Vec<DecryptedPasswordEntry>, ErrorResponse> {
+    let session_state = session.lock().map_err(|_| ErrorResponse {
+        error: "Failed to lock session state".into(),
+    })?;
+    Ok(session_state
+        .password_entries
+        .iter()
+        .filter(|entry| !entry.is_trashed() && entry.matches_url(&url))
+        .cloned()
+        .collect())
+}
+
+// command to list the entries whose passwords are expired or expire within the warning period, soonest first
+#[command]
+fn get_expiring_entries(session: State<'_, Mutex<SessionState>>) -> Result<Vec<ExpiryReminder>, ErrorResponse> {
+    let session_state = session.lock().map_err(|_| ErrorResponse {
+        error: "Failed to lock session state".into(),
+    })?;
+    Ok(session_state.expiry_reminders())
+}
+
+// Reminders are a convenience, so a login still succeeds if the event can't be sent
+fn emit_expiry_reminders(app: &AppHandle, session_state: &SessionState) {
+    let reminders = session_state.expiry_reminders();
+    if !reminders.is_empty() {
+        let _ = app.emit_all(EXPIRY_REMINDERS_EVENT, reminders);
+    }
+}
+
 #[command]
 fn get_session_state(
     session: State<'_, Mutex<SessionState>>,
@@ -433,15 +1328,254 @@ fn get_session_state(
     Ok(session_state.clone())
 }
 
+// command to move an entry to the trash. It keeps its attachments and can be restored until the retention period in the user settings is up.
 #[command]
 fn remove_password_entry(
     entry_id: String,
     session: State<'_, Mutex<SessionState>>,
+) -> Result<(), ErrorResponse> {
+    find_session_entry(&session, &entry_id, |entry| {
+        if !entry.is_trashed() {
+            entry.move_to_trash();
+        }
+        Ok(())
+    })
+}
+
+// command to list the entries in the trash, most recently removed first
+#[command]
+fn get_trash(session: State<'_, Mutex<SessionState>>) -> Result<Vec<DecryptedPasswordEntry>, ErrorResponse> {
+    let session_state = session.lock().map_err(|_| ErrorResponse {
+        error: "Failed to lock session state".into(),
+    })?;
+    let mut trashed: Vec<DecryptedPasswordEntry> = session_state
+        .password_entries
+        .iter()
+        .filter(|entry| entry.is_trashed())
+        .cloned()
+        .collect();
+    trashed.sort_by(|a, b| b.deleted.cmp(&a.deleted));
+    Ok(trashed)
+}
+
+// command to take an entry back out of the trash
+#[command]
+fn restore_password_entry(
+    entry_id: String,
+    session: State<'_, Mutex<SessionState>>,
+) -> Result<(), ErrorResponse> {
+    find_session_entry(&session, &entry_id, |entry| {
+        if !entry.is_trashed() {
+            return Err(CryptoError::PasswordEntryNotFound);
+        }
+        entry.restore_from_trash();
+        Ok(())
+    })
+}
+
+// command to delete an entry in the trash for good, along with its attachments
+#[command]
+fn delete_password_entry(
+    entry_id: String,
+    session: State<'_, Mutex<SessionState>>,
+    state: State<'_, AppConfig>,
+    config: State<'_, Config>,
+) -> Result<(), ErrorResponse> {
+    purge_session_entries(&session, &state, &config, |entry| entry.id == entry_id && entry.is_trashed())
+}
+
+// command to delete everything in the trash for good
+#[command]
+fn empty_trash(
+    session: State<'_, Mutex<SessionState>>,
+    state: State<'_, AppConfig>,
+    config: State<'_, Config>,
+) -> Result<(), ErrorResponse> {
+    purge_session_entries(&session, &state, &config, |entry| entry.is_trashed())
+}
+
+// removes the session's entries matching `purge` and deletes their attachment blobs
+fn purge_session_entries(
+    session: &State<'_, Mutex<SessionState>>,
+    state: &State<'_, AppConfig>,
+    config: &State<'_, Config>,
+    purge: impl Fn(&DecryptedPasswordEntry) -> bool,
+) -> Result<(), ErrorResponse> {
+    let attachments_dir = state
+        .attachments_dir(config.inner())
+        .map_err(|e| ErrorResponse::from(CryptoError::IO(e)))?;
+    let mut session_state = session.lock().map_err(|_| ErrorResponse {
+        error: "Failed to lock session state".into(),
+    })?;
+    for entry in session_state.password_entries.iter().filter(|entry| purge(entry)) {
+        for entry_attachment in &entry.attachments {
+            attachment::delete_attachment(&attachments_dir, &entry_attachment.id)?;
+        }
+    }
+    session_state.password_entries.retain(|entry| !purge(entry));
+    Ok(())
+}
+
+// command to encrypt a file into the attachments directory and attach it to an entry. The file is streamed, so large ones are never held in memory whole.
+#[command]
+async fn add_attachment(
+    entry_id: String,
+    file_path: String,
+    session: State<'_, Mutex<SessionState>>,
+    state: State<'_, AppConfig>,
+    config: State<'_, Config>,
+) -> Result<DecryptedAttachment, ErrorResponse> {
+    let attachments_dir = state
+        .attachments_dir(config.inner())
+        .map_err(|e| ErrorResponse::from(CryptoError::IO(e)))?;
+    let mek = session_mek(&session)?;
+    find_session_entry(&session, &entry_id, |_| Ok(()))?;
+
+    let new_attachment = {
+        let attachments_dir = attachments_dir.clone();
+        tokio::task::spawn_blocking(move || attachment::add_attachment(&attachments_dir, Path::new(&file_path), &mek))
+            .await
+            .map_err(|e| ErrorResponse {
+                error: e.to_string(),
+            })?
+            .map_err(|e| ErrorResponse::from(e))?
+    };
+
+    // the entry may have been removed while the file was being encrypted
+    let added = find_session_entry(&session, &entry_id, |entry| {
+        entry.attachments.push(new_attachment.clone());
+        entry.touch();
+        Ok(())
+    });
+    if added.is_err() {
+        let _ = attachment::delete_attachment(&attachments_dir, &new_attachment.id);
+    }
+    added.map(|_| new_attachment)
+}
+
+// command to decrypt one of an entry's attachments to a file the user picked
+#[command]
+async fn export_attachment(
+    entry_id: String,
+    attachment_id: String,
+    file_path: String,
+    session: State<'_, Mutex<SessionState>>,
+    state: State<'_, AppConfig>,
+    config: State<'_, Config>,
+) -> Result<(), ErrorResponse> {
+    let attachments_dir = state
+        .attachments_dir(config.inner())
+        .map_err(|e| ErrorResponse::from(CryptoError::IO(e)))?;
+    let mek = session_mek(&session)?;
+    find_session_entry(&session, &entry_id, |entry| {
+        if entry.attachments.iter().any(|a| a.id == attachment_id) {
+            Ok(())
+        } else {
+            Err(CryptoError::AttachmentNotFound)
+        }
+    })?;
+
+    tokio::task::spawn_blocking(move || {
+        attachment::export_attachment(&attachments_dir, &attachment_id, Path::new(&file_path), &mek)
+    })
+    .await
+    .map_err(|e| ErrorResponse {
+        error: e.to_string(),
+    })?
+    .map_err(|e| ErrorResponse::from(e))?;
+    Ok(())
+}
+
+// command to remove an attachment from an entry and delete its encrypted file
+#[command]
+fn delete_attachment(
+    entry_id: String,
+    attachment_id: String,
+    session: State<'_, Mutex<SessionState>>,
+    state: State<'_, AppConfig>,
+    config: State<'_, Config>,
+) -> Result<(), ErrorResponse> {
+    let attachments_dir = state
+        .attachments_dir(config.inner())
+        .map_err(|e| ErrorResponse::from(CryptoError::IO(e)))?;
+    find_session_entry(&session, &entry_id, |entry| {
+        let index = entry
+            .attachments
+            .iter()
+            .position(|a| a.id == attachment_id)
+            .ok_or(CryptoError::AttachmentNotFound)?;
+        attachment::delete_attachment(&attachments_dir, &attachment_id)?;
+        entry.attachments.remove(index);
+        entry.touch();
+        Ok(())
+    })
+}
+
+// command to list an entry's previous passwords, newest first
+#[command]
+fn get_password_history(
+    entry_id: String,
+    session: State<'_, Mutex<SessionState>>,
+) -> Result<Vec<PasswordHistoryItem>, ErrorResponse> {
+    let mut history = Vec::new();
+    find_session_entry(&session, &entry_id, |entry| {
+        history = entry.password_history.clone();
+        Ok(())
+    })?;
+    Ok(history)
+}
+
+// command to make a previous password current again; the replaced one moves into the history. Returns the updated entry.
+#[command]
+fn restore_password(
+    entry_id: String,
+    index: usize,
+    session: State<'_, Mutex<SessionState>>,
+) -> Result<DecryptedPasswordEntry, ErrorResponse> {
+    let mut restored = None;
+    find_session_entry(&session, &entry_id, |entry| {
+        entry.restore_password(index)?;
+        restored = Some(entry.clone());
+        Ok(())
+    })?;
+    restored.ok_or_else(|| ErrorResponse::from(CryptoError::PasswordEntryNotFound))
+}
+
+// command for when the frontend copies or fills an entry's password
+#[command]
+fn mark_password_used(entry_id: String, session: State<'_, Mutex<SessionState>>) -> Result<(), ErrorResponse> {
+    find_session_entry(&session, &entry_id, |entry| {
+        entry.mark_used();
+        Ok(())
+    })
+}
+
+// runs `change` on all of the session's entries, trashed ones included
+fn change_session_entries(
+    session: &State<'_, Mutex<SessionState>>,
+    change: impl FnOnce(&mut [DecryptedPasswordEntry]) -> Result<usize, CryptoError>,
+) -> Result<usize, ErrorResponse> {
+    let mut session_state = session.lock().map_err(|_| ErrorResponse {
+        error: "Failed to lock session state".into(),
+    })?;
+    Ok(change(&mut session_state.password_entries)?)
+}
+
+// runs `change` on the session's entry with `entry_id`
+fn find_session_entry(
+    session: &State<'_, Mutex<SessionState>>,
+    entry_id: &str,
+    change: impl FnOnce(&mut DecryptedPasswordEntry) -> Result<(), CryptoError>,
 ) -> Result<(), ErrorResponse> {
     let mut session_state = session.lock().map_err(|_| ErrorResponse {
         error: "Failed to lock session state".into(),
     })?;
-    session_state.password_entries.retain(|entry| entry.id != entry_id);
+    let entry = session_state
+        .password_entries
+        .iter_mut()
+        .find(|entry| entry.id == entry_id)
+        .ok_or(CryptoError::PasswordEntryNotFound)?;
+    change(entry)?;
     Ok(())
 }
 
@@ -457,6 +1591,31 @@ fn main() {
             check_application_data_existence,
             create_master_password,
             change_master_password,
+            create_recovery_key,
+            recover_with_key,
+            list_key_slots,
+            remove_key_slot,
+            generate_key_file,
+            enable_key_file,
+            disable_key_file,
+            create_secret_shares,
+            login_with_shares,
+            recover_with_shares,
+            enable_duress_password,
+            disable_duress_password,
+            import_bitwarden,
+            import_kdbx,
+            import_1pux,
+            import_pass,
+            merge_vault_file,
+            preview_csv_import,
+            import_csv,
+            export_kdbx,
+            export_portable,
+            export_plaintext,
+            import_portable,
+            commit_import,
+            cancel_import,
             login,
             logout,
             get_password_entries,
@@ -467,10 +1626,29 @@ fn main() {
             generate_password,
             save_session_state,
             get_password_entry,
-            get_categories,
+            get_folders,
+            get_tags,
+            rename_folder,
+            move_folder,
+            delete_folder,
+            rename_tag,
+            delete_tag,
             get_favorites,
+            search_password_entries,
+            find_entries_for_url,
+            get_expiring_entries,
             get_session_state,
             remove_password_entry,
+            add_attachment,
+            export_attachment,
+            delete_attachment,
+            get_password_history,
+            restore_password,
+            mark_password_used,
+            get_trash,
+            restore_password_entry,
+            delete_password_entry,
+            empty_trash,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
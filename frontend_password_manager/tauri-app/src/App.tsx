@@ -1,4 +1,6 @@
 import { useEffect, useState } from 'react';
+import { listen } from '@tauri-apps/api/event';
+import { toast } from 'react-toastify';
 import { BrowserRouter as Router, Routes, Route, Navigate } from 'react-router-dom';
 import LoginPage from './pages/LoginPage';
 import DashboardPage from './pages/DashboardPage';
@@ -6,6 +8,7 @@ import MasterPasswordSetupPage from './pages/MasterPasswordSetupPage';
 import ChangeMasterPasswordPage from './pages/ChangeMasterPasswordPage';
 import { checkApplicationDataExistence } from './utils/api'; 
 import PasswordEntryPage from './pages/PasswordEntryPage';
+import { ExpiryReminder } from './hooks/useSession';
 
 function App() {
   const [initialRoute, setInitialRoute] = useState('/');
@@ -31,6 +34,23 @@ function App() {
     initializeApp();
   }, []);
 
+  // the backend sends reminders right after login; toasts wait until the dashboard's container is shown
+  useEffect(() => {
+    const unlisten = listen<ExpiryReminder[]>('expiry-reminders', (event) => {
+      event.payload.forEach((reminder) => {
+        const due = new Date(reminder.due).toLocaleDateString();
+        if (reminder.status === 'expired') {
+          toast.error(`The password for ${reminder.title} expired on ${due}.`);
+        } else {
+          toast.warn(`The password for ${reminder.title} expires on ${due}.`);
+        }
+      });
+    });
+    return () => {
+      unlisten.then((stop) => stop());
+    };
+  }, []);
+
   return (
     <Router>
       <Routes>
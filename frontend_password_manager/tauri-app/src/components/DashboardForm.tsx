@@ -4,20 +4,24 @@ import 'react-toastify/dist/ReactToastify.css';
 import React, { useState, useEffect } from 'react';
 import { useNavigate } from 'react-router-dom';
 import { useSession } from '../hooks/useSession';
-import { DecryptedPasswordEntry } from '../hooks/useSession';
-import { getCategories } from '../utils/api';
+import { DecryptedPasswordEntry, Folder, TagCount } from '../hooks/useSession';
+import { getFolders, getTags, markPasswordUsed } from '../utils/api';
 import { toast, ToastContainer } from 'react-toastify';
 import { confirmAlert } from 'react-confirm-alert';
 
 
-
+// every folder path in the tree, parents before their children
+const flattenFolders = (folders: Folder[]): Folder[] =>
+    folders.flatMap((folder) => [folder, ...flattenFolders(folder.children)]);
 
 const DashboardForm: React.FC = () => {
     const { sessionState, fetchSessionData, removePasswordEntry } = useSession();
     const [searchTerm, setSearchTerm] = useState('');
-    const [selectedCategory, setSelectedCategory] = useState('');
+    const [selectedFolder, setSelectedFolder] = useState('');
+    const [selectedTag, setSelectedTag] = useState('');
     const [filteredEntries, setFilteredEntries] = useState<DecryptedPasswordEntry[]>([]);
-    const [categories, setCategories] = useState<string[]>([]); 
+    const [folders, setFolders] = useState<Folder[]>([]);
+    const [tags, setTags] = useState<TagCount[]>([]);
     const [showFavorites, setShowFavorites] = useState(false);
 
     const navigate = useNavigate();
@@ -27,18 +31,27 @@ const DashboardForm: React.FC = () => {
     }, [fetchSessionData]);
 
     useEffect(() => {
-        getCategories().then(setCategories);
-    }, []);
+        getFolders().then((tree) => setFolders(flattenFolders(tree)));
+        getTags().then(setTags);
+    }, [sessionState.passwordEntries]);
 
     useEffect(() => {
-        let filtered = sessionState.passwordEntries.filter((entry) =>
+        let filtered = sessionState.passwordEntries.filter((entry) => !entry.deleted).filter((entry) =>
             entry.title.toLowerCase().includes(searchTerm.toLowerCase()) ||
             entry.username?.toLowerCase().includes(searchTerm.toLowerCase()) ||
-            entry.category?.toLowerCase().includes(searchTerm.toLowerCase())
+            entry.folder?.toLowerCase().includes(searchTerm.toLowerCase()) ||
+            entry.tags.some((tag) => tag.toLowerCase().includes(searchTerm.toLowerCase()))
         );
 
-        if (selectedCategory) {
-            filtered = filtered.filter((entry) => entry.category?.toLowerCase() === selectedCategory.toLowerCase());
+        // a folder shows its subfolders' entries too
+        if (selectedFolder) {
+            filtered = filtered.filter((entry) =>
+                entry.folder === selectedFolder || entry.folder?.startsWith(selectedFolder + '/')
+            );
+        }
+
+        if (selectedTag) {
+            filtered = filtered.filter((entry) => entry.tags.includes(selectedTag));
         }
 
         if (showFavorites) {
@@ -48,7 +61,7 @@ const DashboardForm: React.FC = () => {
         filtered.sort((a, b) => a.title.localeCompare(b.title));
 
         setFilteredEntries(filtered);
-    }, [searchTerm, selectedCategory, showFavorites, sessionState.passwordEntries]);
+    }, [searchTerm, selectedFolder, selectedTag, showFavorites, sessionState.passwordEntries]);
     
     const handleRemove = (id: string) => {
         confirmAlert({
@@ -67,9 +80,10 @@ const DashboardForm: React.FC = () => {
         });
     };
     
-    const handleCopyPassword = (password: string) => {
+    const handleCopyPassword = (id: string, password: string) => {
         navigator.clipboard.writeText(password);
         toast.success('Password copied to clipboard', { autoClose: 800 });
+        markPasswordUsed(id).catch(error => console.error('Failed to mark password as used:', error));
     };
 
     const handleCopyUsername = (username: string) => {
@@ -101,10 +115,16 @@ const DashboardForm: React.FC = () => {
                         value={searchTerm}
                         onChange={(e) => setSearchTerm(e.target.value)}
                     />
-                    <select value={selectedCategory} onChange={(e) => setSelectedCategory(e.target.value)}>
-                        <option value="">All Categories</option>
-                        {categories.map((category) => (
-                            <option key={category} value={category}>{category}</option>
+                    <select value={selectedFolder} onChange={(e) => setSelectedFolder(e.target.value)}>
+                        <option value="">All Folders</option>
+                        {folders.map((folder) => (
+                            <option key={folder.path} value={folder.path}>{folder.path}</option>
+                        ))}
+                    </select>
+                    <select value={selectedTag} onChange={(e) => setSelectedTag(e.target.value)}>
+                        <option value="">All Tags</option>
+                        {tags.map(({ tag, entryCount }) => (
+                            <option key={tag} value={tag}>{tag} ({entryCount})</option>
                         ))}
                     </select>
                     <label className="custom-checkbox">
@@ -128,21 +148,23 @@ const DashboardForm: React.FC = () => {
                                         <p>Title:</p>
                                         <p>Username:</p>
                                         <p>URL:</p>
-                                        <p>Category:</p>
+                                        <p>Folder:</p>
+                                        <p>Tags:</p>
                                         <p>Date:</p>
                                     </div>
                                     <div className="entry-data">
                                         <p>{entry.title}</p>
                                         <p>{entry.username}</p>
-                                        <p>{entry.url}</p>
-                                        <p>{entry.category}</p>
-                                        <p>{entry.creationDate && new Date(entry.creationDate).toLocaleDateString()}</p>
+                                        <p>{entry.urls[0]?.url}</p>
+                                        <p>{entry.folder}</p>
+                                        <p>{entry.tags.join(', ')}</p>
+                                        <p>{entry.created && new Date(entry.created).toLocaleDateString()}</p>
                                     </div>
                                 </div>
                                 <div className="entry-buttons">
                                     <button onClick={() => handleEdit(entry)}>Edit</button>
                                     <button onClick={() => entry.username && handleCopyUsername(entry.username)}>Copy Username</button>
-                                    <button onClick={() => entry.password && handleCopyPassword(entry.password)}>Copy Password</button>
+                                    <button onClick={() => entry.password && handleCopyPassword(entry.id, entry.password)}>Copy Password</button>
                                     <button style={{color: '#f72044'}} onClick={() => handleRemove(entry.id)}>Remove</button>
                                 </div>
                             </div>
@@ -1,11 +1,29 @@
 // src/components/PasswordEntryForm.tsx
 import React, { useState, useEffect } from 'react';
-import { DecryptedPasswordEntry, useSession, UserSettings } from '../hooks/useSession';
+import { DecryptedPasswordEntry, EntryUrl, UrlMatch, useSession, UserSettings } from '../hooks/useSession';
 import { createNewDecryptedPasswordEntry } from '../utils/api';
 import { useNavigate, useLocation } from 'react-router-dom';
 import 'react-toastify/dist/ReactToastify.css';
 import { toast, ToastContainer } from 'react-toastify';
 
+const URL_MATCHES: { value: UrlMatch; label: string }[] = [
+  { value: 'baseDomain', label: 'Base domain' },
+  { value: 'host', label: 'Host' },
+  { value: 'startsWith', label: 'Starts with' },
+  { value: 'exact', label: 'Exact' },
+  { value: 'regex', label: 'Regular expression' },
+  { value: 'never', label: 'Never' },
+];
+
+// the tags field is comma separated; the backend trims them and drops repeats
+const splitTags = (tags: string): string[] =>
+  tags.split(',').map((tag) => tag.trim()).filter((tag) => tag.length > 0);
+
+// the expiry input is a plain date, taken as midnight local time
+const expiryDate = (expires?: string): string => (expires ? expires.slice(0, 10) : '');
+const expiryTimestamp = (date: string): string | undefined =>
+  date ? new Date(`${date}T00:00:00`).toISOString() : undefined;
+const rotationInterval = (days: string): number | undefined => (Number(days) > 0 ? Number(days) : undefined);
 
 function PasswordEntryForm() {
   const location = useLocation();
@@ -27,10 +45,13 @@ function PasswordEntryForm() {
   const [title, setTitle] = useState(initialEntry?.title || '');
   const [username, setUsername] = useState(initialEntry?.username || '');
   const [password, setPassword] = useState(initialEntry?.password || '');
-  const [url, setUrl] = useState(initialEntry?.url || '');
+  const [urls, setUrls] = useState<EntryUrl[]>(initialEntry?.urls || []);
   const [notes, setNotes] = useState(initialEntry?.notes || '');
-  const [category, setCategory] = useState(initialEntry?.category || '');
+  const [folder, setFolder] = useState(initialEntry?.folder || '');
+  const [tags, setTags] = useState(initialEntry?.tags.join(', ') || '');
   const [favorite, setFavorite] = useState(initialEntry?.favorite || false);
+  const [expires, setExpires] = useState(expiryDate(initialEntry?.expires));
+  const [rotationDays, setRotationDays] = useState(initialEntry?.rotationDays?.toString() || '');
 
   const [settings, setSettings] = useState(sessionState.userSettings);
   const [entry, setEntry] = useState<DecryptedPasswordEntry | null>(initialEntry || null);
@@ -42,10 +63,13 @@ function PasswordEntryForm() {
     setTitle(initialEntry?.title || '');
     setUsername(initialEntry?.username || '');
     setPassword(initialEntry?.password || '');
-    setUrl(initialEntry?.url || '');
+    setUrls(initialEntry?.urls || []);
     setNotes(initialEntry?.notes || '');
-    setCategory(initialEntry?.category || '');
+    setFolder(initialEntry?.folder || '');
+    setTags(initialEntry?.tags.join(', ') || '');
     setFavorite(initialEntry?.favorite || false);
+    setExpires(expiryDate(initialEntry?.expires));
+    setRotationDays(initialEntry?.rotationDays?.toString() || '');
   }, [initialEntry]);
 
   const validateSettings = (settings: UserSettings) => {
@@ -78,6 +102,10 @@ function PasswordEntryForm() {
   };
 
 
+  const handleUrlChange = (index: number, change: Partial<EntryUrl>) => {
+    setUrls((previous) => previous.map((entryUrl, i) => (i === index ? { ...entryUrl, ...change } : entryUrl)));
+  };
+
   const handleChange = (event: React.ChangeEvent<HTMLInputElement | HTMLTextAreaElement>) => {
     console.log('handleChange called', event.target);
     const { name, value, type } = event.target;
@@ -93,18 +121,24 @@ function PasswordEntryForm() {
       case 'password':
         setPassword(newValue as string);
         break;
-      case 'url':
-        setUrl(newValue as string);
-        break;
       case 'notes':
         setNotes(newValue as string);
         break;
-      case 'category':
-        setCategory(newValue as string);
+      case 'folder':
+        setFolder(newValue as string);
+        break;
+      case 'tags':
+        setTags(newValue as string);
         break;
       case 'favorite':
         setFavorite(newValue as boolean);
         break;
+      case 'expires':
+        setExpires(newValue as string);
+        break;
+      case 'rotationDays':
+        setRotationDays(newValue as string);
+        break;
       default:
         break;
     }
@@ -178,10 +212,13 @@ function PasswordEntryForm() {
           title,
           username,
           password,
-          url,
+          urls: urls.filter((entryUrl) => entryUrl.url.trim().length > 0),
           notes,
-          category,
+          folder,
+          tags: splitTags(tags),
           favorite,
+          expires: expiryTimestamp(expires),
+          rotationDays: rotationInterval(rotationDays),
         };
       } else {
         console.log('Creating new entry');
@@ -191,10 +228,13 @@ function PasswordEntryForm() {
           title,
           username,
           password,
-          url,
+          urls: urls.filter((entryUrl) => entryUrl.url.trim().length > 0),
           notes,
-          category,
+          folder,
+          tags: splitTags(tags),
           favorite,
+          expires: expiryTimestamp(expires),
+          rotationDays: rotationInterval(rotationDays),
         };
       }
       await addPasswordEntry(newEntry);
@@ -249,10 +289,13 @@ function PasswordEntryForm() {
     title,
     username,
     password,
-    url,
+    urls,
     notes,
-    category,
+    folder,
+    tags,
     favorite,
+    expires,
+    rotationDays,
     settings,
     entry,
     titleError,
@@ -280,21 +323,51 @@ function PasswordEntryForm() {
           <button type="button" onClick={handleCopyPassword}>Copy</button>
         </label>
         <label>
-          URL
-          <input type="text" name="url" value={url} onChange={handleChange} />
+          URLs
+          {urls.map((entryUrl, index) => (
+            <div key={index}>
+              <input
+                type="text"
+                value={entryUrl.url}
+                onChange={(e) => handleUrlChange(index, { url: e.target.value })}
+              />
+              <select
+                value={entryUrl.matchMode}
+                onChange={(e) => handleUrlChange(index, { matchMode: e.target.value as UrlMatch })}
+              >
+                {URL_MATCHES.map(({ value, label }) => (
+                  <option key={value} value={value}>{label}</option>
+                ))}
+              </select>
+              <button type="button" onClick={() => setUrls(urls.filter((_, i) => i !== index))}>Remove</button>
+            </div>
+          ))}
+          <button type="button" onClick={() => setUrls([...urls, { url: '', matchMode: 'baseDomain' }])}>Add URL</button>
         </label>
         <label>
           Notes
           <textarea name="notes" value={notes} onChange={handleChange} />
         </label>
         <label>
-          Category
-          <input type="text" name="category" value={category} onChange={handleChange} />
+          Folder
+          <input type="text" name="folder" value={folder} placeholder="e.g. Work/Servers" onChange={handleChange} />
+        </label>
+        <label>
+          Tags
+          <input type="text" name="tags" value={tags} placeholder="comma separated" onChange={handleChange} />
         </label>
         <label>
           Favorite
           <input type="checkbox" name="favorite" checked={favorite} onChange={handleChange} />
         </label>
+        <label>
+          Expires
+          <input type="date" name="expires" value={expires} onChange={handleChange} />
+        </label>
+        <label>
+          Change every (days)
+          <input type="number" name="rotationDays" min="0" max="36500" value={rotationDays} placeholder="e.g. 90" onChange={handleChange} />
+        </label>
       </div>
       
       {/* Settings fields */}
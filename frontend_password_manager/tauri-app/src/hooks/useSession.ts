@@ -18,6 +18,8 @@ export interface UserSettings {
     minSymbol: number;
     useLower: boolean;
     useUpper: boolean;
+    trashRetentionDays: number; // removed entries are purged on unlock after this many days
+    expiryWarningDays: number; // entries expiring within this many days get a reminder
 }
 
 export interface DecryptedPasswordEntry {
@@ -25,11 +27,224 @@ export interface DecryptedPasswordEntry {
     title: string;
     username?: string;
     password?: string;
-    url?: string;
+    urls: EntryUrl[]; // the first one is shown in lists
     notes?: string;
-    creationDate: string; // date string (not set by user)
-    category?: string;
+    // RFC 3339 dates, set by the backend only
+    created: string;
+    modified?: string; // unset until the entry is first edited
+    passwordChanged?: string; // unset until the first password is replaced
+    lastUsed?: string; // see markPasswordUsed
+    deleted?: string; // set while the entry is in the trash
+    // the password is due for changing at `expires` or `rotationDays` after it was last changed, whichever is first
+    expires?: string; // RFC 3339 date
+    rotationDays?: number;
+    folder?: string; // "/"-separated path, e.g. "Work/Servers"
+    tags: string[];
     favorite: boolean;
+    kind: EntryKind; // 'login' for entries created before kinds existed
+    details?: EntryDetails; // present for every kind except 'login'
+    customFields: CustomField[]; // in display order
+    attachments: Attachment[]; // read-only here; use addAttachment/deleteAttachment
+    passwordHistory: PasswordHistoryItem[]; // newest first, at most 10; read-only here
+}
+
+// How a URL is matched against a page: 'baseDomain' (login.example.com matches www.example.com, by
+// the public suffix list), 'host' (same host and port), 'startsWith' (same origin and URL prefix),
+// 'exact', 'regex' (the whole page URL) or 'never'
+export type UrlMatch = 'baseDomain' | 'host' | 'startsWith' | 'exact' | 'regex' | 'never';
+
+export interface EntryUrl {
+    url: string;
+    matchMode: UrlMatch;
+}
+
+export type ExpiryStatus = 'expired' | 'expiringSoon';
+
+// sent with the 'expiry-reminders' event after login and returned by getExpiringEntries
+export interface ExpiryReminder {
+    id: string;
+    title: string;
+    due: string; // RFC 3339 date
+    status: ExpiryStatus;
+}
+
+export interface Folder {
+    name: string;
+    path: string; // full path, used to refer to the folder in the folder commands
+    entryCount: number; // entries directly in this folder
+    children: Folder[];
+}
+
+export interface TagCount {
+    tag: string;
+    entryCount: number;
+}
+
+export interface PasswordHistoryItem {
+    password: string;
+    replacedAt: string; // RFC 3339 date
+}
+
+export interface Attachment {
+    id: string;
+    name: string; // original file name
+    size: number; // bytes
+    added: string; // RFC 3339 date
+}
+
+export type CustomFieldType = 'text' | 'hidden' | 'url' | 'boolean';
+
+export interface CustomField {
+    name: string;
+    type: CustomFieldType;
+    value: string; // 'true' or 'false' for boolean fields; hidden values are encrypted at rest
+}
+
+export type EntryKind = 'login' | 'secureNote' | 'paymentCard' | 'identity' | 'bankAccount';
+
+export interface SecureNoteDetails {
+    kind: 'secureNote';
+    text: string;
+}
+
+export interface PaymentCardDetails {
+    kind: 'paymentCard';
+    cardholderName?: string;
+    brand?: string;
+    number?: string;
+    expiryMonth?: number; // 1-12
+    expiryYear?: number; // four digits
+    cvv?: string;
+}
+
+export interface IdentityDetails {
+    kind: 'identity';
+    fullName?: string;
+    email?: string;
+    phone?: string;
+    addressLine1?: string;
+    addressLine2?: string;
+    city?: string;
+    region?: string;
+    postalCode?: string;
+    country?: string;
+}
+
+export interface BankAccountDetails {
+    kind: 'bankAccount';
+    bankName?: string;
+    accountHolder?: string;
+    accountNumber?: string;
+    routingNumber?: string;
+    iban?: string;
+    swift?: string;
+}
+
+export type EntryDetails = SecureNoteDetails | PaymentCardDetails | IdentityDetails | BankAccountDetails;
+
+export type KeySlotKind = 'masterPassword' | 'recoveryKey' | 'secretShares';
+
+export interface KeySlotInfo {
+    id: string;
+    label: string;
+    kind: KeySlotKind;
+    kdfIterations: number;
+    requiresKeyFile: boolean;
+}
+
+export interface SkippedItem {
+    name: string;
+    reason: string;
+}
+
+export interface ImportReport {
+    imported: number;
+    replaced: number;
+    skipped: SkippedItem[];
+}
+
+export interface EntryConflict {
+    id: string;
+    title: string;
+    oursModified: string;
+    theirsModified: string;
+    kept: 'ours' | 'theirs';
+}
+
+export interface LeftOutAttachment {
+    entryId: string;
+    entryTitle: string;
+    name: string;
+}
+
+export interface VaultMergeReport {
+    added: number;
+    updated: number;
+    unchanged: number;
+    conflicts: EntryConflict[];
+    leftOutAttachments: LeftOutAttachment[];
+}
+
+export type PlaintextFormat = 'json' | 'csv';
+
+export type MergeMode = 'skipExisting' | 'overwrite' | 'keepBoth';
+
+export interface MergeOptions {
+    defaultMode: MergeMode;
+    perEntry?: Record<string, MergeMode>;
+}
+
+export interface ImportConflict {
+    incomingId: string;
+    incomingTitle: string;
+    existingId: string;
+    existingTitle: string;
+    url?: string;
+    username?: string;
+    samePassword: boolean;
+}
+
+export interface StagedImport {
+    pending: number;
+    conflicts: ImportConflict[];
+    skipped: SkippedItem[];
+}
+
+export interface ColumnMapping {
+    title?: string;
+    username?: string;
+    password?: string;
+    url?: string;
+    notes?: string;
+    folder?: string;
+    favorite?: string;
+}
+
+export type CsvPreset =
+    | { kind: 'chrome' }
+    | { kind: 'firefox' }
+    | { kind: 'edge' }
+    | { kind: 'lastPass' }
+    | { kind: 'custom'; mapping: ColumnMapping };
+
+export interface CsvImportOptions {
+    preset: CsvPreset;
+    encoding?: string;
+}
+
+export interface CsvPreviewRow {
+    title: string;
+    username?: string;
+    url?: string;
+    folder?: string;
+    hasPassword: boolean;
+}
+
+export interface CsvPreview {
+    headers: string[];
+    rows: CsvPreviewRow[];
+    totalRows: number;
+    skipped: SkippedItem[];
 }
 
 export interface SessionState {
@@ -46,6 +261,8 @@ const defaultUserSettings: UserSettings = {
     minSymbol: 2,
     useLower: true,
     useUpper: true,
+    trashRetentionDays: 30,
+    expiryWarningDays: 14,
 };
 
 export const useSession = () => {
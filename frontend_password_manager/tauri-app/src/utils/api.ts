@@ -1,7 +1,7 @@
 // src/utils/api.ts
 
 import { invoke } from '@tauri-apps/api/tauri';
-import { DecryptedPasswordEntry, UserSettings, SessionState } from '../hooks/useSession';
+import { DecryptedPasswordEntry, UserSettings, SessionState, KeySlotInfo, ImportReport, CsvImportOptions, CsvPreview, MergeOptions, StagedImport, PlaintextFormat, VaultMergeReport, EntryKind, Attachment, PasswordHistoryItem, Folder, TagCount, ExpiryReminder } from '../hooks/useSession';
 
 /**
  * Checks if the application data file exists and verifies hashes.
@@ -13,26 +13,137 @@ export const checkApplicationDataExistence = async (): Promise<boolean> => {
 /**
  * Creates a new master password and initializes application data.
  * @param password The new master password to be set.
+ * @param enableRecoveryKey Whether to also generate a recovery key.
+ * @returns {Promise<string | null>} The recovery key (shown to the user once) if one was requested.
  */
-export const createMasterPassword = async (password: string): Promise<void> => {
-  return await invoke('create_master_password', { password });
+export const createMasterPassword = async (password: string, enableRecoveryKey: boolean = false): Promise<string | null> => {
+  return await invoke('create_master_password', { password, enableRecoveryKey });
+};
+
+/**
+ * Generates a new recovery key, replacing any existing one.
+ * @param password The current master password.
+ * @returns {Promise<string>} A promise that resolves to the new recovery key.
+ */
+export const createRecoveryKey = async (password: string): Promise<string> => {
+  return await invoke('create_recovery_key', { password });
+};
+
+/**
+ * Sets a new master password using the recovery key, keeping all entries.
+ * @param recoveryKey The recovery key generated at setup.
+ * @param newPassword The new master password to be set.
+ */
+export const recoverWithKey = async (recoveryKey: string, newPassword: string): Promise<void> => {
+  return await invoke('recover_with_key', { recoveryKey, newPassword });
 };
 
 /**
  * Changes the master password.
  * @param oldPassword The current master password.
  * @param newPassword The new master password to be set.
+ * @param keyFilePath Path to the key file, required if the vault uses one.
+ */
+export const changeMasterPassword = async (oldPassword: string, newPassword: string, keyFilePath?: string): Promise<void> => {
+  return await invoke('change_master_password', { oldPassword, newPassword, keyFilePath });
+};
+
+/**
+ * Lists the key slots that can unlock the vault.
+ * @returns {Promise<KeySlotInfo[]>} A promise that resolves to the key slots (no secret material).
+ */
+export const listKeySlots = async (): Promise<KeySlotInfo[]> => {
+  return await invoke('list_key_slots');
+};
+
+/**
+ * Removes a key slot. The last remaining slot can't be removed.
+ * @param password The current master password.
+ * @param slotId The ID of the key slot to remove.
+ */
+export const removeKeySlot = async (password: string, slotId: string): Promise<void> => {
+  return await invoke('remove_key_slot', { password, slotId });
+};
+
+/**
+ * Splits a new recovery secret into printable shares, any `threshold` of which unlock the vault.
+ * @param password The current master password.
+ * @param threshold How many shares are needed to unlock.
+ * @param shareCount How many shares to create.
+ * @returns {Promise<string[]>} A promise that resolves to the shares (only shown once).
+ */
+export const createSecretShares = async (password: string, threshold: number, shareCount: number): Promise<string[]> => {
+  return await invoke('create_secret_shares', { password, threshold, shareCount });
+};
+
+/**
+ * Opens a session using enough secret shares instead of the master password.
+ * @param shares The shares entered by the shareholders.
+ */
+export const loginWithShares = async (shares: string[]): Promise<void> => {
+  return await invoke('login_with_shares', { shares });
+};
+
+/**
+ * Sets a new master password using enough secret shares, keeping all entries.
+ * @param shares The shares entered by the shareholders.
+ * @param newPassword The new master password to be set.
+ */
+export const recoverWithShares = async (shares: string[], newPassword: string): Promise<void> => {
+  return await invoke('recover_with_shares', { shares, newPassword });
+};
+
+/**
+ * Sets up or replaces the duress password. The current vault is hidden and an empty decoy,
+ * opened by the duress password, takes its place. Logs the user out.
+ * @param password The real master password.
+ * @param duressPassword The password that should open the decoy vault.
+ */
+export const enableDuressPassword = async (password: string, duressPassword: string): Promise<void> => {
+  return await invoke('enable_duress_password', { password, duressPassword });
+};
+
+/**
+ * Removes the duress password and discards the decoy vault. Logs the user out.
+ * @param password The real master password.
+ */
+export const disableDuressPassword = async (password: string): Promise<void> => {
+  return await invoke('disable_duress_password', { password });
+};
+
+/**
+ * Writes a new random key file to the given path.
+ * @param filePath Where to save the key file.
+ */
+export const generateKeyFile = async (filePath: string): Promise<void> => {
+  return await invoke('generate_key_file', { filePath });
+};
+
+/**
+ * Requires the key file in addition to the master password from now on.
+ * @param password The current master password.
+ * @param keyFilePath Path to the key file.
+ */
+export const enableKeyFile = async (password: string, keyFilePath: string): Promise<void> => {
+  return await invoke('enable_key_file', { password, keyFilePath });
+};
+
+/**
+ * Stops requiring a key file. The current key file is needed to do this.
+ * @param password The current master password.
+ * @param keyFilePath Path to the current key file.
  */
-export const changeMasterPassword = async (oldPassword: string, newPassword: string): Promise<void> => {
-  return await invoke('change_master_password', { oldPassword, newPassword });
+export const disableKeyFile = async (password: string, keyFilePath: string): Promise<void> => {
+  return await invoke('disable_key_file', { password, keyFilePath });
 };
 
 /**
  * Attempts to log in with the provided master password.
  * @param password The master password for authentication.
+ * @param keyFilePath Path to the key file, required if the vault uses one.
  */
-export const login = async (password: string): Promise<void> => {
-  return await invoke('login', { password });
+export const login = async (password: string, keyFilePath?: string): Promise<void> => {
+  return await invoke('login', { password, keyFilePath });
 };
 
 /**
@@ -69,6 +180,128 @@ export const addPasswordEntry = async (passwordEntry: DecryptedPasswordEntry): P
   return await invoke('add_password_entry', { passwordEntry });
 };
 
+/**
+ * Imports an unencrypted Bitwarden JSON export into the open vault.
+ * @param filePath Path of the export file.
+ * @returns {Promise<StagedImport>} The entries waiting for commitImport, duplicates of vault entries, and skipped items.
+ */
+export const importBitwarden = async (filePath: string): Promise<StagedImport> => {
+  return await invoke('import_bitwarden', { filePath });
+};
+
+/**
+ * Imports a KeePass KDBX 4 database into the open vault. Groups become categories.
+ * @param filePath Path of the .kdbx file.
+ * @param password The KeePass database password.
+ * @returns {Promise<StagedImport>} The entries waiting for commitImport, duplicates of vault entries, and skipped items.
+ */
+export const importKdbx = async (filePath: string, password: string): Promise<StagedImport> => {
+  return await invoke('import_kdbx', { filePath, password });
+};
+
+/**
+ * Imports a 1Password .1pux export into the open vault. Vault names become categories.
+ * @param filePath Path of the .1pux file.
+ * @returns {Promise<StagedImport>} The entries waiting for commitImport, duplicates of vault entries, and skipped items.
+ */
+export const import1pux = async (filePath: string): Promise<StagedImport> => {
+  return await invoke('import_1pux', { filePath });
+};
+
+/**
+ * Imports a `pass` password store. Each file is decrypted with gpg, which asks for the key's passphrase itself.
+ * @param directoryPath The store's root directory, usually ~/.password-store.
+ * @returns {Promise<StagedImport>} The entries waiting for commitImport, duplicates of vault entries, and skipped items.
+ */
+export const importPass = async (directoryPath: string): Promise<StagedImport> => {
+  return await invoke('import_pass', { directoryPath });
+};
+
+/**
+ * Merges another PawPass vault file (such as a copy from another machine) into the open vault.
+ * Entries are matched by id; when both sides edited one, the later edit is kept.
+ * @param filePath Path of the other vault file.
+ * @param password The master password of the other vault.
+ * @returns {Promise<VaultMergeReport>} Counts of added and updated entries, every entry edited on both sides, and the other vault's attachments that couldn't be brought over.
+ */
+export const mergeVaultFile = async (filePath: string, password: string): Promise<VaultMergeReport> => {
+  return await invoke('merge_vault_file', { filePath, password });
+};
+
+/**
+ * Reads a CSV export and shows what importing it would produce, without changing the vault.
+ * @param filePath Path of the .csv file.
+ * @param options The preset (or custom column mapping) and an optional encoding label.
+ * @returns {Promise<CsvPreview>} The file's headers, the first rows as they would be imported, and rows that can't be.
+ */
+export const previewCsvImport = async (filePath: string, options: CsvImportOptions): Promise<CsvPreview> => {
+  return await invoke('preview_csv_import', { filePath, options });
+};
+
+/**
+ * Imports a CSV export from a browser or LastPass, or any CSV with a custom column mapping.
+ * @param filePath Path of the .csv file.
+ * @param options The preset (or custom column mapping) and an optional encoding label.
+ * @returns {Promise<StagedImport>} The entries waiting for commitImport, duplicates of vault entries, and skipped items.
+ */
+export const importCsv = async (filePath: string, options: CsvImportOptions): Promise<StagedImport> => {
+  return await invoke('import_csv', { filePath, options });
+};
+
+/**
+ * Exports the open vault as a KeePass KDBX 4 file.
+ * @param filePath Where to write the .kdbx file.
+ * @param password The password the KeePass database will be protected with.
+ */
+export const exportKdbx = async (filePath: string, password: string): Promise<void> => {
+  return await invoke('export_kdbx', { filePath, password });
+};
+
+/**
+ * Exports the open vault to a single file encrypted under its own passphrase, for moving to another install.
+ * @param filePath Where to write the export.
+ * @param passphrase The passphrase the export will be encrypted with (not the master password).
+ */
+export const exportPortable = async (filePath: string, passphrase: string): Promise<void> => {
+  return await invoke('export_portable', { filePath, passphrase });
+};
+
+/**
+ * Writes every entry, passwords included, to an unencrypted JSON or CSV file for use with other tools.
+ * @param filePath Where to write the file.
+ * @param format 'json' or 'csv'.
+ * @param password The master password, entered again to confirm; the backend rejects the export without it.
+ */
+export const exportPlaintext = async (filePath: string, format: PlaintextFormat, password: string): Promise<void> => {
+  return await invoke('export_plaintext', { filePath, format, password });
+};
+
+/**
+ * Reads a portable export and stages its entries for commitImport.
+ * @param filePath Path of the export file.
+ * @param passphrase The passphrase the export was encrypted with.
+ * @returns {Promise<StagedImport>} The entries waiting for commitImport and duplicates of vault entries.
+ */
+export const importPortable = async (filePath: string, passphrase: string): Promise<StagedImport> => {
+  return await invoke('import_portable', { filePath, passphrase });
+};
+
+/**
+ * Adds the staged import to the open vault.
+ * @param options The mode for conflicts without their own choice, and per-entry choices keyed by the imported entry's id.
+ * @returns {Promise<ImportReport>} How many entries were added or replaced and which were skipped.
+ */
+export const commitImport = async (options: MergeOptions): Promise<ImportReport> => {
+  return await invoke('commit_import', { options });
+};
+
+/**
+ * Throws away the staged import without changing the vault.
+ */
+export const cancelImport = async (): Promise<void> => {
+  return await invoke('cancel_import');
+};
+
 
 /**
  * Updates the user settings.
@@ -121,11 +354,132 @@ export function getPasswordEntry(entryId: string): Promise<DecryptedPasswordEntr
 }
 
 /**
- * Gets all unique categories from the password entries.
- * @returns {Promise<string[]>} A promise that resolves to the array of categories.
+ * Gets the folders entries outside the trash are filed in.
+ * @returns {Promise<Folder[]>} A promise that resolves to the top-level folders, sorted by name.
+ */
+export function getFolders(): Promise<Folder[]> {
+  return invoke('get_folders');
+}
+
+/**
+ * Gets the tags on entries outside the trash.
+ * @returns {Promise<TagCount[]>} A promise that resolves to each tag with its number of entries, sorted by tag.
+ */
+export function getTags(): Promise<TagCount[]> {
+  return invoke('get_tags');
+}
+
+/**
+ * Renames a folder in place. Renaming onto an existing folder merges the two.
+ * @param {string} path - The folder's full path.
+ * @param {string} newName - The new name, without "/".
+ * @returns {Promise<number>} A promise that resolves to the number of entries changed.
+ */
+export function renameFolder(path: string, newName: string): Promise<number> {
+  return invoke('rename_folder', { path, newName });
+}
+
+/**
+ * Moves a folder and its subfolders into another folder.
+ * @param {string} path - The folder's full path.
+ * @param {string | undefined} newParent - The destination folder's path, or undefined for the top level.
+ * @returns {Promise<number>} A promise that resolves to the number of entries changed.
+ */
+export function moveFolder(path: string, newParent?: string): Promise<number> {
+  return invoke('move_folder', { path, newParent });
+}
+
+/**
+ * Removes a folder. Its entries and subfolders move up into its parent.
+ * @param {string} path - The folder's full path.
+ * @returns {Promise<number>} A promise that resolves to the number of entries changed.
+ */
+export function deleteFolder(path: string): Promise<number> {
+  return invoke('delete_folder', { path });
+}
+
+/**
+ * Renames a tag on every entry that has it.
+ * @param {string} tag - The current tag.
+ * @param {string} newTag - The new tag; entries that already have it keep just one.
+ * @returns {Promise<number>} A promise that resolves to the number of entries changed.
+ */
+export function renameTag(tag: string, newTag: string): Promise<number> {
+  return invoke('rename_tag', { tag, newTag });
+}
+
+/**
+ * Takes a tag off every entry that has it.
+ * @param {string} tag - The tag to remove.
+ * @returns {Promise<number>} A promise that resolves to the number of entries changed.
+ */
+export function deleteTag(tag: string): Promise<number> {
+  return invoke('delete_tag', { tag });
+}
+
+/**
+ * Gets the previous passwords of an entry.
+ * @param {string} entryId - The entry to look up.
+ * @returns {Promise<PasswordHistoryItem[]>} A promise that resolves to the history, newest first.
+ */
+
+export function getPasswordHistory(entryId: string): Promise<PasswordHistoryItem[]> {
+  return invoke('get_password_history', { entryId });
+}
+
+/**
+ * Makes a previous password current again. The password it replaces is added to the history.
+ * @param {string} entryId - The entry to change.
+ * @param {number} index - Position of the password in the history (0 is the newest).
+ * @returns {Promise<DecryptedPasswordEntry>} A promise that resolves to the updated entry.
+ */
+
+export function restorePassword(entryId: string, index: number): Promise<DecryptedPasswordEntry> {
+  return invoke('restore_password', { entryId, index });
+}
+
+/**
+ * Records that an entry's password was just used, e.g. copied to the clipboard. Sets its lastUsed date.
+ * @param {string} entryId - The entry whose password was used.
+ * @returns {Promise<void>} A promise that resolves once the date is set.
  */
-export function getCategories(): Promise<string[]> {
-  return invoke('get_categories');
+
+export function markPasswordUsed(entryId: string): Promise<void> {
+  return invoke('mark_password_used', { entryId });
+}
+
+/**
+ * Encrypts a file and attaches it to an entry. Files over 64 MiB are rejected.
+ * @param {string} entryId - The entry to attach the file to.
+ * @param {string} filePath - Path of the file to attach.
+ * @returns {Promise<Attachment>} A promise that resolves to the new attachment.
+ */
+
+export function addAttachment(entryId: string, filePath: string): Promise<Attachment> {
+  return invoke('add_attachment', { entryId, filePath });
+}
+
+/**
+ * Decrypts an attachment and writes it to a file.
+ * @param {string} entryId - The entry the attachment belongs to.
+ * @param {string} attachmentId - The attachment to export.
+ * @param {string} filePath - Where to write the decrypted file.
+ * @returns {Promise<void>} A promise that resolves once the file is written.
+ */
+
+export function exportAttachment(entryId: string, attachmentId: string, filePath: string): Promise<void> {
+  return invoke('export_attachment', { entryId, attachmentId, filePath });
+}
+
+/**
+ * Removes an attachment from an entry and deletes its encrypted file.
+ * @param {string} entryId - The entry the attachment belongs to.
+ * @param {string} attachmentId - The attachment to delete.
+ * @returns {Promise<void>} A promise that resolves once the attachment is gone.
+ */
+
+export function deleteAttachment(entryId: string, attachmentId: string): Promise<void> {
+  return invoke('delete_attachment', { entryId, attachmentId });
 }
 
 /**
@@ -137,6 +491,38 @@ export function getFavorites(): Promise<DecryptedPasswordEntry[]> {
   return invoke('get_favorites');
 }
 
+/**
+ * Searches the unlocked vault. Card and account numbers match on their last four digits;
+ * CVVs, passwords and full numbers are never matched.
+ * @param {string} query - The text to look for.
+ * @param {EntryKind} [kind] - Only return entries of this kind.
+ * @returns {Promise<DecryptedPasswordEntry[]>} A promise that resolves to the matching entries.
+ */
+
+export function searchPasswordEntries(query: string, kind?: EntryKind): Promise<DecryptedPasswordEntry[]> {
+  return invoke('search_password_entries', { query, kind });
+}
+
+/**
+ * Finds the entries for a page, using each entry URL's match rule. Entries in the trash are left out.
+ * @param {string} url - The page's URL.
+ * @returns {Promise<DecryptedPasswordEntry[]>} A promise that resolves to the matching entries.
+ */
+
+export function findEntriesForUrl(url: string): Promise<DecryptedPasswordEntry[]> {
+  return invoke('find_entries_for_url', { url });
+}
+
+/**
+ * Lists the entries whose passwords are expired or expire within the warning period, soonest first.
+ * The same list is sent with the 'expiry-reminders' event after login when it isn't empty.
+ * @returns {Promise<ExpiryReminder[]>} A promise that resolves to the reminders.
+ */
+
+export function getExpiringEntries(): Promise<ExpiryReminder[]> {
+  return invoke('get_expiring_entries');
+}
+
 /**
  * Fetches the current session state from the server.
  * @returns {Promise<SessionState>} A promise that resolves to the session state.
@@ -146,11 +532,49 @@ export const getSessionState = async (): Promise<SessionState> => {
 };
 
 /**
- * Removes a password entry by its ID.
+ * Moves a password entry to the trash. It can be restored until the retention period in the user settings is up.
  * @param entryId The ID of the password entry to remove.
  * @returns {Promise<void>} A promise that resolves when the password entry has been removed.
  */
 
 export const removePasswordEntry = async (entryId: string): Promise<void> => {
   return await invoke('remove_password_entry', { entryId });
+}
+
+/**
+ * Fetches the entries in the trash, most recently removed first.
+ * @returns {Promise<DecryptedPasswordEntry[]>} A promise that resolves to the trashed entries.
+ */
+
+export const getTrash = async (): Promise<DecryptedPasswordEntry[]> => {
+  return await invoke('get_trash');
+}
+
+/**
+ * Takes a password entry back out of the trash.
+ * @param entryId The ID of the trashed entry.
+ * @returns {Promise<void>} A promise that resolves when the entry has been restored.
+ */
+
+export const restorePasswordEntry = async (entryId: string): Promise<void> => {
+  return await invoke('restore_password_entry', { entryId });
+}
+
+/**
+ * Deletes a password entry in the trash for good, along with its attachments.
+ * @param entryId The ID of the trashed entry.
+ * @returns {Promise<void>} A promise that resolves when the entry has been deleted.
+ */
+
+export const deletePasswordEntry = async (entryId: string): Promise<void> => {
+  return await invoke('delete_password_entry', { entryId });
+}
+
+/**
+ * Deletes everything in the trash for good.
+ * @returns {Promise<void>} A promise that resolves when the trash is empty.
+ */
+
+export const emptyTrash = async (): Promise<void> => {
+  return await invoke('empty_trash');
 }
\ No newline at end of file
@@ -0,0 +1,275 @@
+// Files attached to entries. Each one is encrypted into its own blob, `<id>.bin` in the
+// attachments directory next to the vault file; entries only hold an `Attachment` pointing at it.
+//
+// Blob layout: MAGIC, a random salt, then the file in CHUNK_SIZE pieces, each sealed with
+// AES-256-GCM under a key derived (HKDF) from the MEK and the salt. A chunk's nonce is its index
+// plus a flag on the last chunk, and the attachment id is the associated data, so chunks can't be
+// reordered, dropped or moved to another attachment without decryption failing.
+use crate::crypto::{Cryptographer, FieldKey};
+use crate::entry_date;
+use crate::error_handling::CryptoError;
+use crate::password_entry::ProtectedText;
+use chrono::{DateTime, Utc};
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::hkdf::{Salt, HKDF_SHA256};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+use zeroize::{Zeroize, Zeroizing};
+
+pub const MAX_ATTACHMENT_SIZE: u64 = 64 * 1024 * 1024;
+pub const ATTACHMENTS_DIR: &str = "attachments";
+const MAGIC: &[u8; 8] = b"PAWATT01";
+const SALT_LEN: usize = 32;
+const CHUNK_SIZE: usize = 64 * 1024;
+const TAG_LEN: usize = 16;
+const KEY_INFO: &[u8] = b"pawpass attachment";
+pub(crate) const ATTACHMENT_NAME_FIELD: &str = "name";
+
+// As stored in the entry; file names can give away as much as notes, so they're encrypted too
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Attachment {
+    pub id: String,
+    pub name: ProtectedText,
+    pub size: u64,
+    #[serde(deserialize_with = "entry_date::deserialize")]
+    pub added: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DecryptedAttachment {
+    pub id: String,
+    pub name: String,
+    pub size: u64,
+    pub added: DateTime<Utc>,
+}
+
+impl Attachment {
+    pub fn decrypt(&self, mek: &[u8]) -> Result<DecryptedAttachment, CryptoError> {
+        Ok(DecryptedAttachment {
+            id: self.id.clone(),
+            name: self.name.open(&FieldKey::new(mek)?, &self.id, ATTACHMENT_NAME_FIELD)?,
+            size: self.size,
+            added: self.added,
+        })
+    }
+}
+
+impl DecryptedAttachment {
+    pub fn encrypt(&self, mek: &[u8]) -> Result<Attachment, CryptoError> {
+        Ok(Attachment {
+            id: self.id.clone(),
+            name: ProtectedText::seal(&self.name, &FieldKey::new(mek)?, &self.id, ATTACHMENT_NAME_FIELD)?,
+            size: self.size,
+            added: self.added,
+        })
+    }
+}
+
+struct StreamKey {
+    key: LessSafeKey,
+    counter: u64,
+}
+
+impl StreamKey {
+    fn new(mek: &[u8], salt: &[u8]) -> Result<Self, CryptoError> {
+        let prk = Salt::new(HKDF_SHA256, salt).extract(mek);
+        let okm = prk
+            .expand(&[KEY_INFO], &AES_256_GCM)
+            .map_err(|_| CryptoError::KeyDerivationFailed)?;
+        Ok(Self {
+            key: LessSafeKey::new(UnboundKey::from(okm)),
+            counter: 0,
+        })
+    }
+
+    fn next_nonce(&mut self, last: bool) -> Result<Nonce, CryptoError> {
+        let mut nonce: [u8; NONCE_LEN] = [0u8; NONCE_LEN];
+        nonce[2..10].copy_from_slice(&self.counter.to_be_bytes());
+        nonce[NONCE_LEN - 1] = u8::from(last);
+        self.counter = self.counter.checked_add(1).ok_or(CryptoError::CryptoOperationFailed)?;
+        Ok(Nonce::assume_unique_for_key(nonce))
+    }
+}
+
+// Reads until `buf` is full or the reader runs out, returning how much was read
+fn fill<R: Read>(reader: &mut R, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled: usize = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+// Streams `reader` into `writer` as an encrypted blob and returns the plaintext size. Only two
+// chunks are in memory at a time; reading one ahead is how the last chunk is recognised.
+pub fn encrypt_attachment<R: Read, W: Write>(mut reader: R, mut writer: W, id: &str, mek: &[u8]) -> Result<u64, CryptoError> {
+    let salt: Vec<u8> = Cryptographer::generate_random_bytes()?;
+    let mut key: StreamKey = StreamKey::new(mek, &salt)?;
+    writer.write_all(MAGIC)?;
+    writer.write_all(&salt)?;
+
+    let mut current: Zeroizing<Vec<u8>> = Zeroizing::new(vec![0u8; CHUNK_SIZE]);
+    let mut next: Zeroizing<Vec<u8>> = Zeroizing::new(vec![0u8; CHUNK_SIZE]);
+    let mut current_len: usize = fill(&mut reader, &mut current)?;
+    let mut size: u64 = 0;
+    loop {
+        size += current_len as u64;
+        if size > MAX_ATTACHMENT_SIZE {
+            return Err(CryptoError::AttachmentTooLarge);
+        }
+        let next_len: usize = if current_len == CHUNK_SIZE { fill(&mut reader, &mut next)? } else { 0 };
+        let last: bool = next_len == 0;
+
+        let nonce: Nonce = key.next_nonce(last)?;
+        let tag = key
+            .key
+            .seal_in_place_separate_tag(nonce, Aad::from(id.as_bytes()), &mut current[..current_len])
+            .map_err(|_| CryptoError::EncryptionFailed)?;
+        writer.write_all(&current[..current_len])?;
+        writer.write_all(tag.as_ref())?;
+        if last {
+            break;
+        }
+        std::mem::swap(&mut current, &mut next);
+        current_len = next_len;
+    }
+    writer.flush()?;
+    Ok(size)
+}
+
+// The reverse of encrypt_attachment. Chunks are written out as they're verified, so on an error
+// `writer` may hold the start of the file; callers writing to disk remove it.
+pub fn decrypt_attachment<R: Read, W: Write>(mut reader: R, mut writer: W, id: &str, mek: &[u8]) -> Result<u64, CryptoError> {
+    let mut header: [u8; MAGIC.len() + SALT_LEN] = [0u8; MAGIC.len() + SALT_LEN];
+    if fill(&mut reader, &mut header)? < header.len() || &header[..MAGIC.len()] != MAGIC {
+        return Err(CryptoError::InvalidInput);
+    }
+    let mut key: StreamKey = StreamKey::new(mek, &header[MAGIC.len()..])?;
+
+    let mut current: Zeroizing<Vec<u8>> = Zeroizing::new(vec![0u8; CHUNK_SIZE + TAG_LEN]);
+    let mut next: Zeroizing<Vec<u8>> = Zeroizing::new(vec![0u8; CHUNK_SIZE + TAG_LEN]);
+    let mut current_len: usize = fill(&mut reader, &mut current)?;
+    let mut size: u64 = 0;
+    loop {
+        if current_len < TAG_LEN {
+            return Err(CryptoError::DecryptionFailed);
+        }
+        let next_len: usize = if current_len == current.len() { fill(&mut reader, &mut next)? } else { 0 };
+        let last: bool = next_len == 0;
+
+        let nonce: Nonce = key.next_nonce(last)?;
+        let plaintext: &mut [u8] = key
+            .key
+            .open_in_place(nonce, Aad::from(id.as_bytes()), &mut current[..current_len])
+            .map_err(|_| CryptoError::DecryptionFailed)?;
+        size += plaintext.len() as u64;
+        writer.write_all(plaintext)?;
+        if last {
+            break;
+        }
+        std::mem::swap(&mut current, &mut next);
+        current_len = next_len;
+    }
+    writer.flush()?;
+    Ok(size)
+}
+
+// Blobs are named after the attachment id. It has to be a UUID, so an id from the frontend can't
+// point outside `dir`.
+fn blob_path(dir: &Path, id: &str) -> Result<PathBuf, CryptoError> {
+    let id: Uuid = Uuid::parse_str(id).map_err(|_| CryptoError::InvalidInput)?;
+    Ok(dir.join(format!("{}.bin", id.hyphenated())))
+}
+
+// Creates `path` readable by the owner only and hands it to `write`; the file is removed again if
+// anything fails
+fn write_private_file<T>(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<&File>) -> Result<T, CryptoError>,
+) -> Result<T, CryptoError> {
+    let mut options: OpenOptions = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let file: File = options.open(path)?;
+
+    let result: Result<T, CryptoError> = (|| {
+        let mut writer: BufWriter<&File> = BufWriter::new(&file);
+        let value: T = write(&mut writer)?;
+        writer.flush()?;
+        file.sync_all()?;
+        Ok(value)
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(path);
+    }
+    result
+}
+
+// Encrypts the file at `source` into `dir`, keeping its file name
+pub fn add_attachment(dir: &Path, source: &Path, mek: &[u8]) -> Result<DecryptedAttachment, CryptoError> {
+    let name: String = source
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or(CryptoError::InvalidInput)?
+        .to_string();
+    let file: File = File::open(source)?;
+    if file.metadata()?.len() > MAX_ATTACHMENT_SIZE {
+        return Err(CryptoError::AttachmentTooLarge);
+    }
+    std::fs::create_dir_all(dir)?;
+
+    // written under a temporary name so a failed copy never looks like a finished blob
+    let id: String = Uuid::new_v4().to_string();
+    let path: PathBuf = blob_path(dir, &id)?;
+    let partial: PathBuf = path.with_extension("partial");
+    let size: u64 = write_private_file(&partial, |writer| encrypt_attachment(BufReader::new(file), writer, &id, mek))?;
+    if let Err(e) = std::fs::rename(&partial, &path) {
+        let _ = std::fs::remove_file(&partial);
+        return Err(CryptoError::IO(e));
+    }
+
+    Ok(DecryptedAttachment {
+        id,
+        name,
+        size,
+        added: Utc::now(),
+    })
+}
+
+// Decrypts an attachment to `destination`, e.g. a path the user picked in a save dialog
+pub fn export_attachment(dir: &Path, id: &str, destination: &Path, mek: &[u8]) -> Result<u64, CryptoError> {
+    let blob: File = match File::open(blob_path(dir, id)?) {
+        Ok(blob) => blob,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Err(CryptoError::AttachmentNotFound),
+        Err(e) => return Err(CryptoError::IO(e)),
+    };
+    write_private_file(destination, |writer| decrypt_attachment(BufReader::new(blob), writer, id, mek))
+}
+
+// Removing an attachment whose blob is already gone isn't an error
+pub fn delete_attachment(dir: &Path, id: &str) -> Result<(), CryptoError> {
+    match std::fs::remove_file(blob_path(dir, id)?) {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(CryptoError::IO(e)),
+        _ => Ok(()),
+    }
+}
+
+impl Zeroize for DecryptedAttachment {
+    fn zeroize(&mut self) {
+        self.name.zeroize();
+    }
+}
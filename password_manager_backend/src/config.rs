@@ -1,3 +1,5 @@
+use crate::expiry::DEFAULT_EXPIRY_WARNING_DAYS;
+use crate::trash::DEFAULT_TRASH_RETENTION_DAYS;
 use rand::{rngs::OsRng, seq::SliceRandom};
 use serde::{Deserialize, Serialize};
 
@@ -24,6 +26,18 @@ pub struct UserSettings {
     pub min_symbol: u8,
     pub use_lower: bool,
     pub use_upper: bool,
+    #[serde(default = "default_trash_retention_days")]
+    pub trash_retention_days: u32, // how long removed entries can be restored; 0 purges them on the next unlock
+    #[serde(default = "default_expiry_warning_days")]
+    pub expiry_warning_days: u32, // how far ahead expiring passwords are reminded of
+}
+
+fn default_trash_retention_days() -> u32 {
+    DEFAULT_TRASH_RETENTION_DAYS
+}
+
+fn default_expiry_warning_days() -> u32 {
+    DEFAULT_EXPIRY_WARNING_DAYS
 }
 
 impl UserSettings {
@@ -84,6 +98,8 @@ impl Default for UserSettings {
             min_symbol: 2,
             use_lower: true,
             use_upper: true,
+            trash_retention_days: DEFAULT_TRASH_RETENTION_DAYS,
+            expiry_warning_days: DEFAULT_EXPIRY_WARNING_DAYS,
         }
     }
 }
@@ -98,6 +114,8 @@ impl PartialEq for UserSettings {
             && self.min_symbol == other.min_symbol
             && self.use_lower == other.use_lower
             && self.use_upper == other.use_upper
+            && self.trash_retention_days == other.trash_retention_days
+            && self.expiry_warning_days == other.expiry_warning_days
     }
 }
 impl Eq for UserSettings {}
\ No newline at end of file
@@ -1,7 +1,7 @@
 use crate::error_handling::CryptoError;
 use rand::{rngs::OsRng, RngCore};
 use ring::{
-    aead::Aad, aead::LessSafeKey, aead::Nonce, aead::UnboundKey, aead::AES_256_GCM, pbkdf2,
+    aead::Aad, aead::LessSafeKey, aead::Nonce, aead::UnboundKey, aead::AES_256_GCM, hkdf, pbkdf2,
     rand::SecureRandom, rand::SystemRandom,
 };
 use serde::{Deserialize, Serialize};
@@ -10,6 +10,7 @@ use zeroize::Zeroize;
 
 const KEY_SIZE: usize = 32;
 const ITERATIONS: u32 = 100_000;
+const FIELD_KEY_INFO: &[u8] = b"pawpass field";
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Cryptographer {
@@ -137,4 +138,63 @@ impl Default for SecureData {
             salt: vec![],
         }
     }
-}
\ No newline at end of file
+}
+
+// A value sealed with a FieldKey
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SealedData {
+    pub(crate) sealed: Vec<u8>,
+    pub(crate) nonce: [u8; 12],
+}
+
+// AES-256-GCM key for the values kept inside entries, derived from the MEK with HKDF: the MEK is
+// already random, so the per-value PBKDF2 that `Cryptographer` runs for password-derived keys isn't
+// needed. Each value is sealed with the id of what it belongs to and its field name as associated
+// data, so it can't be moved to another entry or field without failing to open.
+pub struct FieldKey {
+    key: LessSafeKey,
+}
+
+impl FieldKey {
+    pub fn new(mek: &[u8]) -> Result<Self, CryptoError> {
+        let prk = hkdf::Salt::new(hkdf::HKDF_SHA256, &[]).extract(mek);
+        let okm = prk
+            .expand(&[FIELD_KEY_INFO], &AES_256_GCM)
+            .map_err(|_| CryptoError::KeyDerivationFailed)?;
+        Ok(Self {
+            key: LessSafeKey::new(UnboundKey::from(okm)),
+        })
+    }
+
+    pub fn seal(&self, data: &[u8], owner: &str, field: &str) -> Result<SealedData, CryptoError> {
+        let nonce: [u8; 12] = Cryptographer::generate_nonce()?;
+        let mut sealed: Vec<u8> = Vec::with_capacity(data.len() + AES_256_GCM.tag_len());
+        sealed.extend_from_slice(data);
+        self.key
+            .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::from(field_aad(owner, field)), &mut sealed)
+            .map_err(|_| CryptoError::EncryptionFailed)?;
+        Ok(SealedData { sealed, nonce })
+    }
+
+    pub fn open(&self, data: &SealedData, owner: &str, field: &str) -> Result<Vec<u8>, CryptoError> {
+        let mut buffer: Vec<u8> = data.sealed.clone();
+        let length: usize = match self.key.open_in_place(
+            Nonce::assume_unique_for_key(data.nonce),
+            Aad::from(field_aad(owner, field)),
+            &mut buffer,
+        ) {
+            Ok(plaintext) => plaintext.len(),
+            Err(_) => {
+                buffer.zeroize();
+                return Err(CryptoError::DecryptionFailed);
+            }
+        };
+        buffer.truncate(length);
+        Ok(buffer)
+    }
+}
+
+// Ids are UUIDs, so the separator can't appear in them
+fn field_aad(owner: &str, field: &str) -> Vec<u8> {
+    [owner.as_bytes(), b"\0", field.as_bytes()].concat()
+}
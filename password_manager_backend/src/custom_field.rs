@@ -0,0 +1,114 @@
+use crate::crypto::{FieldKey, SealedData};
+use crate::error_handling::CryptoError;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroize;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum CustomFieldType {
+    #[default]
+    Text,
+    Hidden, // sealed with the entry's field key, never matched by search
+    Url,
+    Boolean, // "true" or "false"
+}
+
+// A user-defined field as stored: hidden values live in `protected_value`, the rest in `value`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CustomField {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub field_type: CustomFieldType,
+    #[serde(default)]
+    pub value: Option<String>,
+    #[serde(default)]
+    pub protected_value: Option<SealedData>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DecryptedCustomField {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub field_type: CustomFieldType,
+    pub value: String,
+}
+
+impl CustomField {
+    // `owner` is the id of the entry the field belongs to
+    pub fn decrypt(&self, field_key: &FieldKey, owner: &str) -> Result<DecryptedCustomField, CryptoError> {
+        let value: String = match (&self.protected_value, &self.value) {
+            (Some(protected_value), _) => {
+                let bytes: Vec<u8> = field_key.open(protected_value, owner, &sealed_field_name(&self.name))?;
+                String::from_utf8(bytes).map_err(|e| {
+                    let mut bytes: Vec<u8> = e.into_bytes();
+                    bytes.zeroize();
+                    CryptoError::InvalidInput
+                })?
+            }
+            (None, Some(value)) => value.clone(),
+            (None, None) => String::new(),
+        };
+        Ok(DecryptedCustomField {
+            name: self.name.clone(),
+            field_type: self.field_type,
+            value,
+        })
+    }
+}
+
+impl DecryptedCustomField {
+    pub fn new(name: &str, field_type: CustomFieldType, value: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            field_type,
+            value: value.to_string(),
+        }
+    }
+
+    pub fn is_hidden(&self) -> bool {
+        self.field_type == CustomFieldType::Hidden
+    }
+
+    // Fields need a name, and booleans have to say "true" or "false"
+    pub fn encrypt(&self, field_key: &FieldKey, owner: &str) -> Result<CustomField, CryptoError> {
+        if self.name.trim().is_empty()
+            || (self.field_type == CustomFieldType::Boolean && !matches!(self.value.as_str(), "true" | "false"))
+        {
+            return Err(CryptoError::InvalidInput);
+        }
+        let (value, protected_value) = if self.is_hidden() {
+            (None, Some(field_key.seal(self.value.as_bytes(), owner, &sealed_field_name(&self.name))?))
+        } else {
+            (Some(self.value.clone()), None)
+        };
+        Ok(CustomField {
+            name: self.name.clone(),
+            field_type: self.field_type,
+            value,
+            protected_value,
+        })
+    }
+}
+
+impl Zeroize for DecryptedCustomField {
+    fn zeroize(&mut self) {
+        self.value.zeroize();
+    }
+}
+
+impl Drop for DecryptedCustomField {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+// Hidden values are bound to their field's name, so they can't be moved to another field
+fn sealed_field_name(name: &str) -> String {
+    format!("custom_field/{}", name)
+}
+
+pub fn encrypt_custom_fields(fields: &[DecryptedCustomField], field_key: &FieldKey, owner: &str) -> Result<Vec<CustomField>, CryptoError> {
+    fields.iter().map(|field| field.encrypt(field_key, owner)).collect()
+}
@@ -0,0 +1,29 @@
+// Dates on entries, their history and attachments. They're written as RFC 3339; vaults and
+// exports from before they were typed hold chrono's Display output instead
+// ("2024-03-01 10:15:00.123456 UTC"), so reading accepts both.
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Deserializer};
+
+pub fn parse_entry_date(date: &str) -> Option<DateTime<Utc>> {
+    let date: &str = date.trim();
+    if let Ok(time) = DateTime::parse_from_rfc3339(date) {
+        return Some(time.with_timezone(&Utc));
+    }
+    let naive: &str = date.strip_suffix(" UTC")?;
+    NaiveDateTime::parse_from_str(naive, "%Y-%m-%d %H:%M:%S%.f")
+        .ok()
+        .map(|time| time.and_utc())
+}
+
+// For dates an entry always has. Old imports could leave them empty or unreadable, which
+// shouldn't stop a vault from loading; those become the Unix epoch.
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime<Utc>, D::Error> {
+    let date: String = String::deserialize(deserializer)?;
+    Ok(parse_entry_date(&date).unwrap_or(DateTime::UNIX_EPOCH))
+}
+
+// For dates that may be missing; unreadable ones count as missing
+pub fn deserialize_option<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error> {
+    let date: Option<String> = Option::deserialize(deserializer)?;
+    Ok(date.as_deref().and_then(parse_entry_date))
+}
@@ -0,0 +1,189 @@
+use crate::crypto::{FieldKey, SealedData};
+use crate::error_handling::CryptoError;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use zeroize::{Zeroize, Zeroizing};
+
+const DETAILS_FIELD: &str = "details";
+
+// What an entry holds. Logins use the original fields; the other kinds keep their own fields
+// in `details`, which is encrypted as a whole. Entries from before kinds existed are logins.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum EntryKind {
+    #[default]
+    Login,
+    SecureNote,
+    PaymentCard,
+    Identity,
+    BankAccount,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum EntryDetails {
+    SecureNote(SecureNoteDetails),
+    PaymentCard(PaymentCardDetails),
+    Identity(IdentityDetails),
+    BankAccount(BankAccountDetails),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SecureNoteDetails {
+    pub text: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct PaymentCardDetails {
+    pub cardholder_name: Option<String>,
+    pub brand: Option<String>,
+    pub number: Option<String>,
+    pub expiry_month: Option<u8>,
+    pub expiry_year: Option<u16>,
+    pub cvv: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct IdentityDetails {
+    pub full_name: Option<String>,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub address_line1: Option<String>,
+    pub address_line2: Option<String>,
+    pub city: Option<String>,
+    pub region: Option<String>,
+    pub postal_code: Option<String>,
+    pub country: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct BankAccountDetails {
+    pub bank_name: Option<String>,
+    pub account_holder: Option<String>,
+    pub account_number: Option<String>,
+    pub routing_number: Option<String>,
+    pub iban: Option<String>,
+    pub swift: Option<String>,
+}
+
+// One labelled value from a kind's details, for search and for formats that only know
+// key/value pairs (e.g. KDBX custom fields)
+pub struct DetailField<'a> {
+    pub label: &'static str,
+    pub value: Cow<'a, str>,
+    pub sensitive: bool, // card numbers, CVVs, account numbers; never matched by search
+}
+
+impl EntryDetails {
+    pub fn kind(&self) -> EntryKind {
+        match self {
+            EntryDetails::SecureNote(_) => EntryKind::SecureNote,
+            EntryDetails::PaymentCard(_) => EntryKind::PaymentCard,
+            EntryDetails::Identity(_) => EntryKind::Identity,
+            EntryDetails::BankAccount(_) => EntryKind::BankAccount,
+        }
+    }
+
+    pub fn fields(&self) -> Vec<DetailField<'_>> {
+        let fields: Vec<Option<DetailField>> = match self {
+            EntryDetails::SecureNote(note) => vec![Some(DetailField {
+                label: "Note",
+                value: Cow::Borrowed(&note.text),
+                sensitive: false,
+            })],
+            EntryDetails::PaymentCard(card) => vec![
+                field("Cardholder name", &card.cardholder_name, false),
+                field("Brand", &card.brand, false),
+                field("Card number", &card.number, true),
+                card.expiry_month.zip(card.expiry_year).map(|(month, year)| DetailField {
+                    label: "Expiry",
+                    value: Cow::Owned(format!("{:02}/{}", month, year)),
+                    sensitive: false,
+                }),
+                field("CVV", &card.cvv, true),
+            ],
+            EntryDetails::Identity(identity) => vec![
+                field("Full name", &identity.full_name, false),
+                field("Email", &identity.email, false),
+                field("Phone", &identity.phone, false),
+                field("Address line 1", &identity.address_line1, false),
+                field("Address line 2", &identity.address_line2, false),
+                field("City", &identity.city, false),
+                field("Region", &identity.region, false),
+                field("Postal code", &identity.postal_code, false),
+                field("Country", &identity.country, false),
+            ],
+            EntryDetails::BankAccount(account) => vec![
+                field("Bank name", &account.bank_name, false),
+                field("Account holder", &account.account_holder, false),
+                field("Account number", &account.account_number, true),
+                field("Routing number", &account.routing_number, false),
+                field("IBAN", &account.iban, true),
+                field("SWIFT/BIC", &account.swift, false),
+            ],
+        };
+        fields.into_iter().flatten().collect()
+    }
+
+    // Card and account numbers can be found by their last four digits, the way statements show them
+    pub fn last_four(&self) -> Option<&str> {
+        let number: &str = match self {
+            EntryDetails::PaymentCard(card) => card.number.as_deref()?,
+            EntryDetails::BankAccount(account) => account.account_number.as_deref().or(account.iban.as_deref())?,
+            _ => return None,
+        };
+        let number: &str = number.trim();
+        number.get(number.len().checked_sub(4)?..)
+    }
+
+    // `owner` is the id of the entry the details belong to
+    pub fn encrypt(&self, field_key: &FieldKey, owner: &str) -> Result<SealedData, CryptoError> {
+        let serialized: Zeroizing<Vec<u8>> = Zeroizing::new(serde_json::to_vec(self)?);
+        field_key.seal(&serialized, owner, DETAILS_FIELD)
+    }
+
+    pub fn decrypt(sealed: &SealedData, field_key: &FieldKey, owner: &str) -> Result<Self, CryptoError> {
+        let serialized: Zeroizing<Vec<u8>> = Zeroizing::new(field_key.open(sealed, owner, DETAILS_FIELD)?);
+        Ok(serde_json::from_slice(&serialized)?)
+    }
+}
+
+fn field<'a>(label: &'static str, value: &'a Option<String>, sensitive: bool) -> Option<DetailField<'a>> {
+    value
+        .as_deref()
+        .filter(|value| !value.is_empty())
+        .map(|value| DetailField { label, value: Cow::Borrowed(value), sensitive })
+}
+
+impl Zeroize for EntryDetails {
+    fn zeroize(&mut self) {
+        match self {
+            EntryDetails::SecureNote(note) => note.text.zeroize(),
+            EntryDetails::PaymentCard(card) => {
+                card.cardholder_name.zeroize();
+                card.number.zeroize();
+                card.cvv.zeroize();
+                card.expiry_month = None;
+                card.expiry_year = None;
+            }
+            EntryDetails::Identity(identity) => {
+                identity.full_name.zeroize();
+                identity.email.zeroize();
+                identity.phone.zeroize();
+                identity.address_line1.zeroize();
+                identity.address_line2.zeroize();
+                identity.postal_code.zeroize();
+            }
+            EntryDetails::BankAccount(account) => {
+                account.account_holder.zeroize();
+                account.account_number.zeroize();
+                account.routing_number.zeroize();
+                account.iban.zeroize();
+            }
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use crate::attachment::MAX_ATTACHMENT_SIZE;
 use ring::error::Unspecified;
 
 #[derive(Debug)]
@@ -22,6 +23,20 @@ pub enum CryptoError {
     MekDecryptionFailed,
     MekHashVerificationFailed,
     MekDataNotPresent,
+    RecoveryKeyNotFound,
+    InvalidRecoveryKey,
+    KeySlotNotFound,
+    LastKeySlot,
+    KeyFileRequired,
+    InvalidKeyFile,
+    InvalidShare,
+    InsufficientShares,
+    HiddenVaultNotFound,
+    HiddenVaultFull,
+    RecoveryUnavailableForHiddenVault,
+    ImportFailed(String),
+    AttachmentNotFound,
+    AttachmentTooLarge,
 }
 
 impl std::fmt::Display for CryptoError {
@@ -51,6 +66,24 @@ impl std::fmt::Display for CryptoError {
             CryptoError::MekDecryptionFailed => write!(f, "MEK decryption failed."),
             CryptoError::MekHashVerificationFailed => write!(f, "MEK hash verification failed."),
             CryptoError::MekDataNotPresent => write!(f, "MEK data not present."),
+            CryptoError::RecoveryKeyNotFound => write!(f, "No recovery key has been set up."),
+            CryptoError::InvalidRecoveryKey => write!(f, "Recovery key is malformed."),
+            CryptoError::KeySlotNotFound => write!(f, "Key slot not found."),
+            CryptoError::LastKeySlot => write!(f, "Cannot remove the last key slot that can unlock the vault."),
+            CryptoError::KeyFileRequired => write!(f, "This vault requires a key file to unlock."),
+            CryptoError::InvalidKeyFile => write!(f, "Key file is empty or unreadable."),
+            CryptoError::InvalidShare => write!(f, "Secret share is malformed or doesn't belong with the others."),
+            CryptoError::InsufficientShares => write!(f, "Not enough secret shares to unlock the vault."),
+            CryptoError::HiddenVaultNotFound => write!(f, "No hidden vault opens with this password."),
+            CryptoError::HiddenVaultFull => write!(f, "The vault is too large to be kept hidden."),
+            CryptoError::RecoveryUnavailableForHiddenVault => {
+                write!(f, "Recovery keys and secret shares can't unlock a hidden vault; only its master password can.")
+            }
+            CryptoError::ImportFailed(reason) => write!(f, "Import failed: {}", reason),
+            CryptoError::AttachmentNotFound => write!(f, "Attachment not found."),
+            CryptoError::AttachmentTooLarge => {
+                write!(f, "Attachments can be at most {} MiB.", MAX_ATTACHMENT_SIZE / (1024 * 1024))
+            }
         }
     }
 }
@@ -0,0 +1,104 @@
+// Entries can say when their password has to be changed: by a fixed date, every so many days
+// counted from the last password change, or both, in which case whichever comes first applies.
+// Reminders cover entries that are past due or due within the warning period from the user
+// settings; changing the password restarts the rotation interval.
+use crate::error_handling::CryptoError;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_EXPIRY_WARNING_DAYS: u32 = 14;
+// Upper bound for rotation intervals and the warning period, about a hundred years
+pub const MAX_EXPIRY_DAYS: u32 = 36_500;
+
+// Implemented by both PasswordEntry and DecryptedPasswordEntry so reminders work on either
+pub trait Expiring {
+    fn id(&self) -> &str;
+    fn title(&self) -> &str;
+    fn is_trashed(&self) -> bool;
+    fn expiry_due(&self) -> Option<DateTime<Utc>>;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ExpiryStatus {
+    Expired,
+    ExpiringSoon,
+}
+
+// What the frontend shows for an entry that needs a new password; no secrets
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExpiryReminder {
+    pub id: String,
+    pub title: String,
+    pub due: DateTime<Utc>,
+    pub status: ExpiryStatus,
+}
+
+// Rotation intervals and warning periods are checked when they're set; the date arithmetic below
+// copes with anything that got past that (e.g. an edited vault file) anyway
+pub fn check_days(days: Option<u32>) -> Result<(), CryptoError> {
+    match days {
+        Some(days) if days > MAX_EXPIRY_DAYS => Err(CryptoError::InvalidInput),
+        _ => Ok(()),
+    }
+}
+
+// `days` days after `date`, or None past the last date chrono can represent
+fn days_after(date: DateTime<Utc>, days: u32) -> Option<DateTime<Utc>> {
+    date.checked_add_signed(Duration::try_days(i64::from(days))?)
+}
+
+// When a password is due for changing. `rotated` is when it was last changed (or created, if it
+// never was); an interval of 0 days counts as no interval, and so does one that overflows.
+pub fn due_date(
+    expires: Option<DateTime<Utc>>,
+    rotation_days: Option<u32>,
+    rotated: DateTime<Utc>,
+) -> Option<DateTime<Utc>> {
+    let rotation_due: Option<DateTime<Utc>> = rotation_days
+        .filter(|days| *days > 0)
+        .and_then(|days| days_after(rotated, days));
+    match (expires, rotation_due) {
+        (Some(expires), Some(rotation_due)) => Some(expires.min(rotation_due)),
+        (expires, rotation_due) => expires.or(rotation_due),
+    }
+}
+
+pub fn status(due: DateTime<Utc>, warning_days: u32, now: DateTime<Utc>) -> Option<ExpiryStatus> {
+    if due <= now {
+        Some(ExpiryStatus::Expired)
+    } else if days_after(now, warning_days).is_none_or(|warn_from| due <= warn_from) {
+        Some(ExpiryStatus::ExpiringSoon)
+    } else {
+        None
+    }
+}
+
+// Reminders for the entries outside the trash that are expired or expire within `warning_days`,
+// soonest first
+pub fn reminders<'a, T: Expiring + 'a>(entries: impl IntoIterator<Item = &'a T>, warning_days: u32) -> Vec<ExpiryReminder> {
+    reminders_at(entries, warning_days, Utc::now())
+}
+
+pub fn reminders_at<'a, T: Expiring + 'a>(
+    entries: impl IntoIterator<Item = &'a T>,
+    warning_days: u32,
+    now: DateTime<Utc>,
+) -> Vec<ExpiryReminder> {
+    let mut reminders: Vec<ExpiryReminder> = entries
+        .into_iter()
+        .filter(|entry| !entry.is_trashed())
+        .filter_map(|entry| {
+            let due: DateTime<Utc> = entry.expiry_due()?;
+            Some(ExpiryReminder {
+                id: entry.id().to_string(),
+                title: entry.title().to_string(),
+                due,
+                status: status(due, warning_days, now)?,
+            })
+        })
+        .collect();
+    reminders.sort_by_key(|reminder| reminder.due);
+    reminders
+}
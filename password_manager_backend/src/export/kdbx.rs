@@ -0,0 +1,105 @@
+use crate::error_handling::CryptoError;
+use crate::folders::FOLDER_SEPARATOR;
+use crate::kdbx::reader::KdbxDatabase;
+use crate::kdbx::writer::write_kdbx;
+use crate::entry_kind::EntryDetails;
+use crate::import::append_note;
+use crate::kdbx::{extra_url_key, Argon2Settings, KdbxEntry, KdbxField, FAVORITE_TAG};
+use crate::password_entry::DecryptedPasswordEntry;
+use uuid::Uuid;
+
+const DATABASE_NAME: &str = "PawPass export";
+const STANDARD_KEYS: [&str; 5] = ["Title", "UserName", "Password", "URL", "Notes"];
+
+// Writes the decrypted vault as a KDBX 4 file protected by `password`. Folders become (nested)
+// groups, tags stay tags and favorites also get the "Favorite" tag. URLs after the first go in
+// KeePassXC's "KP2A_URL" fields; their match rules have no KeePass equivalent. KeePass only has
+// expiry dates, so a rotation interval is written as the date it next falls due. Entries in the
+// trash are left out.
+pub fn export_kdbx(entries: &[DecryptedPasswordEntry], password: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    export_kdbx_with_settings(entries, password, Argon2Settings::default())
+}
+
+pub fn export_kdbx_with_settings(
+    entries: &[DecryptedPasswordEntry],
+    password: &[u8],
+    argon2: Argon2Settings,
+) -> Result<Vec<u8>, CryptoError> {
+    if password.is_empty() {
+        return Err(CryptoError::InvalidInput);
+    }
+    let database: KdbxDatabase = KdbxDatabase {
+        name: Some(DATABASE_NAME.to_string()),
+        entries: entries.iter().filter(|entry| !entry.is_trashed()).map(to_kdbx_entry).collect(),
+    };
+    write_kdbx(&database, password, argon2)
+}
+
+fn to_kdbx_entry(entry: &DecryptedPasswordEntry) -> KdbxEntry {
+    let mut kdbx_entry: KdbxEntry = KdbxEntry::default();
+    kdbx_entry.uuid = Uuid::parse_str(&entry.id).ok().map(|uuid| uuid.into_bytes());
+    kdbx_entry.group_path = entry
+        .folder
+        .as_deref()
+        .unwrap_or_default()
+        .split(FOLDER_SEPARATOR)
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect();
+    kdbx_entry.title = Some(entry.title.clone());
+    kdbx_entry.username = entry.username.clone();
+    kdbx_entry.password = entry.password.clone();
+    kdbx_entry.url = entry.primary_url().map(str::to_string);
+    kdbx_entry.notes = entry.notes.clone();
+    // KeePass has no entry kinds; the details become custom fields, protected where they're secret
+    match &entry.details {
+        Some(EntryDetails::SecureNote(note)) => append_note(&mut kdbx_entry.notes, &note.text),
+        Some(details) => {
+            kdbx_entry.custom_fields = details
+                .fields()
+                .into_iter()
+                .map(|field| KdbxField {
+                    key: field.label.to_string(),
+                    value: field.value.into_owned(),
+                    protected: field.sensitive,
+                })
+                .collect();
+        }
+        None => {}
+    }
+    for (index, url) in entry.urls.iter().skip(1).enumerate() {
+        kdbx_entry.custom_fields.push(KdbxField {
+            key: unique_key(&kdbx_entry.custom_fields, &extra_url_key(index)),
+            value: url.url.clone(),
+            protected: false,
+        });
+    }
+    for field in &entry.custom_fields {
+        let key: String = unique_key(&kdbx_entry.custom_fields, &field.name);
+        kdbx_entry.custom_fields.push(KdbxField {
+            key,
+            value: field.value.clone(),
+            protected: field.is_hidden(),
+        });
+    }
+    kdbx_entry.tags = entry.tags.iter().filter(|tag| *tag != FAVORITE_TAG).cloned().collect();
+    if entry.favorite {
+        kdbx_entry.tags.push(FAVORITE_TAG.to_string());
+    }
+    kdbx_entry.creation_time = Some(entry.created);
+    kdbx_entry.expiry_time = entry.expiry_due();
+    kdbx_entry
+}
+
+// KeePass keys an entry's strings by name, so a repeated or reserved name gets a " (2)" suffix
+fn unique_key(fields: &[KdbxField], name: &str) -> String {
+    let taken = |key: &str| STANDARD_KEYS.contains(&key) || fields.iter().any(|field| field.key == key);
+    let mut key: String = name.to_string();
+    let mut counter: usize = 2;
+    while taken(&key) {
+        key = format!("{} ({})", name, counter);
+        counter += 1;
+    }
+    key
+}
@@ -0,0 +1,3 @@
+pub mod kdbx;
+pub mod portable;
+pub mod plaintext;
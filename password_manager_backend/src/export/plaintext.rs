@@ -0,0 +1,229 @@
+// Unencrypted exports for moving to another password manager. Both formats carry the same fields
+// for every entry that isn't in the trash: everything the user sees, dates and password history.
+// Attachments are left out because their contents stay encrypted, and so are the trash date
+// (always unset here) and the legacy category (already migrated into the folder).
+//
+// - JSON: an array of objects with the same camelCase keys the frontend uses (id, title, username,
+//   password, urls, notes, created, modified, passwordChanged, lastUsed, expires, rotationDays,
+//   folder, tags, favorite, kind, details, customFields, passwordHistory); missing values are null
+//   and dates are RFC 3339.
+// - CSV: RFC 4180 with a header row of those keys in that order; missing values are empty,
+//   favorite is "true"/"false", urls a JSON array of {url, matchMode}, tags a JSON array of
+//   strings, details (cards, identities, ...) is its JSON object, customFields a JSON array of
+//   {name, type, value} and passwordHistory a JSON array of {password, replacedAt}, newest first.
+//   Notes may span lines inside quotes.
+//
+// Entries are written one at a time straight to the writer, so no second plaintext copy of the
+// vault is built up in memory.
+use crate::custom_field::DecryptedCustomField;
+use crate::entry_kind::{EntryDetails, EntryKind};
+use crate::error_handling::CryptoError;
+use crate::password_entry::DecryptedPasswordEntry;
+use crate::password_history::PasswordHistoryItem;
+use crate::url_match::EntryUrl;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use zeroize::Zeroizing;
+
+const CSV_HEADER: [&str; 19] = [
+    "id",
+    "title",
+    "username",
+    "password",
+    "urls",
+    "notes",
+    "created",
+    "modified",
+    "passwordChanged",
+    "lastUsed",
+    "expires",
+    "rotationDays",
+    "folder",
+    "tags",
+    "favorite",
+    "kind",
+    "details",
+    "customFields",
+    "passwordHistory",
+];
+
+// One JSON object of the export, borrowing from the entry
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportedEntry<'a> {
+    id: &'a str,
+    title: &'a str,
+    username: &'a Option<String>,
+    password: &'a Option<String>,
+    urls: &'a [EntryUrl],
+    notes: &'a Option<String>,
+    created: &'a DateTime<Utc>,
+    modified: &'a Option<DateTime<Utc>>,
+    password_changed: &'a Option<DateTime<Utc>>,
+    last_used: &'a Option<DateTime<Utc>>,
+    expires: &'a Option<DateTime<Utc>>,
+    rotation_days: Option<u32>,
+    folder: &'a Option<String>,
+    tags: &'a [String],
+    favorite: bool,
+    kind: EntryKind,
+    details: &'a Option<EntryDetails>,
+    custom_fields: &'a [DecryptedCustomField],
+    password_history: &'a [PasswordHistoryItem],
+}
+
+impl<'a> From<&'a DecryptedPasswordEntry> for ExportedEntry<'a> {
+    fn from(entry: &'a DecryptedPasswordEntry) -> Self {
+        Self {
+            id: &entry.id,
+            title: &entry.title,
+            username: &entry.username,
+            password: &entry.password,
+            urls: &entry.urls,
+            notes: &entry.notes,
+            created: &entry.created,
+            modified: &entry.modified,
+            password_changed: &entry.password_changed,
+            last_used: &entry.last_used,
+            expires: &entry.expires,
+            rotation_days: entry.rotation_days,
+            folder: &entry.folder,
+            tags: &entry.tags,
+            favorite: entry.favorite,
+            kind: entry.kind,
+            details: &entry.details,
+            custom_fields: &entry.custom_fields,
+            password_history: &entry.password_history,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum PlaintextFormat {
+    Json,
+    Csv,
+}
+
+pub fn write_plaintext<W: Write>(
+    entries: &[DecryptedPasswordEntry],
+    format: PlaintextFormat,
+    writer: W,
+) -> Result<(), CryptoError> {
+    match format {
+        PlaintextFormat::Json => write_json(entries, writer),
+        PlaintextFormat::Csv => write_csv(entries, writer),
+    }
+}
+
+// Creates `path` (readable by the owner only, where the platform allows it) and streams the
+// export into it. A half-written file is removed if anything fails.
+pub fn export_plaintext_to_file(
+    entries: &[DecryptedPasswordEntry],
+    format: PlaintextFormat,
+    path: &Path,
+) -> Result<(), CryptoError> {
+    let mut options: OpenOptions = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let file: File = options.open(path)?;
+
+    let result: Result<(), CryptoError> = write_plaintext(entries, format, BufWriter::new(&file))
+        .and_then(|_| file.sync_all().map_err(CryptoError::IO));
+    if result.is_err() {
+        let _ = std::fs::remove_file(path);
+    }
+    result
+}
+
+fn write_json<W: Write>(entries: &[DecryptedPasswordEntry], mut writer: W) -> Result<(), CryptoError> {
+    writer.write_all(b"[")?;
+    for (index, entry) in entries.iter().filter(|entry| !entry.is_trashed()).enumerate() {
+        if index > 0 {
+            writer.write_all(b",")?;
+        }
+        writer.write_all(b"\n  ")?;
+        serde_json::to_writer(&mut writer, &ExportedEntry::from(entry))?;
+    }
+    writer.write_all(b"\n]\n")?;
+    writer.flush()?;
+    Ok(())
+}
+
+fn write_csv<W: Write>(entries: &[DecryptedPasswordEntry], writer: W) -> Result<(), CryptoError> {
+    let mut csv_writer = csv::Writer::from_writer(writer);
+    csv_writer.write_record(CSV_HEADER).map_err(csv_error)?;
+    for entry in entries.iter().filter(|entry| !entry.is_trashed()) {
+        let favorite: &str = if entry.favorite { "true" } else { "false" };
+        let kind: String = serde_json::to_value(entry.kind)?.as_str().unwrap_or_default().to_string();
+        let details: Zeroizing<String> = Zeroizing::new(match &entry.details {
+            Some(details) => serde_json::to_string(details)?,
+            None => String::new(),
+        });
+        let urls: String = if entry.urls.is_empty() {
+            String::new()
+        } else {
+            serde_json::to_string(&entry.urls)?
+        };
+        let rotation_days: String = entry.rotation_days.map(|days| days.to_string()).unwrap_or_default();
+        let tags: String = if entry.tags.is_empty() {
+            String::new()
+        } else {
+            serde_json::to_string(&entry.tags)?
+        };
+        let custom_fields: Zeroizing<String> = Zeroizing::new(if entry.custom_fields.is_empty() {
+            String::new()
+        } else {
+            serde_json::to_string(&entry.custom_fields)?
+        });
+        let password_history: Zeroizing<String> = Zeroizing::new(if entry.password_history.is_empty() {
+            String::new()
+        } else {
+            serde_json::to_string(&entry.password_history)?
+        });
+        csv_writer
+            .write_record([
+                entry.id.as_str(),
+                entry.title.as_str(),
+                entry.username.as_deref().unwrap_or_default(),
+                entry.password.as_deref().unwrap_or_default(),
+                urls.as_str(),
+                entry.notes.as_deref().unwrap_or_default(),
+                entry.created.to_rfc3339().as_str(),
+                rfc3339(&entry.modified).as_str(),
+                rfc3339(&entry.password_changed).as_str(),
+                rfc3339(&entry.last_used).as_str(),
+                rfc3339(&entry.expires).as_str(),
+                rotation_days.as_str(),
+                entry.folder.as_deref().unwrap_or_default(),
+                tags.as_str(),
+                favorite,
+                kind.as_str(),
+                details.as_str(),
+                custom_fields.as_str(),
+                password_history.as_str(),
+            ])
+            .map_err(csv_error)?;
+    }
+    csv_writer.flush()?;
+    Ok(())
+}
+
+// Unset dates are empty cells
+fn rfc3339(date: &Option<DateTime<Utc>>) -> String {
+    date.map(|date| date.to_rfc3339()).unwrap_or_default()
+}
+
+fn csv_error(error: csv::Error) -> CryptoError {
+    match error.into_kind() {
+        csv::ErrorKind::Io(error) => CryptoError::IO(error),
+        kind => CryptoError::IO(std::io::Error::other(format!("{:?}", kind))),
+    }
+}
@@ -0,0 +1,55 @@
+use crate::crypto::{Cryptographer, SecureData};
+use crate::error_handling::CryptoError;
+use crate::password_entry::DecryptedPasswordEntry;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+pub(crate) const PORTABLE_FORMAT: &str = "pawpass-portable";
+pub(crate) const PORTABLE_VERSION: u32 = 1;
+
+// The file on disk: only the format marker is readable, everything else is in `data`
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct PortableFile {
+    pub(crate) format: String,
+    pub(crate) version: u32,
+    pub(crate) data: SecureData,
+}
+
+// What `data` decrypts to
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct PortablePayload {
+    pub(crate) exported_at: String,
+    pub(crate) entries: Vec<DecryptedPasswordEntry>,
+}
+
+// Writes the decrypted entries to a single file encrypted under `passphrase`, which is separate
+// from the master password so the file can be opened on another install. Attachments stay
+// behind: their blobs aren't part of the file, so references to them would dangle. So do
+// entries in the trash.
+pub fn export_portable(entries: &[DecryptedPasswordEntry], passphrase: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    if passphrase.is_empty() {
+        return Err(CryptoError::InvalidInput);
+    }
+    let payload: PortablePayload = PortablePayload {
+        exported_at: Utc::now().to_string(),
+        entries: entries
+            .iter()
+            .filter(|entry| !entry.is_trashed())
+            .cloned()
+            .map(|mut entry| {
+                entry.attachments.clear();
+                entry
+            })
+            .collect(),
+    };
+    let serialized: Zeroizing<Vec<u8>> = Zeroizing::new(serde_json::to_vec(&payload)?);
+
+    let cryptographer: Cryptographer = Cryptographer::new(None);
+    let file: PortableFile = PortableFile {
+        format: PORTABLE_FORMAT.to_string(),
+        version: PORTABLE_VERSION,
+        data: cryptographer.encrypt(&serialized, passphrase)?,
+    };
+    Ok(serde_json::to_vec(&file)?)
+}
@@ -0,0 +1,224 @@
+// Entries are filed in at most one folder and under any number of tags. A folder is a path of
+// names separated by "/", e.g. "Work/Servers"; a folder exists as long as something is filed in
+// it or below it. Like titles, both are stored in plaintext, so they can be listed and
+// reorganised on the stored entries as well as the decrypted ones.
+use crate::error_handling::CryptoError;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+pub const FOLDER_SEPARATOR: char = '/';
+
+// Implemented by both PasswordEntry and DecryptedPasswordEntry so the operations below work on
+// either
+pub trait Filed {
+    fn folder(&self) -> Option<&str>;
+    fn folder_mut(&mut self) -> &mut Option<String>;
+    fn tags(&self) -> &[String];
+    fn tags_mut(&mut self) -> &mut Vec<String>;
+    fn touch(&mut self);
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Folder {
+    pub name: String,
+    pub path: String,
+    pub entry_count: usize, // directly in this folder, not counting subfolders
+    pub children: Vec<Folder>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct TagCount {
+    pub tag: String,
+    pub entry_count: usize,
+}
+
+// Trims every name and drops empty ones, so " Work//Servers/ " is "Work/Servers"; None when
+// nothing is left
+pub fn normalize_folder(path: &str) -> Option<String> {
+    let names: Vec<&str> = path
+        .split(FOLDER_SEPARATOR)
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .collect();
+    (!names.is_empty()).then(|| names.join("/"))
+}
+
+// Trimmed, without empty tags or repeats, in the order given
+pub fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags.iter().map(|tag| tag.trim()).filter(|tag| !tag.is_empty()) {
+        if !normalized.iter().any(|existing| existing == tag) {
+            normalized.push(tag.to_string());
+        }
+    }
+    normalized
+}
+
+// Older entries had a single `category` string instead. It becomes the folder (a "Work/Servers"
+// category from a KeePass import turns into nested folders) unless the entry already has one.
+// Returns whether there was a category to move.
+pub fn migrate_category(category: &mut Option<String>, folder: &mut Option<String>) -> bool {
+    let Some(category) = category.take() else {
+        return false;
+    };
+    if folder.is_none() {
+        *folder = normalize_folder(&category);
+    }
+    true
+}
+
+fn parent(path: &str) -> Option<&str> {
+    path.rsplit_once(FOLDER_SEPARATOR).map(|(parent, _)| parent)
+}
+
+fn name(path: &str) -> &str {
+    path.rsplit_once(FOLDER_SEPARATOR).map_or(path, |(_, name)| name)
+}
+
+fn join(parent: Option<&str>, rest: &str) -> String {
+    match parent {
+        Some(parent) => format!("{}{}{}", parent, FOLDER_SEPARATOR, rest),
+        None => rest.to_string(),
+    }
+}
+
+// Where a folder ends up when `from` is moved to `to` (None: its contents go to the top level)
+fn relocated(folder: &str, from: &str, to: Option<&str>) -> Option<Option<String>> {
+    if folder == from {
+        return Some(to.map(str::to_string));
+    }
+    let rest: &str = folder.strip_prefix(from)?.strip_prefix(FOLDER_SEPARATOR)?;
+    Some(Some(join(to, rest)))
+}
+
+// Moves everything filed in or below `from` to `to`; returns how many entries changed
+fn relocate<T: Filed>(entries: &mut [T], from: &str, to: Option<&str>) -> usize {
+    let mut changed: usize = 0;
+    for entry in entries.iter_mut() {
+        if let Some(folder) = entry.folder().and_then(|folder| relocated(folder, from, to)) {
+            *entry.folder_mut() = folder;
+            entry.touch();
+            changed += 1;
+        }
+    }
+    changed
+}
+
+// A folder name can't be empty or contain the separator
+fn valid_name(name: &str) -> Result<&str, CryptoError> {
+    let name: &str = name.trim();
+    if name.is_empty() || name.contains(FOLDER_SEPARATOR) {
+        return Err(CryptoError::InvalidInput);
+    }
+    Ok(name)
+}
+
+fn valid_path(path: &str) -> Result<String, CryptoError> {
+    normalize_folder(path).ok_or(CryptoError::InvalidInput)
+}
+
+// Renames the folder at `path`, keeping it where it is. Renaming onto an existing folder merges
+// the two.
+pub fn rename_folder<T: Filed>(entries: &mut [T], path: &str, new_name: &str) -> Result<usize, CryptoError> {
+    let path: String = valid_path(path)?;
+    let renamed: String = join(parent(&path), valid_name(new_name)?);
+    Ok(relocate(entries, &path, Some(&renamed)))
+}
+
+// Moves the folder at `path` with its subfolders into `new_parent`, or to the top level
+pub fn move_folder<T: Filed>(entries: &mut [T], path: &str, new_parent: Option<&str>) -> Result<usize, CryptoError> {
+    let path: String = valid_path(path)?;
+    let new_parent: Option<String> = new_parent.map(valid_path).transpose()?;
+    // a folder can't go inside itself
+    if new_parent
+        .as_deref()
+        .is_some_and(|new_parent| relocated(new_parent, &path, None).is_some())
+    {
+        return Err(CryptoError::InvalidInput);
+    }
+    let moved: String = join(new_parent.as_deref(), name(&path));
+    Ok(relocate(entries, &path, Some(&moved)))
+}
+
+// Removes the folder at `path`. Nothing is deleted: its entries and subfolders move up into the
+// folder's parent.
+pub fn delete_folder<T: Filed>(entries: &mut [T], path: &str) -> Result<usize, CryptoError> {
+    let path: String = valid_path(path)?;
+    Ok(relocate(entries, &path, parent(&path)))
+}
+
+// Renames a tag on every entry that has it; entries that already have `new_tag` just lose the old one
+pub fn rename_tag<T: Filed>(entries: &mut [T], tag: &str, new_tag: &str) -> Result<usize, CryptoError> {
+    let new_tag: &str = new_tag.trim();
+    if new_tag.is_empty() {
+        return Err(CryptoError::InvalidInput);
+    }
+    Ok(retag(entries, tag, Some(new_tag)))
+}
+
+pub fn delete_tag<T: Filed>(entries: &mut [T], tag: &str) -> usize {
+    retag(entries, tag, None)
+}
+
+fn retag<T: Filed>(entries: &mut [T], tag: &str, new_tag: Option<&str>) -> usize {
+    let mut changed: usize = 0;
+    for entry in entries.iter_mut() {
+        let Some(index) = entry.tags().iter().position(|existing| existing == tag) else {
+            continue;
+        };
+        let tags: &mut Vec<String> = entry.tags_mut();
+        match new_tag {
+            Some(new_tag) if !tags.iter().any(|existing| existing == new_tag) => tags[index] = new_tag.to_string(),
+            _ => {
+                tags.remove(index);
+            }
+        }
+        entry.touch();
+        changed += 1;
+    }
+    changed
+}
+
+// The folders `entries` are filed in, with their parents, as a tree sorted by name
+pub fn folder_tree<'a, T: Filed + 'a>(entries: impl IntoIterator<Item = &'a T>) -> Vec<Folder> {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for folder in entries.into_iter().filter_map(|entry| entry.folder()) {
+        *counts.entry(folder).or_default() += 1;
+        let mut ancestor: Option<&str> = parent(folder);
+        while let Some(path) = ancestor {
+            counts.entry(path).or_default();
+            ancestor = parent(path);
+        }
+    }
+    children_of(None, &counts)
+}
+
+fn children_of(path: Option<&str>, counts: &BTreeMap<&str, usize>) -> Vec<Folder> {
+    counts
+        .iter()
+        .filter(|(folder, _)| parent(folder) == path)
+        .map(|(folder, count)| Folder {
+            name: name(folder).to_string(),
+            path: folder.to_string(),
+            entry_count: *count,
+            children: children_of(Some(folder), counts),
+        })
+        .collect()
+}
+
+// Every tag on `entries` with how many entries have it, sorted by tag
+pub fn tag_counts<'a, T: Filed + 'a>(entries: impl IntoIterator<Item = &'a T>) -> Vec<TagCount> {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for tag in entries.into_iter().flat_map(|entry| entry.tags()) {
+        *counts.entry(tag).or_default() += 1;
+    }
+    counts
+        .into_iter()
+        .map(|(tag, entry_count)| TagCount {
+            tag: tag.to_string(),
+            entry_count,
+        })
+        .collect()
+}
@@ -0,0 +1,113 @@
+use crate::crypto::{Cryptographer, SecureData};
+use crate::error_handling::CryptoError;
+use crate::mek::MekData;
+use rand::{rngs::OsRng, RngCore};
+use ring::aead::AES_256_GCM;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use zeroize::{Zeroize, Zeroizing};
+
+// Every section, sealed vault or filler, is exactly this big before encryption, whatever the size
+// of the vault inside. A vault that doesn't fit can't be hidden.
+const CAPACITY: usize = 1024 * 1024;
+const LENGTH_PREFIX: usize = 4;
+const KEY_SIZE: usize = 32;
+
+// A second vault hidden inside the file, sealed under a key derived from its own master password.
+// Every vault carries one of these: when there is no hidden vault it holds random filler of the
+// same shape and size, so without the right password the two can't be told apart.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HiddenVault {
+    pub(crate) salt: Vec<u8>,
+    pub(crate) sealed: SecureData,
+}
+
+// Key for re-sealing an opened hidden vault without asking for its password again
+#[derive(Clone)]
+pub struct HiddenVaultKey {
+    salt: Vec<u8>,
+    key: [u8; KEY_SIZE],
+}
+
+impl HiddenVault {
+    pub fn filler() -> Result<Self, CryptoError> {
+        let mut encrypted_data: Vec<u8> = vec![0u8; CAPACITY + AES_256_GCM.tag_len()];
+        OsRng.fill_bytes(&mut encrypted_data);
+
+        Ok(Self {
+            salt: Cryptographer::generate_salt()?,
+            sealed: SecureData {
+                encrypted_data,
+                nonce: Cryptographer::generate_nonce()?,
+                salt: Cryptographer::generate_salt()?,
+            },
+        })
+    }
+
+    pub fn seal(payload: &[u8], key: &HiddenVaultKey) -> Result<Self, CryptoError> {
+        if LENGTH_PREFIX + payload.len() > CAPACITY {
+            return Err(CryptoError::HiddenVaultFull);
+        }
+        let length: u32 = u32::try_from(payload.len()).map_err(|_| CryptoError::InvalidInput)?;
+
+        let mut padded: Zeroizing<Vec<u8>> = Zeroizing::new(Vec::with_capacity(CAPACITY));
+        padded.extend_from_slice(&length.to_le_bytes());
+        padded.extend_from_slice(payload);
+        padded.resize(CAPACITY, 0);
+
+        let cryptographer: Cryptographer = Cryptographer::new(None);
+        Ok(Self {
+            salt: key.salt.clone(),
+            sealed: cryptographer.encrypt(&padded, &key.key)?,
+        })
+    }
+
+    // Derives the key this password would have sealed the vault with
+    pub fn derive_key(&self, password: &[u8]) -> HiddenVaultKey {
+        HiddenVaultKey {
+            salt: self.salt.clone(),
+            key: MekData::derive_mek_key(password, &self.salt),
+        }
+    }
+
+    // None when the key doesn't fit, which is also what filler always gives
+    pub fn open(&self, key: &HiddenVaultKey) -> Option<Zeroizing<Vec<u8>>> {
+        if self.sealed.encrypted_data.len() != CAPACITY + AES_256_GCM.tag_len() {
+            return None;
+        }
+        let cryptographer: Cryptographer = Cryptographer::new(None);
+        let padded: Zeroizing<Vec<u8>> = Zeroizing::new(cryptographer.decrypt(self.sealed.clone(), &key.key).ok()?);
+
+        let length_bytes: [u8; LENGTH_PREFIX] = padded.get(..LENGTH_PREFIX)?.try_into().ok()?;
+        let length: usize = u32::from_le_bytes(length_bytes) as usize;
+        let payload: &[u8] = padded.get(LENGTH_PREFIX..LENGTH_PREFIX + length)?;
+        Some(Zeroizing::new(payload.to_vec()))
+    }
+}
+
+impl HiddenVaultKey {
+    // A key with a fresh salt, for sealing a vault under a (new) password
+    pub fn new(password: &[u8]) -> Result<Self, CryptoError> {
+        let salt: Vec<u8> = Cryptographer::generate_salt()?;
+        let key: [u8; KEY_SIZE] = MekData::derive_mek_key(password, &salt);
+        Ok(Self { salt, key })
+    }
+}
+
+impl fmt::Debug for HiddenVaultKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HiddenVaultKey").finish_non_exhaustive()
+    }
+}
+
+impl Zeroize for HiddenVaultKey {
+    fn zeroize(&mut self) {
+        self.key.zeroize();
+    }
+}
+
+impl Drop for HiddenVaultKey {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
@@ -0,0 +1,212 @@
+use crate::custom_field::{CustomFieldType, DecryptedCustomField};
+use crate::error_handling::CryptoError;
+use crate::import::ImportOutcome;
+use crate::password_entry::PasswordEntry;
+use crate::url_match::{EntryUrl, UrlMatch};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::collections::HashMap;
+use zeroize::Zeroize;
+
+// Bitwarden item and custom field type codes
+const ITEM_LOGIN: u8 = 1;
+const ITEM_SECURE_NOTE: u8 = 2;
+const FIELD_TEXT: u8 = 0;
+const FIELD_HIDDEN: u8 = 1;
+const FIELD_BOOLEAN: u8 = 2;
+
+// Bitwarden URI match detection, in the order of its codes
+const URI_MATCHES: [UrlMatch; 6] = [
+    UrlMatch::BaseDomain,
+    UrlMatch::Host,
+    UrlMatch::StartsWith,
+    UrlMatch::Exact,
+    UrlMatch::Regex,
+    UrlMatch::Never,
+];
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Export {
+    #[serde(default)]
+    encrypted: bool,
+    #[serde(default)]
+    folders: Vec<Folder>,
+    #[serde(default)]
+    items: Vec<Item>,
+}
+
+#[derive(Deserialize)]
+struct Folder {
+    id: String,
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Item {
+    #[serde(rename = "type")]
+    item_type: u8,
+    #[serde(default)]
+    name: String,
+    notes: Option<String>,
+    #[serde(default)]
+    favorite: bool,
+    folder_id: Option<String>,
+    #[serde(default)]
+    fields: Vec<Field>,
+    login: Option<Login>,
+    creation_date: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Field {
+    name: Option<String>,
+    value: Option<String>,
+    #[serde(rename = "type")]
+    field_type: u8,
+}
+
+#[derive(Deserialize)]
+struct Login {
+    username: Option<String>,
+    password: Option<String>,
+    totp: Option<String>,
+    #[serde(default)]
+    uris: Vec<LoginUri>,
+}
+
+#[derive(Deserialize)]
+struct LoginUri {
+    uri: Option<String>,
+    #[serde(default, rename = "match")]
+    match_type: Option<u8>, // None: the user's default in Bitwarden, which is base domain
+}
+
+// Parses Bitwarden's unencrypted JSON export. Logins and secure notes become entries (folders,
+// URIs with their match detection and custom fields with their type all carry over); cards,
+// identities and anything we can't store safely go in the report.
+pub fn import_bitwarden_json(content: &str, mek: &[u8]) -> Result<ImportOutcome, CryptoError> {
+    let mut export: Export = serde_json::from_str(content)?;
+    if export.encrypted {
+        return Err(CryptoError::ImportFailed(
+            "encrypted Bitwarden exports aren't supported, export as unencrypted JSON".into(),
+        ));
+    }
+
+    let folders: HashMap<String, String> = export
+        .folders
+        .drain(..)
+        .map(|folder| (folder.id, folder.name))
+        .collect();
+
+    let mut outcome: ImportOutcome = ImportOutcome::new();
+    for mut item in export.items {
+        let result = import_item(&mut item, &folders, mek, &mut outcome);
+        if let Some(login) = item.login.as_mut() {
+            login.password.zeroize();
+            login.totp.zeroize();
+        }
+        for field in item.fields.iter_mut() {
+            field.value.zeroize();
+        }
+        result?;
+    }
+    Ok(outcome)
+}
+
+fn import_item(
+    item: &mut Item,
+    folders: &HashMap<String, String>,
+    mek: &[u8],
+    outcome: &mut ImportOutcome,
+) -> Result<(), CryptoError> {
+    let name: String = if item.name.trim().is_empty() {
+        "(unnamed item)".to_string()
+    } else {
+        item.name.clone()
+    };
+    if item.item_type != ITEM_LOGIN && item.item_type != ITEM_SECURE_NOTE {
+        outcome.skip(&name, "only logins and secure notes can be imported");
+        return Ok(());
+    }
+
+    let notes: Option<String> = item.notes.clone().filter(|notes| !notes.is_empty());
+    let (username, password, urls) = match item.login.as_ref() {
+        Some(login) => {
+            let urls: Vec<EntryUrl> = login
+                .uris
+                .iter()
+                .filter_map(|uri| {
+                    let url: &str = uri.uri.as_deref().filter(|url| !url.trim().is_empty())?;
+                    let match_mode: UrlMatch = uri
+                        .match_type
+                        .and_then(|code| URI_MATCHES.get(usize::from(code)).copied())
+                        .unwrap_or_default();
+                    Some(EntryUrl::with_match(url, match_mode))
+                })
+                .collect();
+            if login.totp.as_deref().is_some_and(|totp| !totp.is_empty()) {
+                outcome.skip(&format!("{} / TOTP", name), "TOTP secrets aren't supported");
+            }
+            (
+                login.username.clone().filter(|username| !username.is_empty()),
+                login.password.as_deref().filter(|password| !password.is_empty()),
+                urls,
+            )
+        }
+        None => (None, None, Vec::new()),
+    };
+
+    let mut custom_fields: Vec<DecryptedCustomField> = Vec::new();
+    for field in &item.fields {
+        let field_name: &str = field
+            .name
+            .as_deref()
+            .filter(|field_name| !field_name.trim().is_empty())
+            .unwrap_or("(unnamed)");
+        let value: &str = field.value.as_deref().unwrap_or_default();
+        let field_type: CustomFieldType = match field.field_type {
+            FIELD_TEXT => CustomFieldType::Text,
+            FIELD_HIDDEN => CustomFieldType::Hidden,
+            FIELD_BOOLEAN => CustomFieldType::Boolean,
+            _ => {
+                outcome.skip(&format!("{} / {}", name, field_name), "linked custom fields aren't supported");
+                continue;
+            }
+        };
+        let value: &str = match field_type {
+            CustomFieldType::Boolean if value.eq_ignore_ascii_case("true") => "true",
+            CustomFieldType::Boolean => "false",
+            _ => value,
+        };
+        custom_fields.push(DecryptedCustomField::new(field_name, field_type, value));
+    }
+
+    let folder: Option<String> = item
+        .folder_id
+        .as_ref()
+        .and_then(|folder_id| folders.get(folder_id).cloned());
+
+    let mut entry: PasswordEntry = PasswordEntry::new(
+        name,
+        username,
+        password.map(|password| password.as_bytes()),
+        None,
+        notes,
+        folder,
+        Some(item.favorite),
+        Some(mek),
+    )?;
+    if let Some(creation_date) = item
+        .creation_date
+        .as_deref()
+        .and_then(|date| DateTime::parse_from_rfc3339(date).ok())
+    {
+        entry.created = creation_date.with_timezone(&Utc);
+    }
+    entry.urls = urls;
+    entry.add_custom_fields(&custom_fields, mek)?;
+    outcome.push(entry);
+    Ok(())
+}
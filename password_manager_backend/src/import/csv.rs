@@ -0,0 +1,351 @@
+use crate::error_handling::CryptoError;
+use crate::import::{ImportOutcome, SkippedItem};
+use crate::password_entry::PasswordEntry;
+use chrono::{DateTime, Utc};
+use csv::{ReaderBuilder, StringRecord};
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, Zeroizing};
+
+const PREVIEW_ROWS: usize = 20;
+const LASTPASS_NOTE_URL: &str = "http://sn"; // LastPass puts this in the url column of secure notes
+
+// Which export the file came from, or a mapping of our fields onto the file's own headers
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", tag = "kind", content = "mapping")]
+pub enum CsvPreset {
+    Chrome,
+    Firefox,
+    Edge,
+    LastPass,
+    Custom(ColumnMapping),
+}
+
+// Header names (matched case-insensitively) for each field; None leaves the field empty
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ColumnMapping {
+    pub title: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub url: Option<String>,
+    pub notes: Option<String>,
+    #[serde(alias = "category")]
+    pub folder: Option<String>,
+    pub favorite: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CsvImportOptions {
+    pub preset: CsvPreset,
+    pub encoding: Option<String>, // WHATWG label such as "windows-1252"; detected when None
+}
+
+// What the user sees before anything is imported. Passwords are never part of it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CsvPreview {
+    pub headers: Vec<String>,
+    pub rows: Vec<CsvPreviewRow>,
+    pub total_rows: usize,
+    pub skipped: Vec<SkippedItem>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CsvPreviewRow {
+    pub title: String,
+    pub username: Option<String>,
+    pub url: Option<String>,
+    pub folder: Option<String>,
+    pub has_password: bool,
+}
+
+// One row after the preset has been applied
+struct CsvRow {
+    title: String,
+    username: Option<String>,
+    password: Option<String>,
+    url: Option<String>,
+    notes: Option<String>,
+    folder: Option<String>,
+    favorite: bool,
+    creation_date: Option<DateTime<Utc>>,
+}
+
+impl Drop for CsvRow {
+    fn drop(&mut self) {
+        self.password.zeroize();
+    }
+}
+
+// Column positions resolved against the file's header row
+struct Columns {
+    title: Option<usize>,
+    username: Option<usize>,
+    password: Option<usize>,
+    url: Option<usize>,
+    notes: Option<usize>,
+    folder: Option<usize>,
+    favorite: Option<usize>,
+    firefox_created: Option<usize>,
+    lastpass_totp: Option<usize>,
+}
+
+impl CsvPreset {
+    fn mapping(&self) -> ColumnMapping {
+        let name = |header: &str| Some(header.to_string());
+        match self {
+            CsvPreset::Chrome | CsvPreset::Edge => ColumnMapping {
+                title: name("name"),
+                username: name("username"),
+                password: name("password"),
+                url: name("url"),
+                notes: name("note"),
+                ..ColumnMapping::default()
+            },
+            CsvPreset::Firefox => ColumnMapping {
+                username: name("username"),
+                password: name("password"),
+                url: name("url"),
+                ..ColumnMapping::default()
+            },
+            CsvPreset::LastPass => ColumnMapping {
+                title: name("name"),
+                username: name("username"),
+                password: name("password"),
+                url: name("url"),
+                notes: name("extra"),
+                folder: name("grouping"),
+                favorite: name("fav"),
+            },
+            CsvPreset::Custom(mapping) => mapping.clone(),
+        }
+    }
+
+    // Columns that must be present for the preset to make sense
+    fn required(&self) -> &'static [&'static str] {
+        match self {
+            CsvPreset::Chrome | CsvPreset::Edge => &["name", "url", "username", "password"],
+            CsvPreset::Firefox => &["url", "username", "password"],
+            CsvPreset::LastPass => &["url", "username", "password", "name"],
+            CsvPreset::Custom(_) => &[],
+        }
+    }
+}
+
+impl Columns {
+    fn resolve(headers: &StringRecord, preset: &CsvPreset) -> Result<Self, CryptoError> {
+        let find = |name: &str| {
+            headers
+                .iter()
+                .position(|header| header.trim().eq_ignore_ascii_case(name.trim()))
+        };
+        for required in preset.required() {
+            if find(required).is_none() {
+                return Err(CryptoError::ImportFailed(format!("missing column '{}'", required)));
+            }
+        }
+
+        let mapping: ColumnMapping = preset.mapping();
+        let lookup = |header: &Option<String>| -> Result<Option<usize>, CryptoError> {
+            match header {
+                Some(header) => find(header)
+                    .map(Some)
+                    .ok_or_else(|| CryptoError::ImportFailed(format!("missing column '{}'", header))),
+                None => Ok(None),
+            }
+        };
+        let columns: Columns = Columns {
+            title: lookup(&mapping.title)?,
+            username: lookup(&mapping.username)?,
+            password: lookup(&mapping.password)?,
+            url: lookup(&mapping.url)?,
+            // Chrome only added the note column recently, so it's optional there
+            notes: if matches!(preset, CsvPreset::Chrome | CsvPreset::Edge) {
+                find("note")
+            } else {
+                lookup(&mapping.notes)?
+            },
+            folder: lookup(&mapping.folder)?,
+            favorite: lookup(&mapping.favorite)?,
+            firefox_created: if *preset == CsvPreset::Firefox { find("timeCreated") } else { None },
+            lastpass_totp: if *preset == CsvPreset::LastPass { find("totp") } else { None },
+        };
+        if columns.title.is_none() && columns.url.is_none() && columns.username.is_none() {
+            return Err(CryptoError::ImportFailed(
+                "map at least one of title, url or username".into(),
+            ));
+        }
+        Ok(columns)
+    }
+
+    fn row(&self, record: &StringRecord, preset: &CsvPreset) -> Option<CsvRow> {
+        let get = |column: Option<usize>| {
+            column
+                .and_then(|index| record.get(index))
+                .map(str::to_string)
+                .filter(|value| !value.trim().is_empty())
+        };
+
+        let mut url: Option<String> = get(self.url);
+        let is_lastpass_note: bool = *preset == CsvPreset::LastPass && url.as_deref() == Some(LASTPASS_NOTE_URL);
+        if is_lastpass_note {
+            url = None;
+        }
+        let username: Option<String> = get(self.username);
+        let password: Option<String> = get(self.password);
+        let notes: Option<String> = get(self.notes);
+        let title: Option<String> = get(self.title);
+        if title.is_none() && url.is_none() && username.is_none() && password.is_none() && notes.is_none() {
+            return None;
+        }
+
+        let title: String = title
+            .or_else(|| url.as_deref().and_then(host_of))
+            .or_else(|| username.clone())
+            .unwrap_or_else(|| "(untitled)".to_string());
+        let favorite: bool = get(self.favorite)
+            .map(|value| matches!(value.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+            .unwrap_or(false);
+        let creation_date: Option<DateTime<Utc>> = get(self.firefox_created)
+            .and_then(|millis| millis.trim().parse::<i64>().ok())
+            .and_then(DateTime::from_timestamp_millis);
+        // LastPass nests groups with backslashes
+        let folder: Option<String> = get(self.folder).map(|folder| match preset {
+            CsvPreset::LastPass => folder.replace('\\', "/"),
+            _ => folder,
+        });
+
+        Some(CsvRow {
+            title,
+            username,
+            password,
+            url,
+            notes,
+            folder,
+            favorite,
+            creation_date,
+        })
+    }
+}
+
+// Host part of a URL, used as the title when the export has no name column (Firefox)
+fn host_of(url: &str) -> Option<String> {
+    let rest: &str = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let authority: &str = rest.split(['/', '?', '#']).next()?;
+    let host: &str = authority.rsplit('@').next()?.split(':').next()?;
+    Some(host.to_string()).filter(|host| !host.is_empty())
+}
+
+// Decodes the raw file. A BOM wins, then an explicit label, then UTF-8 if it's valid, and
+// Windows-1252 otherwise (what Excel on Windows tends to save).
+pub fn decode_csv(data: &[u8], encoding: Option<&str>) -> Result<Zeroizing<String>, CryptoError> {
+    let encoding: &'static Encoding = match Encoding::for_bom(data) {
+        Some((encoding, _)) => encoding,
+        None => match encoding {
+            Some(label) => Encoding::for_label(label.as_bytes())
+                .ok_or_else(|| CryptoError::ImportFailed(format!("unknown encoding '{}'", label)))?,
+            None if std::str::from_utf8(data).is_ok() => UTF_8,
+            None => WINDOWS_1252,
+        },
+    };
+    let (text, _, had_errors) = encoding.decode(data);
+    if had_errors && (encoding == UTF_8 || encoding == UTF_16LE || encoding == UTF_16BE) {
+        return Err(CryptoError::ImportFailed(format!("file isn't valid {}", encoding.name())));
+    }
+    Ok(Zeroizing::new(text.into_owned()))
+}
+
+// Parses every row; `on_row` gets the mapped rows in file order, rows that can't be used are
+// reported by line number
+fn read_rows(
+    data: &[u8],
+    options: &CsvImportOptions,
+    skipped: &mut Vec<SkippedItem>,
+    mut on_row: impl FnMut(CsvRow) -> Result<(), CryptoError>,
+) -> Result<StringRecord, CryptoError> {
+    let text: Zeroizing<String> = decode_csv(data, options.encoding.as_deref())?;
+    let mut reader = ReaderBuilder::new().flexible(true).from_reader(text.as_bytes());
+    let headers: StringRecord = reader
+        .headers()
+        .map_err(|e| CryptoError::ImportFailed(e.to_string()))?
+        .clone();
+    let columns: Columns = Columns::resolve(&headers, &options.preset)?;
+
+    let mut record: StringRecord = StringRecord::new();
+    loop {
+        let line: u64 = reader.position().line();
+        match reader.read_record(&mut record) {
+            Ok(false) => break,
+            Ok(true) => {
+                if let Some(row) = columns.row(&record, &options.preset) {
+                    if columns.lastpass_totp.and_then(|index| record.get(index)).is_some_and(|totp| !totp.is_empty()) {
+                        skipped.push(SkippedItem {
+                            name: format!("{} / TOTP", row.title),
+                            reason: "TOTP secrets aren't supported".into(),
+                        });
+                    }
+                    on_row(row)?;
+                }
+            }
+            Err(error) => skipped.push(SkippedItem {
+                name: format!("line {}", line),
+                reason: error.to_string(),
+            }),
+        }
+        record.clear();
+    }
+    Ok(headers)
+}
+
+pub fn preview_csv(data: &[u8], options: &CsvImportOptions) -> Result<CsvPreview, CryptoError> {
+    let mut skipped: Vec<SkippedItem> = Vec::new();
+    let mut rows: Vec<CsvPreviewRow> = Vec::new();
+    let mut total_rows: usize = 0;
+    let headers: StringRecord = read_rows(data, options, &mut skipped, |row| {
+        total_rows += 1;
+        if rows.len() < PREVIEW_ROWS {
+            rows.push(CsvPreviewRow {
+                title: row.title.clone(),
+                username: row.username.clone(),
+                url: row.url.clone(),
+                folder: row.folder.clone(),
+                has_password: row.password.is_some(),
+            });
+        }
+        Ok(())
+    })?;
+
+    Ok(CsvPreview {
+        headers: headers.iter().map(str::to_string).collect(),
+        rows,
+        total_rows,
+        skipped,
+    })
+}
+
+pub fn import_csv(data: &[u8], options: &CsvImportOptions, mek: &[u8]) -> Result<ImportOutcome, CryptoError> {
+    let mut outcome: ImportOutcome = ImportOutcome::new();
+    let mut skipped: Vec<SkippedItem> = Vec::new();
+    read_rows(data, options, &mut skipped, |row| {
+        let mut entry: PasswordEntry = PasswordEntry::new(
+            row.title.clone(),
+            row.username.clone(),
+            row.password.as_deref().map(str::as_bytes),
+            row.url.clone(),
+            row.notes.clone(),
+            row.folder.clone(),
+            Some(row.favorite),
+            Some(mek),
+        )?;
+        if let Some(creation_date) = row.creation_date {
+            entry.created = creation_date;
+        }
+        outcome.push(entry);
+        Ok(())
+    })?;
+    outcome.report.skipped.extend(skipped);
+    Ok(outcome)
+}
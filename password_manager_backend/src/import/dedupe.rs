@@ -0,0 +1,156 @@
+use crate::import::{ImportReport, SkippedItem};
+use crate::password_entry::DecryptedPasswordEntry;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+// What to do with an imported entry that matches one already in the vault
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum MergeMode {
+    #[default]
+    SkipExisting,
+    // The vault entry's contents are replaced, but it keeps its id, dates, trash state, attachments
+    // and password history (which gains the replaced password), like an edit in the app
+    Overwrite,
+    KeepBoth,  // the imported copy is added alongside, with a new id if they share one
+}
+
+// A default for every conflict plus per-entry choices keyed by the imported entry's id
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeOptions {
+    pub default_mode: MergeMode,
+    #[serde(default)]
+    pub per_entry: HashMap<String, MergeMode>,
+}
+
+// An imported entry and the vault entry it looks like a copy of. Passwords are left out.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportConflict {
+    pub incoming_id: String,
+    pub incoming_title: String,
+    pub existing_id: String,
+    pub existing_title: String,
+    pub url: Option<String>,
+    pub username: Option<String>,
+    pub same_password: bool,
+}
+
+// An import that has been read but not yet added, waiting for the user to resolve its conflicts
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct StagedImport {
+    pub pending: usize,
+    pub conflicts: Vec<ImportConflict>,
+    pub skipped: Vec<SkippedItem>,
+}
+
+impl MergeOptions {
+    pub fn with_default(default_mode: MergeMode) -> Self {
+        Self { default_mode, per_entry: HashMap::new() }
+    }
+
+    fn mode_for(&self, incoming_id: &str) -> MergeMode {
+        self.per_entry.get(incoming_id).copied().unwrap_or(self.default_mode)
+    }
+}
+
+// Lowercased host (without "www." and default ports) and path (without trailing slash), so
+// "https://www.Example.com/login/" and "http://example.com/login?next=/" match. Scheme, query
+// and fragment are ignored.
+pub fn normalize_url(url: &str) -> Option<String> {
+    let url: &str = url.trim();
+    let rest: &str = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let rest: &str = rest.split(['?', '#']).next().unwrap_or_default();
+    let (authority, path) = rest.split_once('/').unwrap_or((rest, ""));
+    let host: &str = authority.rsplit('@').next().unwrap_or_default();
+    let host: String = host.to_ascii_lowercase();
+    let host: &str = host.strip_suffix(":443").or_else(|| host.strip_suffix(":80")).unwrap_or(&host);
+    let host: &str = host.strip_prefix("www.").unwrap_or(host);
+    if host.is_empty() {
+        return None;
+    }
+    let path: &str = path.trim_end_matches('/');
+    Some(if path.is_empty() { host.to_string() } else { format!("{}/{}", host, path) })
+}
+
+// Entries with neither a URL nor a username are never treated as duplicates by content
+fn dedupe_key(entry: &DecryptedPasswordEntry) -> Option<(Option<String>, String)> {
+    let url: Option<String> = entry.primary_url().and_then(normalize_url);
+    let username: String = entry.username.as_deref().unwrap_or_default().trim().to_lowercase();
+    if url.is_none() && username.is_empty() {
+        return None;
+    }
+    Some((url, username))
+}
+
+fn find_duplicate(vault: &[DecryptedPasswordEntry], entry: &DecryptedPasswordEntry) -> Option<usize> {
+    vault.iter().position(|e| e.id == entry.id).or_else(|| {
+        let key = dedupe_key(entry)?;
+        vault.iter().position(|e| dedupe_key(e).as_ref() == Some(&key))
+    })
+}
+
+// Every imported entry that matches a vault entry (by id, or by URL + username), including
+// copies of entries earlier in the same import
+pub fn find_conflicts(vault: &[DecryptedPasswordEntry], incoming: &[DecryptedPasswordEntry]) -> Vec<ImportConflict> {
+    let mut seen: Vec<DecryptedPasswordEntry> = vault.to_vec();
+    let mut conflicts: Vec<ImportConflict> = Vec::new();
+    for entry in incoming {
+        match find_duplicate(&seen, entry) {
+            Some(index) => conflicts.push(ImportConflict {
+                incoming_id: entry.id.clone(),
+                incoming_title: entry.title.clone(),
+                existing_id: seen[index].id.clone(),
+                existing_title: seen[index].title.clone(),
+                url: entry.primary_url().map(str::to_string),
+                username: entry.username.clone(),
+                same_password: seen[index].password == entry.password,
+            }),
+            None => seen.push(entry.clone()),
+        }
+    }
+    conflicts
+}
+
+// Adds `incoming` to the vault, resolving each duplicate the way `options` says
+pub fn merge_entries(
+    vault: &mut Vec<DecryptedPasswordEntry>,
+    incoming: Vec<DecryptedPasswordEntry>,
+    options: &MergeOptions,
+) -> ImportReport {
+    let mut report: ImportReport = ImportReport::default();
+    for mut entry in incoming {
+        let Some(index) = find_duplicate(vault, &entry) else {
+            vault.push(entry);
+            report.imported += 1;
+            continue;
+        };
+        match options.mode_for(&entry.id) {
+            MergeMode::SkipExisting => report.skipped.push(SkippedItem {
+                name: entry.title.clone(),
+                reason: format!("duplicate of '{}'", vault[index].title),
+            }),
+            MergeMode::Overwrite => {
+                let existing: &DecryptedPasswordEntry = &vault[index];
+                entry.id = existing.id.clone();
+                entry.attachments = existing.attachments.clone();
+                entry.keep_history_from(existing);
+                entry.keep_dates_from(existing);
+                entry.touch();
+                vault[index] = entry;
+                report.replaced += 1;
+            }
+            MergeMode::KeepBoth => {
+                if vault.iter().any(|e| e.id == entry.id) {
+                    entry.id = Uuid::new_v4().to_string();
+                }
+                vault.push(entry);
+                report.imported += 1;
+            }
+        }
+    }
+    report
+}
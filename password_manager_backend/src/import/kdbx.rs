@@ -0,0 +1,81 @@
+use crate::custom_field::{CustomFieldType, DecryptedCustomField};
+use crate::error_handling::CryptoError;
+use crate::folders::normalize_tags;
+use crate::import::ImportOutcome;
+use crate::kdbx::reader::{read_kdbx, KdbxDatabase};
+use crate::kdbx::{is_extra_url_key, KdbxEntry, FAVORITE_TAG};
+use crate::password_entry::PasswordEntry;
+use crate::url_match::EntryUrl;
+
+// Converts a KDBX 4 database into entries. The group path (below the root group) becomes the
+// folder, e.g. "Work/Servers"; custom fields stay custom fields (protected ones hidden), except
+// KeePassXC's "KP2A_URL" fields, which become extra URLs, and tags stay tags.
+pub fn import_kdbx(data: &[u8], password: &[u8], mek: &[u8]) -> Result<ImportOutcome, CryptoError> {
+    let database: KdbxDatabase = read_kdbx(data, password)?;
+
+    let mut outcome: ImportOutcome = ImportOutcome::new();
+    for entry in &database.entries {
+        import_entry(entry, mek, &mut outcome)?;
+    }
+    Ok(outcome)
+}
+
+fn import_entry(entry: &KdbxEntry, mek: &[u8], outcome: &mut ImportOutcome) -> Result<(), CryptoError> {
+    let title: String = entry.title.clone().unwrap_or_else(|| "(untitled)".to_string());
+    if entry.in_recycle_bin {
+        outcome.skip(&title, "entry is in the recycle bin");
+        return Ok(());
+    }
+
+    let mut urls: Vec<EntryUrl> = entry.url.iter().map(|url| EntryUrl::new(url)).collect();
+    urls.extend(
+        entry
+            .custom_fields
+            .iter()
+            .filter(|field| is_extra_url_key(&field.key) && !field.value.trim().is_empty())
+            .map(|field| EntryUrl::new(&field.value)),
+    );
+    let custom_fields: Vec<DecryptedCustomField> = entry
+        .custom_fields
+        .iter()
+        .filter(|field| !is_extra_url_key(&field.key))
+        .map(|field| {
+            let field_type: CustomFieldType = if field.protected {
+                CustomFieldType::Hidden
+            } else {
+                CustomFieldType::Text
+            };
+            DecryptedCustomField::new(&field.key, field_type, &field.value)
+        })
+        .collect();
+    let tags: Vec<String> = entry
+        .tags
+        .iter()
+        .filter(|tag| *tag != FAVORITE_TAG)
+        .cloned()
+        .collect();
+    for attachment in &entry.attachments {
+        outcome.skip(&format!("{} / {}", title, attachment), "attachments aren't supported");
+    }
+
+    let folder: Option<String> = Some(entry.group_path.join("/")).filter(|path| !path.is_empty());
+    let mut password_entry: PasswordEntry = PasswordEntry::new(
+        title,
+        entry.username.clone(),
+        entry.password.as_deref().map(str::as_bytes),
+        None,
+        entry.notes.clone(),
+        folder,
+        Some(entry.tags.iter().any(|tag| tag == FAVORITE_TAG)),
+        Some(mek),
+    )?;
+    if let Some(creation_time) = entry.creation_time {
+        password_entry.created = creation_time;
+    }
+    password_entry.urls = urls;
+    password_entry.expires = entry.expiry_time;
+    password_entry.tags = normalize_tags(&tags);
+    password_entry.add_custom_fields(&custom_fields, mek)?;
+    outcome.push(password_entry);
+    Ok(())
+}
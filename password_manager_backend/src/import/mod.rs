@@ -0,0 +1,67 @@
+pub mod bitwarden;
+pub mod csv;
+pub mod dedupe;
+pub mod kdbx;
+pub mod onepux;
+pub mod pass;
+pub mod portable;
+
+use crate::password_entry::PasswordEntry;
+use serde::{Deserialize, Serialize};
+
+// What an importer produced: entries ready to add to the vault (passwords already encrypted
+// with the MEK) and a report for the user
+#[derive(Debug)]
+pub struct ImportOutcome {
+    pub entries: Vec<PasswordEntry>,
+    pub report: ImportReport,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportReport {
+    pub imported: usize,
+    #[serde(default)]
+    pub replaced: usize, // existing entries overwritten by a merge
+    pub skipped: Vec<SkippedItem>,
+}
+
+// An item (or the part of one) that couldn't be brought over, with the reason shown to the user
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SkippedItem {
+    pub name: String,
+    pub reason: String,
+}
+
+impl ImportOutcome {
+    pub(crate) fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            report: ImportReport::default(),
+        }
+    }
+
+    pub(crate) fn push(&mut self, entry: PasswordEntry) {
+        self.entries.push(entry);
+        self.report.imported += 1;
+    }
+
+    pub(crate) fn skip(&mut self, name: &str, reason: &str) {
+        self.report.skipped.push(SkippedItem {
+            name: name.to_string(),
+            reason: reason.to_string(),
+        });
+    }
+}
+
+// Appends `line` to the notes being built up for an imported entry
+pub(crate) fn append_note(notes: &mut Option<String>, line: &str) {
+    match notes {
+        Some(existing) if !existing.is_empty() => {
+            existing.push('\n');
+            existing.push_str(line);
+        }
+        _ => *notes = Some(line.to_string()),
+    }
+}
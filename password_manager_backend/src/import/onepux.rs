@@ -0,0 +1,277 @@
+use crate::custom_field::{CustomFieldType, DecryptedCustomField};
+use crate::error_handling::CryptoError;
+use crate::folders::normalize_tags;
+use crate::import::ImportOutcome;
+use crate::password_entry::PasswordEntry;
+use crate::url_match::EntryUrl;
+use chrono::DateTime;
+use serde::Deserialize;
+use serde_json::Value;
+use std::io::{Cursor, Read};
+use zeroize::{Zeroize, Zeroizing};
+use zip::ZipArchive;
+
+const EXPORT_DATA: &str = "export.data";
+
+// 1Password category codes
+const CATEGORY_LOGIN: &str = "001";
+const CATEGORY_SECURE_NOTE: &str = "003";
+const CATEGORY_PASSWORD: &str = "005";
+
+#[derive(Deserialize)]
+struct Export {
+    #[serde(default)]
+    accounts: Vec<Account>,
+}
+
+#[derive(Deserialize)]
+struct Account {
+    #[serde(default)]
+    vaults: Vec<Vault>,
+}
+
+#[derive(Deserialize)]
+struct Vault {
+    attrs: VaultAttrs,
+    #[serde(default)]
+    items: Vec<Item>,
+}
+
+#[derive(Deserialize)]
+struct VaultAttrs {
+    #[serde(default)]
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Item {
+    #[serde(default)]
+    fav_index: u32,
+    created_at: Option<i64>,
+    #[serde(default)]
+    category_uuid: String,
+    #[serde(default)]
+    details: Details,
+    #[serde(default)]
+    overview: Overview,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct Details {
+    #[serde(default)]
+    login_fields: Vec<LoginField>,
+    notes_plain: Option<String>,
+    #[serde(default)]
+    sections: Vec<Section>,
+    password: Option<String>,
+    document_attributes: Option<DocumentAttributes>,
+}
+
+#[derive(Deserialize)]
+struct LoginField {
+    #[serde(default)]
+    value: String,
+    designation: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Section {
+    #[serde(default)]
+    fields: Vec<SectionField>,
+}
+
+#[derive(Deserialize)]
+struct SectionField {
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    value: Value,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DocumentAttributes {
+    #[serde(default)]
+    file_name: String,
+}
+
+#[derive(Deserialize, Default)]
+struct Overview {
+    #[serde(default)]
+    title: String,
+    url: Option<String>,
+    #[serde(default)]
+    urls: Vec<OverviewUrl>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct OverviewUrl {
+    #[serde(default)]
+    url: String,
+}
+
+// Reads a 1Password `.1pux` archive. Logins, passwords and secure notes become entries with all
+// their URLs and tags, the vault name as their folder and section fields as custom fields; every other item
+// type goes in the report.
+pub fn import_1pux(data: &[u8], mek: &[u8]) -> Result<ImportOutcome, CryptoError> {
+    let mut archive: ZipArchive<Cursor<&[u8]>> =
+        ZipArchive::new(Cursor::new(data)).map_err(|e| CryptoError::ImportFailed(e.to_string()))?;
+    let mut content: Zeroizing<String> = Zeroizing::new(String::new());
+    archive
+        .by_name(EXPORT_DATA)
+        .map_err(|_| CryptoError::ImportFailed(format!("archive has no {}", EXPORT_DATA)))?
+        .read_to_string(&mut content)
+        .map_err(|e| CryptoError::ImportFailed(e.to_string()))?;
+    let export: Export = serde_json::from_str(&content)?;
+
+    let mut outcome: ImportOutcome = ImportOutcome::new();
+    for account in export.accounts {
+        for vault in account.vaults {
+            for mut item in vault.items {
+                let result = import_item(&item, &vault.attrs.name, mek, &mut outcome);
+                item.details.password.zeroize();
+                for field in item.details.login_fields.iter_mut() {
+                    field.value.zeroize();
+                }
+                for section in item.details.sections.iter_mut() {
+                    for object in section.fields.iter_mut().filter_map(|field| field.value.as_object_mut()) {
+                        for value in object.values_mut() {
+                            if let Value::String(text) = value {
+                                text.zeroize();
+                            }
+                        }
+                    }
+                }
+                result?;
+            }
+        }
+    }
+    Ok(outcome)
+}
+
+fn import_item(item: &Item, vault: &str, mek: &[u8], outcome: &mut ImportOutcome) -> Result<(), CryptoError> {
+    let title: String = if item.overview.title.trim().is_empty() {
+        "(untitled)".to_string()
+    } else {
+        item.overview.title.clone()
+    };
+    if !matches!(
+        item.category_uuid.as_str(),
+        CATEGORY_LOGIN | CATEGORY_SECURE_NOTE | CATEGORY_PASSWORD
+    ) {
+        let reason: String = format!("1Password {} items aren't supported", category_name(&item.category_uuid));
+        outcome.skip(&title, &reason);
+        return Ok(());
+    }
+
+    let designated = |designation: &str| {
+        item.details
+            .login_fields
+            .iter()
+            .find(|field| field.designation.as_deref() == Some(designation))
+            .map(|field| field.value.as_str())
+            .filter(|value| !value.is_empty())
+    };
+    let username: Option<String> = designated("username").map(str::to_string);
+    let password: Option<&str> = designated("password")
+        .or(item.details.password.as_deref())
+        .filter(|password| !password.is_empty());
+
+    let notes: Option<String> = item.details.notes_plain.clone().filter(|notes| !notes.is_empty());
+    // the main URL first, then any others the item lists
+    let mut urls: Vec<EntryUrl> = Vec::new();
+    for url in item.overview.url.iter().chain(item.overview.urls.iter().map(|url| &url.url)) {
+        if !url.trim().is_empty() && !urls.iter().any(|existing| existing.url == url.trim()) {
+            urls.push(EntryUrl::new(url));
+        }
+    }
+
+    let mut custom_fields: Vec<DecryptedCustomField> = Vec::new();
+    for field in item.details.sections.iter().flat_map(|section| &section.fields) {
+        custom_fields.extend(import_section_field(field, &title, outcome));
+    }
+    if let Some(document) = &item.details.document_attributes {
+        outcome.skip(&format!("{} / {}", title, document.file_name), "attachments aren't supported");
+    }
+
+    let folder: Option<String> = Some(vault.to_string()).filter(|vault| !vault.is_empty());
+    let mut entry: PasswordEntry = PasswordEntry::new(
+        title,
+        username,
+        password.map(str::as_bytes),
+        None,
+        notes,
+        folder,
+        Some(item.fav_index > 0),
+        Some(mek),
+    )?;
+    if let Some(created_at) = item.created_at.and_then(|secs| DateTime::from_timestamp(secs, 0)) {
+        entry.created = created_at;
+    }
+    entry.urls = urls;
+    entry.tags = normalize_tags(&item.overview.tags);
+    entry.add_custom_fields(&custom_fields, mek)?;
+    outcome.push(entry);
+    Ok(())
+}
+
+// Section field values are single-key objects such as {"string": "..."} or {"concealed": "..."}
+fn import_section_field(field: &SectionField, title: &str, outcome: &mut ImportOutcome) -> Option<DecryptedCustomField> {
+    let (kind, value) = field.value.as_object().and_then(|value| value.iter().next())?;
+    let name: String = format!("{} / {}", title, field.title);
+    let text: Option<String> = match value {
+        Value::String(text) => Some(text.clone()),
+        Value::Number(number) => Some(number.to_string()),
+        // newer exports wrap emails as {"email_address": "...", "provider": ...}
+        Value::Object(object) => object.get("email_address").and_then(Value::as_str).map(str::to_string),
+        _ => None,
+    };
+    let field_type: CustomFieldType = match (kind.as_str(), &text) {
+        ("totp", _) => {
+            outcome.skip(&name, "TOTP secrets aren't supported");
+            return None;
+        }
+        (_, Some(text)) if text.is_empty() => return None,
+        ("concealed", Some(_)) => CustomFieldType::Hidden,
+        ("url", Some(_)) => CustomFieldType::Url,
+        ("string" | "email" | "phone" | "menu", Some(_)) => CustomFieldType::Text,
+        _ => {
+            outcome.skip(&name, &format!("{} fields aren't supported", kind));
+            return None;
+        }
+    };
+    let field_name: &str = if field.title.trim().is_empty() { kind } else { &field.title };
+    let mut text: String = text.unwrap_or_default();
+    let custom_field: DecryptedCustomField = DecryptedCustomField::new(field_name, field_type, &text);
+    text.zeroize();
+    Some(custom_field)
+}
+
+fn category_name(category_uuid: &str) -> &str {
+    match category_uuid {
+        "002" => "credit card",
+        "004" => "identity",
+        "006" => "document",
+        "100" => "software license",
+        "101" => "bank account",
+        "102" => "database",
+        "103" => "driver license",
+        "104" => "outdoor license",
+        "105" => "membership",
+        "106" => "passport",
+        "107" => "reward program",
+        "108" => "social security number",
+        "109" => "wireless router",
+        "110" => "server",
+        "111" => "email account",
+        "112" => "API credential",
+        "113" => "medical record",
+        "114" => "SSH key",
+        "115" => "crypto wallet",
+        _ => "unknown",
+    }
+}
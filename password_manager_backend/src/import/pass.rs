@@ -0,0 +1,195 @@
+use crate::custom_field::{CustomFieldType, DecryptedCustomField};
+use crate::error_handling::CryptoError;
+use crate::import::{append_note, ImportOutcome};
+use crate::password_entry::PasswordEntry;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use zeroize::Zeroizing;
+
+const GPG_EXTENSION: &str = "gpg";
+const GPG_NAMES: [&str; 2] = ["gpg", "gpg2"];
+// Where the usual installers put gpg, for apps started without the shell's PATH (macOS GUI apps)
+const GPG_INSTALL_DIRS: [&str; 5] = [
+    "/usr/local/bin",
+    "/opt/homebrew/bin",
+    "/usr/local/MacGPG2/bin",
+    "C:\\Program Files (x86)\\GnuPG\\bin",
+    "C:\\Program Files\\GnuPG\\bin",
+];
+// Words in a field name that mark its value as a secret
+const SECRET_WORDS: [&str; 7] = ["password", "passphrase", "pin", "secret", "token", "cvv", "cvc"];
+
+// Turns one encrypted file from the store into its plaintext
+pub trait PassDecryptor {
+    fn decrypt(&self, path: &Path) -> Result<Zeroizing<Vec<u8>>, CryptoError>;
+}
+
+// Runs the local gpg binary, which asks gpg-agent (and its pinentry) for the key's passphrase
+pub struct GpgDecryptor {
+    binary: PathBuf,
+}
+
+impl GpgDecryptor {
+    // Looks gpg up on PATH and then in the usual install locations
+    pub fn new() -> Self {
+        Self::with_binary(locate_gpg().unwrap_or_else(|| PathBuf::from("gpg")))
+    }
+
+    pub fn with_binary(binary: impl Into<PathBuf>) -> Self {
+        Self { binary: binary.into() }
+    }
+}
+
+impl Default for GpgDecryptor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PassDecryptor for GpgDecryptor {
+    fn decrypt(&self, path: &Path) -> Result<Zeroizing<Vec<u8>>, CryptoError> {
+        let output: Output = Command::new(&self.binary)
+            .args(["--quiet", "--batch", "--decrypt"])
+            .arg(path)
+            .output()
+            .map_err(|e| CryptoError::ImportFailed(format!("couldn't run {}: {}", self.binary.display(), e)))?;
+        let stdout: Zeroizing<Vec<u8>> = Zeroizing::new(output.stdout);
+        if !output.status.success() {
+            let stderr: String = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            return Err(CryptoError::ImportFailed(stderr));
+        }
+        Ok(stdout)
+    }
+}
+
+fn locate_gpg() -> Option<PathBuf> {
+    let path_dirs: Vec<PathBuf> = env::var_os("PATH").map(|path| env::split_paths(&path).collect()).unwrap_or_default();
+    path_dirs
+        .into_iter()
+        .chain(GPG_INSTALL_DIRS.iter().map(PathBuf::from))
+        .flat_map(|dir| GPG_NAMES.map(|name| dir.join(format!("{}{}", name, env::consts::EXE_SUFFIX))))
+        .find(|candidate| candidate.is_file())
+}
+
+// Walks a password-store directory. Each .gpg file becomes an entry named after the file, with
+// its directory (relative to `root`) as the folder. In the decrypted text the first line is
+// the password, login/url lines fill those fields, other `key: value` lines become custom
+// fields (hidden when the key names a secret) and the remaining lines go into notes.
+pub fn import_pass_store(root: &Path, decryptor: &dyn PassDecryptor, mek: &[u8]) -> Result<ImportOutcome, CryptoError> {
+    if !root.is_dir() {
+        return Err(CryptoError::ImportFailed(format!("{} isn't a directory", root.display())));
+    }
+    let mut files: Vec<PathBuf> = Vec::new();
+    collect_files(root, &mut files)?;
+
+    let mut outcome: ImportOutcome = ImportOutcome::new();
+    for path in files {
+        let relative: &Path = path.strip_prefix(root).unwrap_or(&path);
+        let name: String = relative.with_extension("").to_string_lossy().replace('\\', "/");
+        match decryptor.decrypt(&path) {
+            Ok(plaintext) => import_file(relative, &name, &plaintext, mek, &mut outcome)?,
+            Err(error) => outcome.skip(&name, &error.to_string()),
+        }
+    }
+    Ok(outcome)
+}
+
+// Sorted so the import order (and the report) doesn't depend on the file system. Dot entries
+// such as .git and .gpg-id are store metadata.
+fn collect_files(directory: &Path, files: &mut Vec<PathBuf>) -> Result<(), CryptoError> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(directory)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<_, _>>()?;
+    paths.sort();
+    for path in paths {
+        if path.file_name().and_then(|name| name.to_str()).is_some_and(|name| name.starts_with('.')) {
+            continue;
+        }
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else if path.extension().and_then(|extension| extension.to_str()) == Some(GPG_EXTENSION) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn import_file(
+    relative: &Path,
+    name: &str,
+    plaintext: &[u8],
+    mek: &[u8],
+    outcome: &mut ImportOutcome,
+) -> Result<(), CryptoError> {
+    let Ok(text) = std::str::from_utf8(plaintext) else {
+        outcome.skip(name, "contents aren't text");
+        return Ok(());
+    };
+    let mut lines = text.lines();
+    let password: Option<&str> = lines.next().filter(|password| !password.is_empty());
+
+    let mut username: Option<String> = None;
+    let mut url: Option<String> = None;
+    let mut notes: Option<String> = None;
+    let mut custom_fields: Vec<DecryptedCustomField> = Vec::new();
+    for line in lines {
+        if line.trim_start().starts_with("otpauth://") {
+            outcome.skip(&format!("{} / otpauth", name), "TOTP secrets aren't supported");
+            continue;
+        }
+        // A bare link such as "https://..." is not a key/value pair
+        let field = line
+            .split_once(':')
+            .map(|(key, value)| (key.trim(), value.trim()))
+            .filter(|(key, value)| !key.is_empty() && !value.starts_with("//"));
+        match field {
+            Some((key, value)) if is_username_key(key) && username.is_none() => username = Some(value.to_string()),
+            Some((key, value)) if is_url_key(key) && url.is_none() => url = Some(value.to_string()),
+            Some((key, value)) => custom_fields.push(DecryptedCustomField::new(key, field_type(key), value)),
+            None => append_note(&mut notes, line),
+        }
+    }
+    let file_name: String = relative
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| name.to_string());
+    let folder: Option<String> = relative
+        .parent()
+        .map(|parent| parent.to_string_lossy().replace('\\', "/"))
+        .filter(|parent| !parent.is_empty());
+
+    let mut entry: PasswordEntry = PasswordEntry::new(
+        file_name,
+        username,
+        password.map(str::as_bytes),
+        url,
+        notes.filter(|notes| !notes.trim().is_empty()),
+        folder,
+        Some(false),
+        Some(mek),
+    )?;
+    entry.add_custom_fields(&custom_fields, mek)?;
+    outcome.push(entry);
+    Ok(())
+}
+
+fn is_username_key(key: &str) -> bool {
+    ["login", "username", "user", "email"].iter().any(|name| key.eq_ignore_ascii_case(name))
+}
+
+fn is_url_key(key: &str) -> bool {
+    ["url", "website", "site"].iter().any(|name| key.eq_ignore_ascii_case(name))
+}
+
+fn field_type(key: &str) -> CustomFieldType {
+    let secret: bool = key
+        .split(|c: char| !c.is_alphanumeric())
+        .any(|word| SECRET_WORDS.iter().any(|secret| word.eq_ignore_ascii_case(secret)));
+    if secret {
+        CustomFieldType::Hidden
+    } else {
+        CustomFieldType::Text
+    }
+}
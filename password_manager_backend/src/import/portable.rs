@@ -0,0 +1,33 @@
+use crate::crypto::Cryptographer;
+use crate::error_handling::CryptoError;
+use crate::export::portable::{PortableFile, PortablePayload, PORTABLE_FORMAT, PORTABLE_VERSION};
+use crate::password_entry::DecryptedPasswordEntry;
+use zeroize::Zeroizing;
+
+// Opens a file written by `export_portable`. A wrong passphrase is an AuthenticationFailed.
+pub fn read_portable(data: &[u8], passphrase: &[u8]) -> Result<Vec<DecryptedPasswordEntry>, CryptoError> {
+    let file: PortableFile = serde_json::from_slice(data)
+        .map_err(|_| CryptoError::ImportFailed("not a PawPass portable export".into()))?;
+    if file.format != PORTABLE_FORMAT {
+        return Err(CryptoError::ImportFailed("not a PawPass portable export".into()));
+    }
+    if file.version > PORTABLE_VERSION {
+        return Err(CryptoError::ImportFailed(format!(
+            "export format version {} is newer than this app supports",
+            file.version
+        )));
+    }
+
+    let cryptographer: Cryptographer = Cryptographer::new(None);
+    let serialized: Zeroizing<Vec<u8>> = Zeroizing::new(
+        cryptographer
+            .decrypt(file.data, passphrase)
+            .map_err(|_| CryptoError::AuthenticationFailed)?,
+    );
+    let mut payload: PortablePayload = serde_json::from_slice(&serialized)?;
+    // exports from before folders and tags carry a category instead
+    for entry in payload.entries.iter_mut() {
+        entry.migrate_category();
+    }
+    Ok(payload.entries)
+}
@@ -0,0 +1,459 @@
+pub mod reader;
+pub mod writer;
+
+use crate::error_handling::CryptoError;
+use aes::cipher::{BlockEncrypt, KeyInit, KeyIvInit, StreamCipher};
+use aes::Aes256;
+use argon2::{Algorithm, Argon2, Params, Version};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256, Sha512};
+use std::collections::HashMap;
+use zeroize::{Zeroize, Zeroizing};
+
+pub(crate) const SIGNATURE_1: u32 = 0x9AA2_D903;
+pub(crate) const SIGNATURE_2: u32 = 0xB54B_FB67;
+pub(crate) const MAJOR_VERSION: u16 = 4;
+
+// Outer header field ids
+pub(crate) const HEADER_END: u8 = 0;
+pub(crate) const HEADER_CIPHER_ID: u8 = 2;
+pub(crate) const HEADER_COMPRESSION: u8 = 3;
+pub(crate) const HEADER_MASTER_SEED: u8 = 4;
+pub(crate) const HEADER_ENCRYPTION_IV: u8 = 7;
+pub(crate) const HEADER_KDF_PARAMETERS: u8 = 11;
+
+// Inner header field ids
+pub(crate) const INNER_HEADER_END: u8 = 0;
+pub(crate) const INNER_STREAM_ID: u8 = 1;
+pub(crate) const INNER_STREAM_KEY: u8 = 2;
+
+pub(crate) const CIPHER_AES256: [u8; 16] = hex_uuid(*b"31c1f2e6bf714350be5805216afc5aff");
+pub(crate) const CIPHER_CHACHA20: [u8; 16] = hex_uuid(*b"d6038a2b8b6f4cb5a524339a31dbb59a");
+pub(crate) const KDF_AES: [u8; 16] = hex_uuid(*b"c9d9f39a628a4460bf740d08c18a4fea");
+pub(crate) const KDF_ARGON2D: [u8; 16] = hex_uuid(*b"ef636ddf8c29444b91f7a9a403e30a0c");
+pub(crate) const KDF_ARGON2ID: [u8; 16] = hex_uuid(*b"9e298b1956db4773b23dfc3ec6f0a1e6");
+
+pub(crate) const STREAM_SALSA20: u32 = 2;
+pub(crate) const STREAM_CHACHA20: u32 = 3;
+const SALSA20_NONCE: [u8; 8] = [0xE8, 0x30, 0x09, 0x4B, 0x97, 0x20, 0x5D, 0x2A];
+
+// KDBX 4 stores times as seconds since 0001-01-01T00:00:00Z
+const KDBX_EPOCH_OFFSET: i64 = 62_135_596_800;
+
+// The most a file may ask of the KDF, so a crafted file can't exhaust memory or hang the import
+const MAX_ARGON2_MEMORY_BYTES: u64 = 1024 * 1024 * 1024;
+const MAX_AES_KDF_ROUNDS: u64 = 1_000_000_000;
+
+// Argon2id cost used when writing a database
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Argon2Settings {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for Argon2Settings {
+    fn default() -> Self {
+        Self {
+            memory_kib: 64 * 1024,
+            iterations: 3,
+            parallelism: 2,
+        }
+    }
+}
+
+// Tag PawPass uses for favorites, since KeePass has no favorite flag of its own
+pub const FAVORITE_TAG: &str = "Favorite";
+
+// KeePass has a single URL per entry; KeePassXC and KeePass2Android keep the others in custom
+// fields named "KP2A_URL", "KP2A_URL_1", "KP2A_URL_2", ...
+pub const EXTRA_URL_FIELD: &str = "KP2A_URL";
+
+pub fn extra_url_key(index: usize) -> String {
+    match index {
+        0 => EXTRA_URL_FIELD.to_string(),
+        index => format!("{}_{}", EXTRA_URL_FIELD, index),
+    }
+}
+
+pub fn is_extra_url_key(key: &str) -> bool {
+    key.strip_prefix(EXTRA_URL_FIELD)
+        .is_some_and(|rest| rest.is_empty() || rest.strip_prefix('_').is_some_and(|n| n.parse::<usize>().is_ok()))
+}
+
+// One entry as KeePass sees it, independent of how PawPass stores it
+#[derive(Debug, Clone, Default)]
+pub struct KdbxEntry {
+    pub uuid: Option<[u8; 16]>,
+    pub group_path: Vec<String>, // group names below the root group
+    pub title: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub url: Option<String>,
+    pub notes: Option<String>,
+    pub custom_fields: Vec<KdbxField>,
+    pub tags: Vec<String>,
+    pub attachments: Vec<String>,
+    pub creation_time: Option<DateTime<Utc>>,
+    pub expiry_time: Option<DateTime<Utc>>, // None unless the entry expires
+    pub in_recycle_bin: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KdbxField {
+    pub key: String,
+    pub value: String,
+    pub protected: bool,
+}
+
+impl Drop for KdbxEntry {
+    fn drop(&mut self) {
+        self.password.zeroize();
+        for field in self.custom_fields.iter_mut() {
+            field.value.zeroize();
+        }
+    }
+}
+
+const fn hex_uuid(text: [u8; 32]) -> [u8; 16] {
+    const fn nibble(c: u8) -> u8 {
+        match c {
+            b'0'..=b'9' => c - b'0',
+            _ => c - b'a' + 10,
+        }
+    }
+    let mut out: [u8; 16] = [0u8; 16];
+    let mut i: usize = 0;
+    while i < 16 {
+        out[i] = (nibble(text[2 * i]) << 4) | nibble(text[2 * i + 1]);
+        i += 1;
+    }
+    out
+}
+
+pub(crate) fn format_error(reason: &str) -> CryptoError {
+    CryptoError::ImportFailed(format!("not a valid KDBX 4 file ({})", reason))
+}
+
+// Little-endian reader over the file bytes that errors instead of panicking on truncated input
+pub(crate) struct ByteReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    pub(crate) fn position(&self) -> usize {
+        self.pos
+    }
+
+    pub(crate) fn remaining(&self) -> &'a [u8] {
+        &self.data[self.pos..]
+    }
+
+    pub(crate) fn take(&mut self, len: usize) -> Result<&'a [u8], CryptoError> {
+        let end: usize = self.pos.checked_add(len).filter(|end| *end <= self.data.len())
+            .ok_or_else(|| format_error("unexpected end of data"))?;
+        let bytes: &'a [u8] = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    pub(crate) fn read_u8(&mut self) -> Result<u8, CryptoError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub(crate) fn read_u16(&mut self) -> Result<u16, CryptoError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn read_u32(&mut self) -> Result<u32, CryptoError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn read_i32(&mut self) -> Result<i32, CryptoError> {
+        Ok(i32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn read_u64(&mut self) -> Result<u64, CryptoError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
+
+// Typed values of a KDBX VariantDictionary (used for the KDF parameters)
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Variant {
+    UInt32(u32),
+    UInt64(u64),
+    Bool(bool),
+    Int32(i32),
+    Int64(i64),
+    String(String),
+    Bytes(Vec<u8>),
+}
+
+pub(crate) fn parse_variant_dictionary(data: &[u8]) -> Result<HashMap<String, Variant>, CryptoError> {
+    let mut reader: ByteReader = ByteReader::new(data);
+    if reader.read_u16()? >> 8 != 1 {
+        return Err(format_error("unsupported variant dictionary version"));
+    }
+
+    let mut dictionary: HashMap<String, Variant> = HashMap::new();
+    loop {
+        let value_type: u8 = reader.read_u8()?;
+        if value_type == 0 {
+            return Ok(dictionary);
+        }
+        let name_len: usize = reader.read_i32()?.try_into().map_err(|_| format_error("negative length"))?;
+        let name: String = String::from_utf8_lossy(reader.take(name_len)?).into_owned();
+        let value_len: usize = reader.read_i32()?.try_into().map_err(|_| format_error("negative length"))?;
+        let value: &[u8] = reader.take(value_len)?;
+
+        let mut value_reader: ByteReader = ByteReader::new(value);
+        let variant: Variant = match value_type {
+            0x04 => Variant::UInt32(value_reader.read_u32()?),
+            0x05 => Variant::UInt64(value_reader.read_u64()?),
+            0x08 => Variant::Bool(value_reader.read_u8()? != 0),
+            0x0C => Variant::Int32(value_reader.read_i32()?),
+            0x0D => Variant::Int64(value_reader.read_u64()? as i64),
+            0x18 => Variant::String(String::from_utf8_lossy(value).into_owned()),
+            0x42 => Variant::Bytes(value.to_vec()),
+            _ => return Err(format_error("unknown variant type")),
+        };
+        dictionary.insert(name, variant);
+    }
+}
+
+pub(crate) fn write_variant_dictionary(entries: &[(&str, Variant)]) -> Vec<u8> {
+    let mut out: Vec<u8> = 0x0100u16.to_le_bytes().to_vec();
+    for (name, variant) in entries {
+        let (value_type, value): (u8, Vec<u8>) = match variant {
+            Variant::UInt32(value) => (0x04, value.to_le_bytes().to_vec()),
+            Variant::UInt64(value) => (0x05, value.to_le_bytes().to_vec()),
+            Variant::Bool(value) => (0x08, vec![*value as u8]),
+            Variant::Int32(value) => (0x0C, value.to_le_bytes().to_vec()),
+            Variant::Int64(value) => (0x0D, value.to_le_bytes().to_vec()),
+            Variant::String(value) => (0x18, value.as_bytes().to_vec()),
+            Variant::Bytes(value) => (0x42, value.clone()),
+        };
+        out.push(value_type);
+        out.extend_from_slice(&(name.len() as i32).to_le_bytes());
+        out.extend_from_slice(name.as_bytes());
+        out.extend_from_slice(&(value.len() as i32).to_le_bytes());
+        out.extend_from_slice(&value);
+    }
+    out.push(0);
+    out
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum KdfParameters {
+    Aes { rounds: u64, seed: Vec<u8> },
+    Argon2 {
+        algorithm: Algorithm,
+        salt: Vec<u8>,
+        iterations: u64,
+        memory_bytes: u64,
+        parallelism: u32,
+    },
+}
+
+impl KdfParameters {
+    pub(crate) fn from_dictionary(dictionary: &HashMap<String, Variant>) -> Result<Self, CryptoError> {
+        let bytes = |name: &str| match dictionary.get(name) {
+            Some(Variant::Bytes(bytes)) => Ok(bytes.clone()),
+            _ => Err(format_error("missing KDF parameter")),
+        };
+        let uint64 = |name: &str| match dictionary.get(name) {
+            Some(Variant::UInt64(value)) => Ok(*value),
+            _ => Err(format_error("missing KDF parameter")),
+        };
+
+        let uuid: Vec<u8> = bytes("$UUID")?;
+        if uuid == KDF_AES {
+            return Ok(KdfParameters::Aes {
+                rounds: uint64("R")?,
+                seed: bytes("S")?,
+            });
+        }
+
+        let algorithm: Algorithm = if uuid == KDF_ARGON2D {
+            Algorithm::Argon2d
+        } else if uuid == KDF_ARGON2ID {
+            Algorithm::Argon2id
+        } else {
+            return Err(CryptoError::ImportFailed("unsupported key derivation function".into()));
+        };
+        if matches!(dictionary.get("V"), Some(Variant::UInt32(version)) if *version != 0x13) {
+            return Err(CryptoError::ImportFailed("unsupported Argon2 version".into()));
+        }
+        if matches!(dictionary.get("K"), Some(Variant::Bytes(secret)) if !secret.is_empty())
+            || matches!(dictionary.get("A"), Some(Variant::Bytes(data)) if !data.is_empty())
+        {
+            return Err(CryptoError::ImportFailed("Argon2 secret keys aren't supported".into()));
+        }
+        let parallelism: u32 = match dictionary.get("P") {
+            Some(Variant::UInt32(parallelism)) => *parallelism,
+            _ => return Err(format_error("missing KDF parameter")),
+        };
+
+        Ok(KdfParameters::Argon2 {
+            algorithm,
+            salt: bytes("S")?,
+            iterations: uint64("I")?,
+            memory_bytes: uint64("M")?,
+            parallelism,
+        })
+    }
+
+    pub(crate) fn to_dictionary(&self) -> Vec<u8> {
+        match self {
+            KdfParameters::Aes { rounds, seed } => write_variant_dictionary(&[
+                ("$UUID", Variant::Bytes(KDF_AES.to_vec())),
+                ("R", Variant::UInt64(*rounds)),
+                ("S", Variant::Bytes(seed.clone())),
+            ]),
+            KdfParameters::Argon2 { algorithm, salt, iterations, memory_bytes, parallelism } => {
+                let uuid: [u8; 16] = if *algorithm == Algorithm::Argon2d { KDF_ARGON2D } else { KDF_ARGON2ID };
+                write_variant_dictionary(&[
+                    ("$UUID", Variant::Bytes(uuid.to_vec())),
+                    ("S", Variant::Bytes(salt.clone())),
+                    ("P", Variant::UInt32(*parallelism)),
+                    ("M", Variant::UInt64(*memory_bytes)),
+                    ("I", Variant::UInt64(*iterations)),
+                    ("V", Variant::UInt32(0x13)),
+                ])
+            }
+        }
+    }
+
+    // Turns the composite key into the transformed key that the file keys are derived from
+    pub(crate) fn transform(&self, composite_key: &[u8; 32]) -> Result<Zeroizing<[u8; 32]>, CryptoError> {
+        let mut transformed: Zeroizing<[u8; 32]> = Zeroizing::new([0u8; 32]);
+        match self {
+            KdfParameters::Aes { rounds, seed } => {
+                if *rounds > MAX_AES_KDF_ROUNDS {
+                    return Err(CryptoError::ImportFailed("more than a billion AES-KDF rounds aren't supported".into()));
+                }
+                let cipher: Aes256 = Aes256::new_from_slice(seed).map_err(|_| format_error("bad AES-KDF seed"))?;
+                let mut blocks: Zeroizing<[u8; 32]> = Zeroizing::new(*composite_key);
+                let (left, right) = blocks.split_at_mut(16);
+                for _ in 0..*rounds {
+                    cipher.encrypt_block(left.into());
+                    cipher.encrypt_block(right.into());
+                }
+                transformed.copy_from_slice(&Sha256::digest(blocks.as_ref()));
+            }
+            KdfParameters::Argon2 { algorithm, salt, iterations, memory_bytes, parallelism } => {
+                if *memory_bytes > MAX_ARGON2_MEMORY_BYTES {
+                    return Err(CryptoError::ImportFailed("Argon2 memory above 1 GiB isn't supported".into()));
+                }
+                let params: Params = Params::new(
+                    u32::try_from(memory_bytes / 1024).map_err(|_| format_error("Argon2 memory too large"))?,
+                    u32::try_from(*iterations).map_err(|_| format_error("Argon2 iterations too large"))?,
+                    *parallelism,
+                    Some(32),
+                )
+                .map_err(|_| format_error("bad Argon2 parameters"))?;
+                Argon2::new(*algorithm, Version::V0x13, params)
+                    .hash_password_into(composite_key, salt, transformed.as_mut())
+                    .map_err(|_| CryptoError::KeyDerivationFailed)?;
+            }
+        }
+        Ok(transformed)
+    }
+}
+
+// KeePass composite key for a password-only database: SHA-256 over the SHA-256 of each component
+pub(crate) fn composite_key(password: &[u8]) -> [u8; 32] {
+    let password_hash = Sha256::digest(password);
+    Sha256::digest(password_hash).into()
+}
+
+// The two keys a KDBX 4 file needs once the KDF has run: one for the payload cipher and
+// a base key for the HMACs over the header and each block
+pub(crate) struct FileKeys {
+    pub(crate) encryption_key: Zeroizing<[u8; 32]>,
+    hmac_base_key: Zeroizing<[u8; 64]>,
+}
+
+impl FileKeys {
+    pub(crate) fn derive(master_seed: &[u8], transformed_key: &[u8; 32]) -> Self {
+        let mut encryption_key: Zeroizing<[u8; 32]> = Zeroizing::new([0u8; 32]);
+        encryption_key.copy_from_slice(&Sha256::new().chain_update(master_seed).chain_update(transformed_key).finalize());
+
+        let mut hmac_base_key: Zeroizing<[u8; 64]> = Zeroizing::new([0u8; 64]);
+        hmac_base_key.copy_from_slice(
+            &Sha512::new()
+                .chain_update(master_seed)
+                .chain_update(transformed_key)
+                .chain_update([1u8])
+                .finalize(),
+        );
+        Self { encryption_key, hmac_base_key }
+    }
+
+    fn block_hmac(&self, index: u64) -> Hmac<Sha256> {
+        let key = Sha512::new().chain_update(index.to_le_bytes()).chain_update(self.hmac_base_key.as_ref()).finalize();
+        <Hmac<Sha256> as Mac>::new_from_slice(&key).expect("HMAC accepts any key length")
+    }
+
+    // The header is authenticated like a block with index u64::MAX
+    pub(crate) fn header_hmac(&self, header: &[u8]) -> [u8; 32] {
+        let mut mac: Hmac<Sha256> = self.block_hmac(u64::MAX);
+        mac.update(header);
+        mac.finalize().into_bytes().into()
+    }
+
+    pub(crate) fn data_block_hmac(&self, index: u64, data: &[u8]) -> [u8; 32] {
+        let mut mac: Hmac<Sha256> = self.block_hmac(index);
+        mac.update(&index.to_le_bytes());
+        mac.update(&(data.len() as i32).to_le_bytes());
+        mac.update(data);
+        mac.finalize().into_bytes().into()
+    }
+}
+
+// Keystream that protected values (passwords etc.) inside the XML are XORed with, in document order
+pub(crate) enum ProtectedStream {
+    Salsa20(salsa20::Salsa20),
+    ChaCha20(chacha20::ChaCha20),
+}
+
+impl ProtectedStream {
+    pub(crate) fn new(stream_id: u32, key: &[u8]) -> Result<Self, CryptoError> {
+        match stream_id {
+            STREAM_CHACHA20 => {
+                let hash = Sha512::digest(key);
+                Ok(ProtectedStream::ChaCha20(chacha20::ChaCha20::new(
+                    hash[..32].into(),
+                    hash[32..44].into(),
+                )))
+            }
+            STREAM_SALSA20 => {
+                let hash = Sha256::digest(key);
+                Ok(ProtectedStream::Salsa20(salsa20::Salsa20::new(&hash, &SALSA20_NONCE.into())))
+            }
+            _ => Err(CryptoError::ImportFailed("unsupported inner stream cipher".into())),
+        }
+    }
+
+    pub(crate) fn apply(&mut self, data: &mut [u8]) {
+        match self {
+            ProtectedStream::Salsa20(cipher) => cipher.apply_keystream(data),
+            ProtectedStream::ChaCha20(cipher) => cipher.apply_keystream(data),
+        }
+    }
+}
+
+pub(crate) fn time_from_kdbx(seconds: i64) -> Option<DateTime<Utc>> {
+    DateTime::from_timestamp(seconds.checked_sub(KDBX_EPOCH_OFFSET)?, 0)
+}
+
+pub(crate) fn time_to_kdbx(time: &DateTime<Utc>) -> i64 {
+    time.timestamp() + KDBX_EPOCH_OFFSET
+}
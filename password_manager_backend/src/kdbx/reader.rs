@@ -0,0 +1,306 @@
+use crate::error_handling::CryptoError;
+use crate::kdbx::*;
+use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, KeyIvInit, StreamCipher};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, Utc};
+use flate2::read::GzDecoder;
+use roxmltree::{Document, Node, NodeId};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::Read;
+use zeroize::{Zeroize, Zeroizing};
+
+#[derive(Debug, Default)]
+pub struct KdbxDatabase {
+    pub name: Option<String>,
+    pub entries: Vec<KdbxEntry>,
+}
+
+// What the outer header tells us about how the payload was produced
+struct OuterHeader {
+    cipher_id: Vec<u8>,
+    compressed: bool,
+    master_seed: Vec<u8>,
+    encryption_iv: Vec<u8>,
+    kdf: KdfParameters,
+}
+
+// Opens a password-protected KDBX 4 database. A wrong password shows up as a header HMAC
+// mismatch and is reported as AuthenticationFailed; anything malformed is ImportFailed.
+pub fn read_kdbx(data: &[u8], password: &[u8]) -> Result<KdbxDatabase, CryptoError> {
+    let mut reader: ByteReader = ByteReader::new(data);
+    if reader.read_u32()? != SIGNATURE_1 || reader.read_u32()? != SIGNATURE_2 {
+        return Err(format_error("bad signature"));
+    }
+    let _minor_version: u16 = reader.read_u16()?;
+    if reader.read_u16()? != MAJOR_VERSION {
+        return Err(CryptoError::ImportFailed(
+            "only KDBX 4 databases are supported, save it with KeePass 2.35 or newer first".into(),
+        ));
+    }
+
+    let header: OuterHeader = read_outer_header(&mut reader)?;
+    let header_bytes: &[u8] = &data[..reader.position()];
+    let header_hash: &[u8] = reader.take(32)?;
+    if Sha256::digest(header_bytes).as_slice() != header_hash {
+        return Err(format_error("header checksum mismatch"));
+    }
+
+    let composite: Zeroizing<[u8; 32]> = Zeroizing::new(composite_key(password));
+    let transformed: Zeroizing<[u8; 32]> = header.kdf.transform(&composite)?;
+    let keys: FileKeys = FileKeys::derive(&header.master_seed, &transformed);
+    if keys.header_hmac(header_bytes) != reader.take(32)? {
+        return Err(CryptoError::AuthenticationFailed);
+    }
+
+    let encrypted: Vec<u8> = read_blocks(&mut reader, &keys)?;
+    let mut payload: Zeroizing<Vec<u8>> = decrypt_payload(&header, &keys, encrypted)?;
+    if header.compressed {
+        let mut decompressed: Zeroizing<Vec<u8>> = Zeroizing::new(Vec::new());
+        GzDecoder::new(payload.as_slice())
+            .read_to_end(&mut decompressed)
+            .map_err(|_| format_error("payload doesn't decompress"))?;
+        payload = decompressed;
+    }
+
+    let mut inner: ByteReader = ByteReader::new(&payload);
+    let mut stream: ProtectedStream = read_inner_header(&mut inner)?;
+    let xml: &str = std::str::from_utf8(inner.remaining()).map_err(|_| format_error("XML isn't UTF-8"))?;
+    parse_xml(xml, &mut stream)
+}
+
+fn read_outer_header(reader: &mut ByteReader) -> Result<OuterHeader, CryptoError> {
+    let mut cipher_id: Option<Vec<u8>> = None;
+    let mut compressed: bool = false;
+    let mut master_seed: Option<Vec<u8>> = None;
+    let mut encryption_iv: Option<Vec<u8>> = None;
+    let mut kdf: Option<KdfParameters> = None;
+
+    loop {
+        let field_id: u8 = reader.read_u8()?;
+        let size: usize = reader.read_u32()? as usize;
+        let value: &[u8] = reader.take(size)?;
+        match field_id {
+            HEADER_END => break,
+            HEADER_CIPHER_ID => cipher_id = Some(value.to_vec()),
+            HEADER_COMPRESSION => compressed = ByteReader::new(value).read_u32()? == 1,
+            HEADER_MASTER_SEED => master_seed = Some(value.to_vec()),
+            HEADER_ENCRYPTION_IV => encryption_iv = Some(value.to_vec()),
+            HEADER_KDF_PARAMETERS => kdf = Some(KdfParameters::from_dictionary(&parse_variant_dictionary(value)?)?),
+            _ => {} // public custom data and the like don't matter for reading
+        }
+    }
+
+    let missing = || format_error("incomplete header");
+    Ok(OuterHeader {
+        cipher_id: cipher_id.ok_or_else(missing)?,
+        compressed,
+        master_seed: master_seed.filter(|seed| seed.len() == 32).ok_or_else(missing)?,
+        encryption_iv: encryption_iv.ok_or_else(missing)?,
+        kdf: kdf.ok_or_else(missing)?,
+    })
+}
+
+// HMAC-protected block stream: [hmac: 32][size: i32][data], ending with an empty block
+fn read_blocks(reader: &mut ByteReader, keys: &FileKeys) -> Result<Vec<u8>, CryptoError> {
+    let mut encrypted: Vec<u8> = Vec::new();
+    for index in 0u64.. {
+        let hmac: &[u8] = reader.take(32)?;
+        let size: usize = reader.read_i32()?.try_into().map_err(|_| format_error("negative block size"))?;
+        let block: &[u8] = reader.take(size)?;
+        if keys.data_block_hmac(index, block) != hmac {
+            return Err(format_error("block HMAC mismatch"));
+        }
+        if size == 0 {
+            break;
+        }
+        encrypted.extend_from_slice(block);
+    }
+    Ok(encrypted)
+}
+
+fn decrypt_payload(header: &OuterHeader, keys: &FileKeys, mut encrypted: Vec<u8>) -> Result<Zeroizing<Vec<u8>>, CryptoError> {
+    if header.cipher_id == CIPHER_AES256 {
+        cbc::Decryptor::<aes::Aes256>::new_from_slices(keys.encryption_key.as_ref(), &header.encryption_iv)
+            .map_err(|_| format_error("bad AES IV"))?
+            .decrypt_padded_vec_mut::<Pkcs7>(&encrypted)
+            .map(Zeroizing::new)
+            .map_err(|_| CryptoError::DecryptionFailed)
+    } else if header.cipher_id == CIPHER_CHACHA20 {
+        chacha20::ChaCha20::new_from_slices(keys.encryption_key.as_ref(), &header.encryption_iv)
+            .map_err(|_| format_error("bad ChaCha20 IV"))?
+            .apply_keystream(&mut encrypted);
+        Ok(Zeroizing::new(encrypted))
+    } else {
+        Err(CryptoError::ImportFailed("unsupported cipher (only AES-256 and ChaCha20 are)".into()))
+    }
+}
+
+// Inner header carries the protected value stream; attachments (field 3) are skipped
+fn read_inner_header(reader: &mut ByteReader) -> Result<ProtectedStream, CryptoError> {
+    let mut stream_id: Option<u32> = None;
+    let mut stream_key: Option<Zeroizing<Vec<u8>>> = None;
+    loop {
+        let field_id: u8 = reader.read_u8()?;
+        let size: usize = reader.read_u32()? as usize;
+        let value: &[u8] = reader.take(size)?;
+        match field_id {
+            INNER_HEADER_END => break,
+            INNER_STREAM_ID => stream_id = Some(ByteReader::new(value).read_u32()?),
+            INNER_STREAM_KEY => stream_key = Some(Zeroizing::new(value.to_vec())),
+            _ => {}
+        }
+    }
+    match (stream_id, stream_key) {
+        (Some(stream_id), Some(stream_key)) => ProtectedStream::new(stream_id, &stream_key),
+        _ => Err(format_error("incomplete inner header")),
+    }
+}
+
+fn parse_xml(xml: &str, stream: &mut ProtectedStream) -> Result<KdbxDatabase, CryptoError> {
+    let document: Document = Document::parse(xml).map_err(|_| format_error("malformed XML"))?;
+    let root: Node = document.root_element();
+
+    // Protected values share one keystream, so they have to be decrypted in document order
+    let mut protected: HashMap<NodeId, String> = HashMap::new();
+    for node in root.descendants().filter(|n| n.has_tag_name("Value") && n.attribute("Protected") == Some("True")) {
+        let mut bytes: Vec<u8> = STANDARD
+            .decode(node.text().unwrap_or_default())
+            .map_err(|_| format_error("protected value isn't base64"))?;
+        stream.apply(&mut bytes);
+        let value: Result<String, _> = String::from_utf8(bytes);
+        match value {
+            Ok(value) => {
+                protected.insert(node.id(), value);
+            }
+            Err(error) => {
+                error.into_bytes().zeroize();
+                return Err(format_error("protected value isn't UTF-8"));
+            }
+        }
+    }
+
+    let meta: Option<Node> = child(root, "Meta");
+    let recycle_bin: Option<&str> = meta
+        .and_then(|meta| child_text(meta, "RecycleBinUUID"))
+        .filter(|uuid| *uuid != "AAAAAAAAAAAAAAAAAAAAAA==");
+    let mut database: KdbxDatabase = KdbxDatabase {
+        name: meta.and_then(|meta| child_text(meta, "DatabaseName")).map(str::to_string),
+        entries: Vec::new(),
+    };
+
+    let top_group: Node = child(root, "Root")
+        .and_then(|r| child(r, "Group"))
+        .ok_or_else(|| format_error("no root group"))?;
+    let mut walker: GroupWalker = GroupWalker { protected: &mut protected, recycle_bin, entries: &mut database.entries };
+    walker.walk(top_group, &mut Vec::new(), false);
+
+    for value in protected.values_mut() {
+        value.zeroize();
+    }
+    Ok(database)
+}
+
+struct GroupWalker<'a, 'input> {
+    protected: &'a mut HashMap<NodeId, String>,
+    recycle_bin: Option<&'input str>,
+    entries: &'a mut Vec<KdbxEntry>,
+}
+
+impl GroupWalker<'_, '_> {
+    // `path` holds the names of the groups between the root group and `group`
+    fn walk(&mut self, group: Node, path: &mut Vec<String>, in_recycle_bin: bool) {
+        for node in group.children().filter(Node::is_element) {
+            match node.tag_name().name() {
+                "Entry" => {
+                    let entry: KdbxEntry = self.read_entry(node, path, in_recycle_bin);
+                    self.entries.push(entry);
+                }
+                "Group" => {
+                    let recycled: bool = in_recycle_bin || self.recycle_bin.is_some() && child_text(node, "UUID") == self.recycle_bin;
+                    path.push(child_text(node, "Name").unwrap_or_default().to_string());
+                    self.walk(node, path, recycled);
+                    path.pop();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // History lives in a child element of the entry and is ignored here
+    fn read_entry(&mut self, node: Node, path: &[String], in_recycle_bin: bool) -> KdbxEntry {
+        let mut entry: KdbxEntry = KdbxEntry::default();
+        entry.group_path = path.to_vec();
+        entry.in_recycle_bin = in_recycle_bin;
+        entry.uuid = child_text(node, "UUID")
+            .and_then(|uuid| STANDARD.decode(uuid).ok())
+            .and_then(|uuid| uuid.try_into().ok());
+
+        for field in node.children().filter(|n| n.has_tag_name("String")) {
+            let key: &str = child_text(field, "Key").unwrap_or_default();
+            let value_node: Option<Node> = child(field, "Value");
+            let protected: bool = value_node.is_some_and(|v| v.attribute("Protected") == Some("True"));
+            let value: String = match value_node {
+                Some(value_node) if protected => self.protected.remove(&value_node.id()).unwrap_or_default(),
+                Some(value_node) => value_node.text().unwrap_or_default().to_string(),
+                None => String::new(),
+            };
+            let value: Option<String> = Some(value).filter(|value| !value.is_empty());
+
+            match key {
+                "Title" => entry.title = value,
+                "UserName" => entry.username = value,
+                "Password" => entry.password = value,
+                "URL" => entry.url = value,
+                "Notes" => entry.notes = value,
+                _ => entry.custom_fields.push(KdbxField {
+                    key: key.to_string(),
+                    value: value.unwrap_or_default(),
+                    protected,
+                }),
+            }
+        }
+
+        entry.tags = child_text(node, "Tags")
+            .map(|tags| {
+                tags.split([';', ','])
+                    .map(str::trim)
+                    .filter(|tag| !tag.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+        entry.attachments = node
+            .children()
+            .filter(|n| n.has_tag_name("Binary"))
+            .filter_map(|binary| child_text(binary, "Key").map(str::to_string))
+            .collect();
+        let times: Option<Node> = child(node, "Times");
+        entry.creation_time = times
+            .and_then(|times| child_text(times, "CreationTime"))
+            .and_then(parse_time);
+        // ExpiryTime is always written; it only applies when Expires is set
+        entry.expiry_time = times
+            .filter(|times| child_text(*times, "Expires").is_some_and(|expires| expires.eq_ignore_ascii_case("true")))
+            .and_then(|times| child_text(times, "ExpiryTime"))
+            .and_then(parse_time);
+        entry
+    }
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|n| n.has_tag_name(name))
+}
+
+fn child_text<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    child(node, name).and_then(|n| n.text())
+}
+
+// KDBX 4 writes base64 seconds since year 1; older writers used ISO 8601 text
+fn parse_time(text: &str) -> Option<DateTime<Utc>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(text) {
+        return Some(time.with_timezone(&Utc));
+    }
+    let bytes: [u8; 8] = STANDARD.decode(text).ok()?.try_into().ok()?;
+    time_from_kdbx(i64::from_le_bytes(bytes))
+}
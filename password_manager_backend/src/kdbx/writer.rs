@@ -0,0 +1,218 @@
+use crate::error_handling::CryptoError;
+use crate::kdbx::reader::KdbxDatabase;
+use crate::kdbx::*;
+use aes::cipher::{KeyIvInit, StreamCipher};
+use argon2::Algorithm;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, Utc};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use rand::{rngs::OsRng, RngCore};
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use zeroize::{Zeroize, Zeroizing};
+
+const BLOCK_SIZE: usize = 1024 * 1024;
+const GENERATOR: &str = "PawPass";
+
+// Writes a password-protected KDBX 4 database: ChaCha20 outer cipher, Argon2id KDF, gzip
+// compression and a ChaCha20 inner stream, which is what current KeePass and KeePassXC default to
+pub fn write_kdbx(database: &KdbxDatabase, password: &[u8], argon2: Argon2Settings) -> Result<Vec<u8>, CryptoError> {
+    let master_seed: [u8; 32] = random_bytes();
+    let encryption_iv: [u8; 12] = random_bytes();
+    let kdf: KdfParameters = KdfParameters::Argon2 {
+        algorithm: Algorithm::Argon2id,
+        salt: random_bytes::<32>().to_vec(),
+        iterations: argon2.iterations as u64,
+        memory_bytes: argon2.memory_kib as u64 * 1024,
+        parallelism: argon2.parallelism,
+    };
+
+    let mut header: Vec<u8> = Vec::new();
+    header.extend_from_slice(&SIGNATURE_1.to_le_bytes());
+    header.extend_from_slice(&SIGNATURE_2.to_le_bytes());
+    header.extend_from_slice(&0u16.to_le_bytes());
+    header.extend_from_slice(&MAJOR_VERSION.to_le_bytes());
+    write_field(&mut header, HEADER_CIPHER_ID, &CIPHER_CHACHA20);
+    write_field(&mut header, HEADER_COMPRESSION, &1u32.to_le_bytes());
+    write_field(&mut header, HEADER_MASTER_SEED, &master_seed);
+    write_field(&mut header, HEADER_ENCRYPTION_IV, &encryption_iv);
+    write_field(&mut header, HEADER_KDF_PARAMETERS, &kdf.to_dictionary());
+    write_field(&mut header, HEADER_END, b"\r\n\r\n");
+
+    let composite: Zeroizing<[u8; 32]> = Zeroizing::new(composite_key(password));
+    let transformed: Zeroizing<[u8; 32]> = kdf.transform(&composite)?;
+    let keys: FileKeys = FileKeys::derive(&master_seed, &transformed);
+
+    let stream_key: Zeroizing<[u8; 64]> = Zeroizing::new(random_bytes());
+    let mut stream: ProtectedStream = ProtectedStream::new(STREAM_CHACHA20, stream_key.as_ref())?;
+    let mut payload: Zeroizing<Vec<u8>> = Zeroizing::new(Vec::new());
+    write_field(&mut payload, INNER_STREAM_ID, &STREAM_CHACHA20.to_le_bytes());
+    write_field(&mut payload, INNER_STREAM_KEY, stream_key.as_ref());
+    write_field(&mut payload, INNER_HEADER_END, &[]);
+    let xml: Zeroizing<String> = write_xml(database, &mut stream);
+    payload.extend_from_slice(xml.as_bytes());
+
+    let mut encoder: GzEncoder<Vec<u8>> = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&payload).map_err(CryptoError::IO)?;
+    let mut encrypted: Vec<u8> = encoder.finish().map_err(CryptoError::IO)?;
+    chacha20::ChaCha20::new(keys.encryption_key.as_ref().into(), &encryption_iv.into()).apply_keystream(&mut encrypted);
+
+    let mut out: Vec<u8> = header.clone();
+    out.extend_from_slice(&Sha256::digest(&header));
+    out.extend_from_slice(&keys.header_hmac(&header));
+    let chunks = encrypted.chunks(BLOCK_SIZE).chain(std::iter::once(&[][..]));
+    for (index, block) in chunks.enumerate() {
+        out.extend_from_slice(&keys.data_block_hmac(index as u64, block));
+        out.extend_from_slice(&(block.len() as i32).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    Ok(out)
+}
+
+fn write_field(out: &mut Vec<u8>, field_id: u8, value: &[u8]) {
+    out.push(field_id);
+    out.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    out.extend_from_slice(value);
+}
+
+fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut bytes: [u8; N] = [0u8; N];
+    OsRng.fill_bytes(&mut bytes);
+    bytes
+}
+
+// Groups in the order their first entry appears, so the output follows the vault's order
+struct GroupTree<'a> {
+    name: &'a str,
+    entries: Vec<&'a KdbxEntry>,
+    groups: Vec<GroupTree<'a>>,
+}
+
+impl<'a> GroupTree<'a> {
+    fn new(name: &'a str) -> Self {
+        Self { name, entries: Vec::new(), groups: Vec::new() }
+    }
+
+    fn insert(&mut self, path: &'a [String], entry: &'a KdbxEntry) {
+        match path.split_first() {
+            None => self.entries.push(entry),
+            Some((name, rest)) => {
+                let index: usize = match self.groups.iter().position(|group| group.name == name) {
+                    Some(index) => index,
+                    None => {
+                        self.groups.push(GroupTree::new(name));
+                        self.groups.len() - 1
+                    }
+                };
+                self.groups[index].insert(rest, entry);
+            }
+        }
+    }
+}
+
+// Protected values have to go through the stream in the same order a reader will meet them,
+// which is simply the order they're written in
+fn write_xml(database: &KdbxDatabase, stream: &mut ProtectedStream) -> Zeroizing<String> {
+    let name: &str = database.name.as_deref().unwrap_or(GENERATOR);
+    let mut root: GroupTree = GroupTree::new(name);
+    for entry in database.entries.iter().filter(|entry| !entry.in_recycle_bin) {
+        root.insert(&entry.group_path, entry);
+    }
+
+    let now: DateTime<Utc> = Utc::now();
+    let mut xml: Zeroizing<String> = Zeroizing::new(String::new());
+    xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\" standalone=\"yes\"?>\n<KeePassFile>\n");
+    xml.push_str(&format!(
+        "\t<Meta>\n\t\t<Generator>{}</Generator>\n\t\t<DatabaseName>{}</DatabaseName>\n\t\t<RecycleBinEnabled>False</RecycleBinEnabled>\n\t</Meta>\n",
+        GENERATOR,
+        escape(name)
+    ));
+    xml.push_str("\t<Root>\n");
+    write_group(&mut xml, &root, stream, &now, 2);
+    xml.push_str("\t</Root>\n</KeePassFile>\n");
+    xml
+}
+
+fn write_group(xml: &mut String, group: &GroupTree, stream: &mut ProtectedStream, now: &DateTime<Utc>, depth: usize) {
+    let indent: String = "\t".repeat(depth);
+    xml.push_str(&format!("{}<Group>\n", indent));
+    xml.push_str(&format!("{}\t<UUID>{}</UUID>\n", indent, STANDARD.encode(random_bytes::<16>())));
+    xml.push_str(&format!("{}\t<Name>{}</Name>\n", indent, escape(group.name)));
+    for entry in &group.entries {
+        write_entry(xml, entry, stream, now, depth + 1);
+    }
+    for child in &group.groups {
+        write_group(xml, child, stream, now, depth + 1);
+    }
+    xml.push_str(&format!("{}</Group>\n", indent));
+}
+
+fn write_entry(xml: &mut String, entry: &KdbxEntry, stream: &mut ProtectedStream, now: &DateTime<Utc>, depth: usize) {
+    let indent: String = "\t".repeat(depth);
+    let time: String = STANDARD.encode(time_to_kdbx(&entry.creation_time.unwrap_or(*now)).to_le_bytes());
+    let modified: String = STANDARD.encode(time_to_kdbx(now).to_le_bytes());
+
+    xml.push_str(&format!("{}<Entry>\n", indent));
+    xml.push_str(&format!("{}\t<UUID>{}</UUID>\n", indent, STANDARD.encode(entry.uuid.unwrap_or_else(random_bytes))));
+    if !entry.tags.is_empty() {
+        xml.push_str(&format!("{}\t<Tags>{}</Tags>\n", indent, escape(&entry.tags.join(";"))));
+    }
+    let expiry: String = match &entry.expiry_time {
+        Some(expiry_time) => format!(
+            "{0}\t\t<ExpiryTime>{1}</ExpiryTime>\n{0}\t\t<Expires>True</Expires>\n",
+            indent,
+            STANDARD.encode(time_to_kdbx(expiry_time).to_le_bytes())
+        ),
+        None => String::new(),
+    };
+    xml.push_str(&format!(
+        "{0}\t<Times>\n{0}\t\t<CreationTime>{1}</CreationTime>\n{0}\t\t<LastModificationTime>{2}</LastModificationTime>\n{0}\t\t<LastAccessTime>{2}</LastAccessTime>\n{3}{0}\t</Times>\n",
+        indent, time, modified, expiry
+    ));
+
+    let standard_fields: [(&str, &Option<String>, bool); 5] = [
+        ("Title", &entry.title, false),
+        ("UserName", &entry.username, false),
+        ("Password", &entry.password, true),
+        ("URL", &entry.url, false),
+        ("Notes", &entry.notes, false),
+    ];
+    for (key, value, protected) in standard_fields {
+        write_string(xml, &indent, key, value.as_deref().unwrap_or_default(), protected, stream);
+    }
+    for field in &entry.custom_fields {
+        write_string(xml, &indent, &field.key, &field.value, field.protected, stream);
+    }
+    xml.push_str(&format!("{}</Entry>\n", indent));
+}
+
+fn write_string(xml: &mut String, indent: &str, key: &str, value: &str, protected: bool, stream: &mut ProtectedStream) {
+    let value: String = if protected {
+        let mut bytes: Vec<u8> = value.as_bytes().to_vec();
+        stream.apply(&mut bytes);
+        let encoded: String = STANDARD.encode(&bytes);
+        bytes.zeroize();
+        format!("<Value Protected=\"True\">{}</Value>", encoded)
+    } else {
+        format!("<Value>{}</Value>", escape(value))
+    };
+    xml.push_str(&format!("{}\t<String>\n{}\t\t<Key>{}</Key>\n{}\t\t{}\n{}\t</String>\n", indent, indent, escape(key), indent, value, indent));
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped: String = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // control characters other than whitespace aren't allowed in XML 1.0 at all
+            c if c < ' ' && !matches!(c, '\t' | '\n' | '\r') => {}
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,59 @@
+use crate::error_handling::CryptoError;
+use rand::{rngs::OsRng, RngCore};
+use ring::digest::{digest, Context, SHA256};
+use std::path::Path;
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use zeroize::{Zeroize, Zeroizing};
+
+const KEY_FILE_BYTES: usize = 32;
+pub const KEY_FILE_HASH_LEN: usize = 32;
+
+// Writes a new key file with 256 random bits, hex encoded so it survives being copied around as text
+pub async fn generate_key_file(file_path: &Path) -> Result<(), CryptoError> {
+    let mut bytes: [u8; KEY_FILE_BYTES] = [0u8; KEY_FILE_BYTES];
+    OsRng.fill_bytes(&mut bytes);
+    let mut encoded: String = hex::encode(bytes);
+    bytes.zeroize();
+
+    let mut file: File = File::create(file_path).await.map_err(CryptoError::IO)?;
+    let result = file.write_all(encoded.as_bytes()).await;
+    encoded.zeroize();
+    result.map_err(CryptoError::IO)?;
+    Ok(())
+}
+
+// Any file can serve as a key file; only the SHA-256 of its exact contents is ever used
+pub async fn hash_key_file(file_path: &Path) -> Result<[u8; KEY_FILE_HASH_LEN], CryptoError> {
+    let mut file: File = File::open(file_path).await.map_err(CryptoError::IO)?;
+    let mut context: Context = Context::new(&SHA256);
+    let mut buffer: Vec<u8> = vec![0u8; 8192];
+    let mut total: usize = 0;
+
+    loop {
+        let read: usize = file.read(&mut buffer).await.map_err(CryptoError::IO)?;
+        if read == 0 {
+            break;
+        }
+        context.update(&buffer[..read]);
+        total += read;
+    }
+    buffer.zeroize();
+
+    if total == 0 {
+        return Err(CryptoError::InvalidKeyFile);
+    }
+    let mut hash: [u8; KEY_FILE_HASH_LEN] = [0u8; KEY_FILE_HASH_LEN];
+    hash.copy_from_slice(context.finish().as_ref());
+    Ok(hash)
+}
+
+// Composite secret fed into MekData::derive_mek_key when a key file is required:
+// SHA-256(password) || SHA-256(key file), so neither half is enough on its own
+pub fn combine_with_password(password: &[u8], key_file_hash: &[u8]) -> Zeroizing<Vec<u8>> {
+    let mut composite: Zeroizing<Vec<u8>> =
+        Zeroizing::new(Vec::with_capacity(KEY_FILE_HASH_LEN + key_file_hash.len()));
+    composite.extend_from_slice(digest(&SHA256, password).as_ref());
+    composite.extend_from_slice(key_file_hash);
+    composite
+}
@@ -0,0 +1,76 @@
+use crate::error_handling::CryptoError;
+use crate::key_file;
+use crate::mek::MekData;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use zeroize::Zeroizing;
+
+pub const MASTER_PASSWORD_LABEL: &str = "Master password";
+pub const RECOVERY_KEY_LABEL: &str = "Recovery key";
+
+// What kind of secret a slot is unlocked with. The master password slot is special: login
+// verifies MasterPasswordData first, so a vault always keeps at least one of these.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum KeySlotKind {
+    MasterPassword,
+    RecoveryKey,
+    SecretShares, // unlocked by a secret rebuilt from K-of-N Shamir shares
+}
+
+// Every slot wraps the same MEK under a different secret, each with its own salt and KDF params
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct KeySlot {
+    pub id: String,
+    pub label: String,
+    pub kind: KeySlotKind,
+    pub(crate) mek_data: MekData,
+    #[serde(default)]
+    pub requires_key_file: bool, // MEK is wrapped under password + key file rather than the password alone
+}
+
+impl KeySlot {
+    pub fn new(kind: KeySlotKind, label: String, mek_data: MekData) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            label,
+            kind,
+            mek_data,
+            requires_key_file: false,
+        }
+    }
+
+    pub fn unlock(&self, secret: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        self.mek_data.decrypt_mek(secret)
+    }
+
+    // The secret this slot's wrapping expects: the password alone, or password + key file hash
+    pub fn composite_secret(&self, password: &[u8], key_file_hash: Option<&[u8]>) -> Result<Zeroizing<Vec<u8>>, CryptoError> {
+        match (self.requires_key_file, key_file_hash) {
+            (true, Some(key_file_hash)) => Ok(key_file::combine_with_password(password, key_file_hash)),
+            (true, None) => Err(CryptoError::KeyFileRequired),
+            (false, _) => Ok(Zeroizing::new(password.to_vec())),
+        }
+    }
+
+    pub fn info(&self) -> KeySlotInfo {
+        KeySlotInfo {
+            id: self.id.clone(),
+            label: self.label.clone(),
+            kind: self.kind,
+            kdf_iterations: self.mek_data.kdf_iterations,
+            requires_key_file: self.requires_key_file,
+        }
+    }
+}
+
+// What we hand to the frontend when listing slots; never includes the wrapped MEK
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct KeySlotInfo {
+    pub id: String,
+    pub label: String,
+    pub kind: KeySlotKind,
+    pub kdf_iterations: u32,
+    pub requires_key_file: bool,
+}
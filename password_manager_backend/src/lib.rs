@@ -1,13 +1,67 @@
+pub mod attachment;
 pub mod config;
 pub mod crypto;
+pub mod custom_field;
+pub mod entry_date;
+pub mod entry_kind;
 pub mod error_handling;
+pub mod expiry;
+pub mod export;
+pub mod folders;
+pub mod hidden_vault;
+pub mod import;
+pub mod kdbx;
+pub mod key_file;
+pub mod key_slot;
 pub mod master_password;
 pub mod mek;
+pub mod migration;
 pub mod password_entry;
+pub mod password_history;
+pub mod recovery;
+pub mod shamir;
 pub mod storage;
+pub mod trash;
+pub mod url_match;
+pub mod vault_merge;
 
 #[cfg(test)]
 mod tests {
+    use crate::password_entry::{DecryptedPasswordEntry, PasswordEntry};
+    use chrono::{DateTime, Utc};
+
+    // Fixtures shared by the test modules below
+
+    fn date(text: &str) -> DateTime<Utc> {
+        crate::entry_date::parse_entry_date(text).unwrap()
+    }
+
+    // A login created at a fixed time; tests set whatever else they need on the result
+    fn login(title: &str, username: &str, password: Option<&str>, url: Option<&str>) -> DecryptedPasswordEntry {
+        let mut entry = DecryptedPasswordEntry::new(
+            title.to_string(),
+            Some(username.to_string()),
+            password.map(str::to_string),
+            url.map(str::to_string),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+        entry.created = date("2024-01-01 00:00:00 UTC");
+        entry
+    }
+
+    // A stored entry with nothing but its title, which doubles as its id
+    fn titled(title: &str) -> PasswordEntry {
+        PasswordEntry {
+            id: title.to_string(),
+            title: title.to_string(),
+            created: date("2024-01-01 12:00:00 UTC"),
+            ..PasswordEntry::default()
+        }
+    }
+
     mod storage_tests {
         use crate::config::UserSettings;
         use crate::master_password::MasterPasswordData;
@@ -29,11 +83,13 @@ mod tests {
                 master_password_data: Some(
                     MasterPasswordData::set_new_master_password(b"bub").unwrap(),
                 ),
-                mek_data: None,
+                key_slots: vec![],
                 password_entries: vec![],
                 user_settings: UserSettings::default(),
                 master_password_data_hash: None,
-                mek_data_hash: None,
+                key_slots_hash: None,
+                hidden_vault: None,
+                hidden_vault_hash: None,
                 password_entries_hash: None,
             };
 
@@ -63,11 +119,13 @@ mod tests {
             .unwrap();
             let app_data = ApplicationData {
                 master_password_data: None,
-                mek_data: None,
+                key_slots: vec![],
                 password_entries: vec![password_entry.clone()],
                 user_settings: UserSettings::default(),
                 master_password_data_hash: None,
-                mek_data_hash: None,
+                key_slots_hash: None,
+                hidden_vault: None,
+                hidden_vault_hash: None,
                 password_entries_hash: None,
             };
 
@@ -91,11 +149,13 @@ mod tests {
             let password_entry = PasswordEntry::default();
             let mut app_data = ApplicationData {
                 master_password_data: None,
-                mek_data: None,
+                key_slots: vec![],
                 password_entries: vec![password_entry.clone()],
                 user_settings: UserSettings::default(),
                 master_password_data_hash: None,
-                mek_data_hash: None,
+                key_slots_hash: None,
+                hidden_vault: None,
+                hidden_vault_hash: None,
                 password_entries_hash: None,
             };
 
@@ -111,11 +171,13 @@ mod tests {
                 master_password_data: Some(
                     MasterPasswordData::set_new_master_password(b"initial").unwrap(),
                 ),
-                mek_data: None,
+                key_slots: vec![],
                 password_entries: vec![],
                 user_settings: UserSettings::default(),
                 master_password_data_hash: None,
-                mek_data_hash: None,
+                key_slots_hash: None,
+                hidden_vault: None,
+                hidden_vault_hash: None,
                 password_entries_hash: None,
             };
 
@@ -158,11 +220,13 @@ mod tests {
             let password_entry = PasswordEntry::default();
             let app_data = ApplicationData {
                 master_password_data: Some(MasterPasswordData::default()),
-                mek_data: None,
+                key_slots: vec![],
                 password_entries: vec![password_entry.clone()],
                 user_settings: UserSettings::default(),
                 master_password_data_hash: None,
-                mek_data_hash: None,
+                key_slots_hash: None,
+                hidden_vault: None,
+                hidden_vault_hash: None,
                 password_entries_hash: None,
             };
 
@@ -275,6 +339,7 @@ mod tests {
 
     mod password_entry_tests {
         use crate::config::UserSettings;
+        use crate::error_handling::CryptoError;
         use crate::password_entry::{DecryptedPasswordEntry, PasswordEntry};
 
         #[test]
@@ -284,7 +349,7 @@ mod tests {
             let password = Some("Test Password".as_bytes());
             let url = Some("https://test.com".to_string());
             let notes = Some("Test Notes".to_string());
-            let category = Some("Test Category".to_string());
+            let folder = Some("Test Folder".to_string());
             let favorite = Some(true);
             let master_pass = Some("Master Password".as_bytes());
 
@@ -294,20 +359,60 @@ mod tests {
                 password,
                 url.clone(),
                 notes.clone(),
-                category.clone(),
+                folder.clone(),
                 favorite,
                 master_pass,
             )
             .unwrap();
 
             assert_eq!(password_entry.title, title); 
-            assert_eq!(password_entry.username, username);
-            assert_eq!(password_entry.url, url);
-            assert_eq!(password_entry.notes, notes);
-            assert_eq!(password_entry.category, category);
+            assert!(password_entry.username.as_ref().unwrap().is_encrypted());
+            assert_eq!(password_entry.primary_url(), url.as_deref());
+            assert!(password_entry.notes.as_ref().unwrap().is_encrypted());
+            let decrypted_entry = password_entry.to_decrypted("Master Password".as_bytes()).unwrap();
+            assert_eq!(decrypted_entry.username, username);
+            assert_eq!(decrypted_entry.notes, notes);
+            assert_eq!(password_entry.folder, folder);
+            assert!(password_entry.tags.is_empty());
             assert_eq!(password_entry.favorite, favorite.unwrap());
         }
 
+        #[test]
+        fn test_password_entry_new_needs_a_key_for_secrets() {
+            let entry = PasswordEntry::new("Note".to_string(), Some("me".to_string()), None, None, None, None, None, None);
+            assert!(matches!(entry, Err(CryptoError::MekDataNotPresent)));
+            let entry = PasswordEntry::new("Note".to_string(), None, None, None, Some("n".to_string()), None, None, None);
+            assert!(matches!(entry, Err(CryptoError::MekDataNotPresent)));
+            assert!(PasswordEntry::new("Title only".to_string(), None, None, None, None, None, None, None).is_ok());
+        }
+
+        #[test]
+        fn test_sealed_text_is_bound_to_its_entry_and_field() {
+            let mek = b"0123456789abcdef0123456789abcdef";
+            let new = |username: &str| {
+                PasswordEntry::new(
+                    "Bank".to_string(),
+                    Some(username.to_string()),
+                    None,
+                    None,
+                    Some("notes".to_string()),
+                    None,
+                    None,
+                    Some(mek),
+                )
+                .unwrap()
+            };
+            let (first, second) = (new("jane"), new("joe"));
+
+            let mut swapped = first.clone();
+            swapped.username = second.username.clone();
+            assert!(matches!(swapped.to_decrypted(mek), Err(CryptoError::DecryptionFailed)));
+            let mut moved = first.clone();
+            moved.notes = first.username.clone();
+            assert!(matches!(moved.to_decrypted(mek), Err(CryptoError::DecryptionFailed)));
+            assert_eq!(first.to_decrypted(mek).unwrap().username.as_deref(), Some("jane"));
+        }
+
         #[test]
         fn test_user_settings_generate_password() {
             let user_settings = UserSettings::default();
@@ -635,4 +740,2572 @@ mod tests {
     }
 
 
-}
\ No newline at end of file
+
+    mod recovery_tests {
+        use crate::error_handling::CryptoError;
+        use crate::recovery::{generate_recovery_key, normalize_recovery_key};
+        use crate::storage::ApplicationData;
+
+        #[test]
+        fn test_generate_recovery_key_format() {
+            let recovery_key = generate_recovery_key();
+
+            assert_eq!(recovery_key.len(), 39); // 32 characters + 7 dashes
+            assert_eq!(recovery_key.split('-').count(), 8);
+            assert_ne!(recovery_key, generate_recovery_key());
+        }
+
+        #[test]
+        fn test_normalize_recovery_key() {
+            let recovery_key = generate_recovery_key();
+            let normalized = normalize_recovery_key(&recovery_key).unwrap();
+
+            // lowercase, spaces instead of dashes and O/I typed for 0/1 still match
+            let hand_typed = recovery_key.to_lowercase().replace('-', " ").replace('0', "o").replace('1', "i");
+            assert_eq!(normalize_recovery_key(&hand_typed).unwrap(), normalized);
+
+            assert!(normalize_recovery_key("too-short").is_err());
+            assert!(normalize_recovery_key(&format!("{}U", &normalized[1..])).is_err());
+        }
+
+        #[test]
+        fn test_recover_with_key_keeps_mek() {
+            let mut app_data = ApplicationData::new();
+            app_data.add_master_password_data(b"forgotten_password").unwrap();
+            let original_mek = app_data.decrypt_mek_data(b"forgotten_password").unwrap();
+
+            let recovery_key = app_data.add_recovery_key(b"forgotten_password").unwrap();
+            assert!(app_data.has_recovery_key());
+            assert!(app_data.verify_hashes());
+
+            app_data.recover_with_key(&recovery_key, b"new_password").unwrap();
+
+            assert!(!app_data.verify_master_password(b"forgotten_password"));
+            assert_eq!(app_data.decrypt_mek_data(b"new_password").unwrap(), original_mek);
+            assert!(app_data.verify_hashes());
+
+            // the recovery key keeps working after it has been used once
+            app_data.recover_with_key(&recovery_key, b"newer_password").unwrap();
+            assert_eq!(app_data.decrypt_mek_data(b"newer_password").unwrap(), original_mek);
+        }
+
+        #[test]
+        fn test_recover_with_wrong_key() {
+            let mut app_data = ApplicationData::new();
+            app_data.add_master_password_data(b"password").unwrap();
+            app_data.add_recovery_key(b"password").unwrap();
+
+            let wrong_key = generate_recovery_key();
+            assert!(matches!(
+                app_data.recover_with_key(&wrong_key, b"new_password"),
+                Err(CryptoError::AuthenticationFailed)
+            ));
+            assert!(app_data.verify_master_password(b"password"));
+        }
+
+        #[test]
+        fn test_recovery_key_requires_master_password() {
+            let mut app_data = ApplicationData::new();
+            app_data.add_master_password_data(b"password").unwrap();
+
+            assert!(app_data.add_recovery_key(b"wrong_password").is_err());
+            assert!(matches!(
+                app_data.recover_with_key(&generate_recovery_key(), b"new_password"),
+                Err(CryptoError::RecoveryKeyNotFound)
+            ));
+
+            app_data.add_recovery_key(b"password").unwrap();
+            assert!(app_data.remove_recovery_key(b"wrong_password").is_err());
+            assert!(app_data.remove_recovery_key(b"password").is_ok());
+            assert!(!app_data.has_recovery_key());
+            assert!(app_data.verify_hashes());
+        }
+    }
+
+    mod key_slot_tests {
+        use crate::error_handling::CryptoError;
+        use crate::key_slot::KeySlotKind;
+        use crate::mek::{MekData, MIN_KDF_ITERATIONS};
+        use crate::storage::ApplicationData;
+
+        #[test]
+        fn test_new_vault_has_master_password_slot() {
+            let mut app_data = ApplicationData::new();
+            app_data.add_master_password_data(b"password").unwrap();
+
+            let slots = app_data.list_key_slots();
+            assert_eq!(slots.len(), 1);
+            assert_eq!(slots[0].kind, KeySlotKind::MasterPassword);
+            assert!(app_data.verify_hashes());
+        }
+
+        #[test]
+        fn test_add_key_slot_wraps_same_mek() {
+            let mut app_data = ApplicationData::new();
+            app_data.add_master_password_data(b"password").unwrap();
+            let mek = app_data.decrypt_mek_data(b"password").unwrap();
+
+            let slot_id = app_data
+                .add_key_slot(b"password", KeySlotKind::RecoveryKey, "Printed backup".to_string(), b"backup secret", Some(120_000))
+                .unwrap();
+
+            let slots = app_data.list_key_slots();
+            let slot = slots.iter().find(|s| s.id == slot_id).unwrap();
+            assert_eq!(slot.label, "Printed backup");
+            assert_eq!(slot.kdf_iterations, 120_000);
+            assert_eq!(
+                app_data.decrypt_mek_with_slot_kind(KeySlotKind::RecoveryKey, b"backup secret").unwrap(),
+                mek
+            );
+            assert!(app_data.decrypt_mek_with_slot_kind(KeySlotKind::RecoveryKey, b"wrong secret").is_err());
+            assert!(app_data.verify_hashes());
+        }
+
+        #[test]
+        fn test_add_key_slot_requires_master_password() {
+            let mut app_data = ApplicationData::new();
+            app_data.add_master_password_data(b"password").unwrap();
+
+            assert!(app_data
+                .add_key_slot(b"wrong_password", KeySlotKind::RecoveryKey, "backup".to_string(), b"secret", None)
+                .is_err());
+            assert!(matches!(
+                app_data.add_key_slot(b"password", KeySlotKind::MasterPassword, "second".to_string(), b"secret", None),
+                Err(CryptoError::InvalidInput)
+            ));
+            assert_eq!(app_data.list_key_slots().len(), 1);
+        }
+
+        #[test]
+        fn test_add_key_slot_rejects_weak_iteration_counts() {
+            let mut app_data = ApplicationData::new();
+            app_data.add_master_password_data(b"password").unwrap();
+
+            for iterations in [0, 1_000, MIN_KDF_ITERATIONS - 1] {
+                assert!(matches!(
+                    app_data.add_key_slot(b"password", KeySlotKind::RecoveryKey, "backup".to_string(), b"secret", Some(iterations)),
+                    Err(CryptoError::InvalidInput)
+                ));
+            }
+            assert_eq!(app_data.list_key_slots().len(), 1);
+            assert!(matches!(
+                MekData::derive_mek_key_with_iterations(b"secret", b"salt", 0),
+                Err(CryptoError::InvalidInput)
+            ));
+        }
+
+        #[test]
+        fn test_remove_key_slot_keeps_one() {
+            let mut app_data = ApplicationData::new();
+            app_data.add_master_password_data(b"password").unwrap();
+            let master_slot_id = app_data.list_key_slots()[0].id.clone();
+            let backup_slot_id = app_data
+                .add_key_slot(b"password", KeySlotKind::RecoveryKey, "backup".to_string(), b"secret", None)
+                .unwrap();
+
+            assert!(matches!(
+                app_data.remove_key_slot(b"password", &master_slot_id),
+                Err(CryptoError::LastKeySlot)
+            ));
+            assert!(app_data.remove_key_slot(b"wrong_password", &backup_slot_id).is_err());
+            assert!(app_data.remove_key_slot(b"password", &backup_slot_id).is_ok());
+            assert!(matches!(
+                app_data.remove_key_slot(b"password", &backup_slot_id),
+                Err(CryptoError::KeySlotNotFound)
+            ));
+            assert!(matches!(
+                app_data.remove_key_slot(b"password", &master_slot_id),
+                Err(CryptoError::LastKeySlot)
+            ));
+            assert_eq!(app_data.list_key_slots().len(), 1);
+            assert!(app_data.verify_hashes());
+        }
+
+        #[test]
+        fn test_update_master_password_rewraps_slot() {
+            let mut app_data = ApplicationData::new();
+            app_data.add_master_password_data(b"old_password").unwrap();
+            let mek = app_data.decrypt_mek_data(b"old_password").unwrap();
+            let recovery_key = app_data.add_recovery_key(b"old_password").unwrap();
+
+            app_data.update_master_password_data(b"old_password", b"new_password").unwrap();
+
+            assert_eq!(app_data.decrypt_mek_data(b"new_password").unwrap(), mek);
+            assert!(app_data.decrypt_mek_with_slot_kind(KeySlotKind::MasterPassword, b"old_password").is_err());
+            // other slots are untouched by a master password change
+            app_data.recover_with_key(&recovery_key, b"newer_password").unwrap();
+            assert_eq!(app_data.decrypt_mek_data(b"newer_password").unwrap(), mek);
+            assert_eq!(app_data.list_key_slots().len(), 2);
+        }
+    }
+
+    mod migration_tests {
+        use crate::key_slot::KeySlotKind;
+        use crate::storage::ApplicationData;
+        use serde_json::{json, Value};
+
+        // Rewrites a current vault into the single `mek_data` layout used before key slots,
+        // with hashes computed over the JSON text exactly as the old versions did
+        fn legacy_file(app_data: &ApplicationData) -> Value {
+            let mut value = serde_json::to_value(app_data).unwrap();
+            let mut mek_data = value["key_slots"][0]["mek_data"].clone();
+            mek_data.as_object_mut().unwrap().remove("kdf_iterations");
+
+            let fields = value.as_object_mut().unwrap();
+            fields.remove("key_slots");
+            fields.remove("key_slots_hash");
+            fields.remove("hidden_vault");
+            fields.remove("hidden_vault_hash");
+            fields.insert("mek_data".to_string(), mek_data);
+            rehash(&mut value);
+            value
+        }
+
+        fn rehash(value: &mut Value) {
+            for section in ["master_password_data", "mek_data", "password_entries"] {
+                let serialized = serde_json::to_string(&value[section]).unwrap();
+                value[format!("{}_hash", section)] = json!(ApplicationData::generate_hash(serialized.as_bytes()));
+            }
+        }
+
+        async fn load(value: &Value) -> ApplicationData {
+            let temp_file = tempfile::NamedTempFile::new().unwrap();
+            std::fs::write(temp_file.path(), serde_json::to_string(value).unwrap()).unwrap();
+            ApplicationData::load_from_file(temp_file.path()).await.unwrap().unwrap()
+        }
+
+        #[tokio::test]
+        async fn test_legacy_mek_data_becomes_key_slot() {
+            let mut app_data = ApplicationData::new();
+            app_data.add_master_password_data(b"password").unwrap();
+            let mek = app_data.decrypt_mek_data(b"password").unwrap();
+
+            let loaded = load(&legacy_file(&app_data)).await;
+
+            assert!(loaded.verify_hashes());
+            let kinds: Vec<KeySlotKind> = loaded.list_key_slots().iter().map(|s| s.kind).collect();
+            assert_eq!(kinds, vec![KeySlotKind::MasterPassword]);
+            assert_eq!(loaded.decrypt_mek_data(b"password").unwrap(), mek);
+            assert!(loaded.hidden_vault.is_some());
+        }
+
+        #[tokio::test]
+        async fn test_tampered_legacy_file_is_not_upgraded() {
+            let mut app_data = ApplicationData::new();
+            app_data.add_master_password_data(b"password").unwrap();
+            let mut legacy = legacy_file(&app_data);
+            legacy["password_entries"] = json!([{
+                "id": "injected", "title": "injected", "username": null, "password": null, "url": null,
+                "notes": null, "creationDate": "", "category": null, "favorite": false
+            }]);
+
+            let loaded = load(&legacy).await;
+
+            assert!(!loaded.verify_hashes());
+            assert!(loaded.list_key_slots().is_empty());
+        }
+
+        #[tokio::test]
+        async fn test_entries_from_before_new_fields_load() {
+            let mut app_data = ApplicationData::new();
+            app_data.add_master_password_data(b"password").unwrap();
+            app_data.add_password_entry(crate::password_entry::PasswordEntry::default()).unwrap();
+            let mut value = serde_json::to_value(&app_data).unwrap();
+            let old_entry = value["password_entries"][0].as_object_mut().unwrap();
+            for field in [
+                "created", "modified", "passwordChanged", "lastUsed", "deleted", "kind", "details", "customFields",
+                "attachments", "passwordHistory", "folder", "tags", "urls", "expires", "rotationDays",
+            ] {
+                old_entry.remove(field);
+            }
+            // dates used to be chrono's Display output
+            old_entry.insert("creationDate".to_string(), json!("2024-03-01 10:15:00.123456 UTC"));
+            old_entry.insert("lastModified".to_string(), json!(null));
+            // and entries had a single category instead of a folder and tags
+            old_entry.insert("category".to_string(), json!("Work / Servers"));
+            // and a single URL
+            old_entry.insert("url".to_string(), json!("https://example.com/login"));
+            // Value sorts keys, so every section is rehashed over the text that gets written
+            for section in ["master_password_data", "key_slots", "password_entries", "hidden_vault"] {
+                let serialized = serde_json::to_string(&value[section]).unwrap();
+                value[format!("{}_hash", section)] = json!(ApplicationData::generate_hash(serialized.as_bytes()));
+            }
+
+            let loaded = load(&value).await;
+
+            assert!(loaded.verify_hashes());
+            assert_eq!(loaded.password_entries[0].created.to_rfc3339(), "2024-03-01T10:15:00.123456+00:00");
+            assert_eq!(loaded.password_entries[0].modified, None);
+            assert_eq!(loaded.password_entries[0].kind, crate::entry_kind::EntryKind::Login);
+            assert_eq!(loaded.password_entries[0].folder.as_deref(), Some("Work/Servers"));
+            assert_eq!(loaded.password_entries[0].category, None);
+            assert!(loaded.password_entries[0].tags.is_empty());
+            assert_eq!(loaded.password_entries[0].primary_url(), Some("https://example.com/login"));
+        }
+
+        #[tokio::test]
+        async fn test_plaintext_usernames_and_notes_are_encrypted_on_unlock() {
+            let mut app_data = ApplicationData::new();
+            app_data.add_master_password_data(b"password").unwrap();
+            app_data.add_password_entry(crate::password_entry::PasswordEntry::default()).unwrap();
+            let mut value = serde_json::to_value(&app_data).unwrap();
+            value["password_entries"][0]["username"] = json!("jane");
+            value["password_entries"][0]["notes"] = json!("recovery codes: 1234 5678");
+            for section in ["master_password_data", "key_slots", "password_entries", "hidden_vault"] {
+                let serialized = serde_json::to_string(&value[section]).unwrap();
+                value[format!("{}_hash", section)] = json!(ApplicationData::generate_hash(serialized.as_bytes()));
+            }
+
+            let mut loaded = load(&value).await;
+            assert!(loaded.verify_hashes());
+            assert!(!loaded.password_entries[0].username.as_ref().unwrap().is_encrypted());
+
+            let mek = loaded.decrypt_mek_data(b"password").unwrap();
+            assert!(loaded.encrypt_plaintext_fields(&mek).unwrap());
+            assert!(loaded.verify_hashes());
+            let stored = serde_json::to_string(&loaded.password_entries).unwrap();
+            assert!(!stored.contains("jane") && !stored.contains("recovery codes"));
+
+            let entry = loaded.password_entries[0].to_decrypted(&mek).unwrap();
+            assert_eq!(entry.username.as_deref(), Some("jane"));
+            assert_eq!(entry.notes.as_deref(), Some("recovery codes: 1234 5678"));
+            assert!(!loaded.encrypt_plaintext_fields(&mek).unwrap());
+        }
+
+        #[tokio::test]
+        async fn test_current_file_is_left_alone() {
+            let mut app_data = ApplicationData::new();
+            app_data.add_master_password_data(b"password").unwrap();
+
+            let loaded = load(&serde_json::to_value(&app_data).unwrap()).await;
+
+            assert!(loaded.verify_hashes());
+            assert_eq!(loaded.list_key_slots(), app_data.list_key_slots());
+        }
+    }
+
+    mod key_file_tests {
+        use crate::error_handling::CryptoError;
+        use crate::key_file::{generate_key_file, hash_key_file};
+        use crate::storage::ApplicationData;
+
+        async fn new_key_file(dir: &tempfile::TempDir, name: &str) -> [u8; 32] {
+            let path = dir.path().join(name);
+            generate_key_file(&path).await.unwrap();
+            hash_key_file(&path).await.unwrap()
+        }
+
+        #[tokio::test]
+        async fn test_generate_and_hash_key_file() {
+            let dir = tempfile::tempdir().unwrap();
+            let first = new_key_file(&dir, "first.key").await;
+            let second = new_key_file(&dir, "second.key").await;
+
+            assert_ne!(first, second);
+            assert_eq!(hash_key_file(&dir.path().join("first.key")).await.unwrap(), first);
+        }
+
+        #[tokio::test]
+        async fn test_empty_or_missing_key_file() {
+            let dir = tempfile::tempdir().unwrap();
+            let empty = dir.path().join("empty.key");
+            std::fs::write(&empty, b"").unwrap();
+
+            assert!(matches!(hash_key_file(&empty).await, Err(CryptoError::InvalidKeyFile)));
+            assert!(matches!(hash_key_file(&dir.path().join("missing.key")).await, Err(CryptoError::IO(_))));
+        }
+
+        #[tokio::test]
+        async fn test_unlock_requires_key_file() {
+            let dir = tempfile::tempdir().unwrap();
+            let key_file = new_key_file(&dir, "vault.key").await;
+            let wrong_key_file = new_key_file(&dir, "other.key").await;
+
+            let mut app_data = ApplicationData::new();
+            app_data.add_master_password_data(b"password").unwrap();
+            let mek = app_data.decrypt_mek_data(b"password").unwrap();
+            app_data.enable_key_file(b"password", &key_file).unwrap();
+
+            assert!(app_data.requires_key_file());
+            assert!(app_data.verify_hashes());
+            assert!(matches!(app_data.decrypt_mek_data(b"password"), Err(CryptoError::KeyFileRequired)));
+            assert!(app_data.decrypt_mek_data_with_key_file(b"password", Some(&wrong_key_file)).is_err());
+            assert!(app_data.decrypt_mek_data_with_key_file(b"wrong_password", Some(&key_file)).is_err());
+            assert_eq!(app_data.decrypt_mek_data_with_key_file(b"password", Some(&key_file)).unwrap(), mek);
+        }
+
+        #[tokio::test]
+        async fn test_disable_key_file() {
+            let dir = tempfile::tempdir().unwrap();
+            let key_file = new_key_file(&dir, "vault.key").await;
+            let wrong_key_file = new_key_file(&dir, "other.key").await;
+
+            let mut app_data = ApplicationData::new();
+            app_data.add_master_password_data(b"password").unwrap();
+            let mek = app_data.decrypt_mek_data(b"password").unwrap();
+            app_data.enable_key_file(b"password", &key_file).unwrap();
+
+            assert!(app_data.disable_key_file(b"password", &wrong_key_file).is_err());
+            assert!(app_data.requires_key_file());
+
+            app_data.disable_key_file(b"password", &key_file).unwrap();
+            assert!(!app_data.requires_key_file());
+            assert_eq!(app_data.decrypt_mek_data(b"password").unwrap(), mek);
+            assert_eq!(app_data.list_key_slots().len(), 1);
+        }
+
+        #[tokio::test]
+        async fn test_change_master_password_with_key_file() {
+            let dir = tempfile::tempdir().unwrap();
+            let key_file = new_key_file(&dir, "vault.key").await;
+
+            let mut app_data = ApplicationData::new();
+            app_data.add_master_password_data(b"old_password").unwrap();
+            let mek = app_data.decrypt_mek_data(b"old_password").unwrap();
+            app_data.enable_key_file(b"old_password", &key_file).unwrap();
+
+            // without the key file nothing may change, or the vault would lock itself out
+            assert!(app_data.update_master_password_data(b"old_password", b"new_password").is_err());
+            let other_key_file = new_key_file(&dir, "other.key").await;
+            assert!(app_data
+                .update_master_password_data_with_key_file(b"old_password", b"new_password", Some(&other_key_file))
+                .is_err());
+            assert!(app_data.verify_master_password(b"old_password"));
+
+            app_data
+                .update_master_password_data_with_key_file(b"old_password", b"new_password", Some(&key_file))
+                .unwrap();
+            assert!(app_data.decrypt_mek_data(b"new_password").is_err());
+            assert_eq!(app_data.decrypt_mek_data_with_key_file(b"new_password", Some(&key_file)).unwrap(), mek);
+        }
+
+        #[tokio::test]
+        async fn test_recovery_key_bypasses_lost_key_file() {
+            let dir = tempfile::tempdir().unwrap();
+            let key_file = new_key_file(&dir, "vault.key").await;
+
+            let mut app_data = ApplicationData::new();
+            app_data.add_master_password_data(b"password").unwrap();
+            let mek = app_data.decrypt_mek_data(b"password").unwrap();
+            let recovery_key = app_data.add_recovery_key(b"password").unwrap();
+            app_data.enable_key_file(b"password", &key_file).unwrap();
+
+            app_data.recover_with_key(&recovery_key, b"new_password").unwrap();
+
+            assert!(!app_data.requires_key_file());
+            assert_eq!(app_data.decrypt_mek_data(b"new_password").unwrap(), mek);
+        }
+    }
+
+    mod shamir_tests {
+        use crate::error_handling::CryptoError;
+        use crate::key_slot::KeySlotKind;
+        use crate::shamir::{combine_shares, split_secret, SecretShare};
+        use crate::storage::ApplicationData;
+
+        fn subset(shares: &[SecretShare], mask: u32) -> Vec<SecretShare> {
+            shares
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| mask & (1 << i) != 0)
+                .map(|(_, share)| share.clone())
+                .collect()
+        }
+
+        #[test]
+        fn test_every_threshold_combination() {
+            let secret: Vec<u8> = (0..32).map(|i| (i * 37 + 11) as u8).collect();
+
+            for share_count in 2..=6u8 {
+                for threshold in 2..=share_count {
+                    let shares = split_secret(&secret, threshold, share_count).unwrap();
+                    assert_eq!(shares.len(), share_count as usize);
+
+                    for mask in 0..(1u32 << share_count) {
+                        let chosen = subset(&shares, mask);
+                        let result = combine_shares(&chosen);
+                        if chosen.len() >= threshold as usize {
+                            assert_eq!(*result.unwrap(), secret, "{}-of-{} with mask {:b}", threshold, share_count, mask);
+                        } else {
+                            assert!(matches!(result, Err(CryptoError::InsufficientShares)));
+                        }
+                    }
+                }
+            }
+        }
+
+        #[test]
+        fn test_share_order_does_not_matter() {
+            let secret = b"order independent secret";
+            let mut shares = split_secret(secret, 3, 5).unwrap();
+            shares.reverse();
+
+            assert_eq!(*combine_shares(&shares[1..4]).unwrap(), secret.to_vec());
+        }
+
+        #[test]
+        fn test_split_secret_invalid_parameters() {
+            assert!(split_secret(b"secret", 1, 3).is_err());
+            assert!(split_secret(b"secret", 4, 3).is_err());
+            assert!(split_secret(b"", 2, 3).is_err());
+        }
+
+        #[test]
+        fn test_share_text_round_trip() {
+            let shares = split_secret(b"secret", 2, 3).unwrap();
+            let text = shares[1].to_text();
+
+            assert!(text.starts_with("pawpass-share:2:2:"));
+            assert_eq!(SecretShare::from_text(&format!("  {}\n", text)).unwrap(), shares[1]);
+
+            // a single mistyped character is caught by the checksum
+            let position = text.len() - 12;
+            let replacement = if &text[position..position + 1] == "0" { "1" } else { "0" };
+            let typo = format!("{}{}{}", &text[..position], replacement, &text[position + 1..]);
+            assert!(matches!(SecretShare::from_text(&typo), Err(CryptoError::InvalidShare)));
+            assert!(SecretShare::from_text("not a share").is_err());
+        }
+
+        #[test]
+        fn test_mismatched_shares_are_rejected() {
+            let two_of_three = split_secret(b"secret", 2, 3).unwrap();
+            let three_of_three = split_secret(b"secret", 3, 3).unwrap();
+
+            let duplicate = vec![two_of_three[0].clone(), two_of_three[0].clone()];
+            assert!(matches!(combine_shares(&duplicate), Err(CryptoError::InvalidShare)));
+
+            let mixed = vec![two_of_three[0].clone(), three_of_three[1].clone(), three_of_three[2].clone()];
+            assert!(matches!(combine_shares(&mixed), Err(CryptoError::InvalidShare)));
+        }
+
+        #[test]
+        fn test_secret_shares_unlock_mek() {
+            let mut app_data = ApplicationData::new();
+            app_data.add_master_password_data(b"password").unwrap();
+            let mek = app_data.decrypt_mek_data(b"password").unwrap();
+
+            let shares = app_data.create_secret_shares(b"password", 2, 3).unwrap();
+            assert_eq!(shares.len(), 3);
+            assert!(app_data.verify_hashes());
+            assert!(app_data.list_key_slots().iter().any(|s| s.kind == KeySlotKind::SecretShares));
+
+            for pair in [[0, 1], [0, 2], [1, 2]] {
+                let chosen: Vec<String> = pair.iter().map(|&i| shares[i].clone()).collect();
+                assert_eq!(app_data.decrypt_mek_with_shares(&chosen).unwrap(), mek);
+            }
+            assert!(matches!(
+                app_data.decrypt_mek_with_shares(&shares[..1]),
+                Err(CryptoError::InsufficientShares)
+            ));
+        }
+
+        #[test]
+        fn test_shares_from_another_split_fail() {
+            let mut app_data = ApplicationData::new();
+            app_data.add_master_password_data(b"password").unwrap();
+            app_data.create_secret_shares(b"password", 2, 3).unwrap();
+
+            let foreign: Vec<String> = split_secret(&[7u8; 32], 2, 3).unwrap().iter().map(SecretShare::to_text).collect();
+            assert!(matches!(
+                app_data.decrypt_mek_with_shares(&foreign[..2]),
+                Err(CryptoError::AuthenticationFailed)
+            ));
+            assert!(app_data.create_secret_shares(b"wrong_password", 2, 3).is_err());
+            assert!(app_data.create_secret_shares(b"password", 4, 3).is_err());
+        }
+
+        #[test]
+        fn test_recover_with_shares() {
+            let mut app_data = ApplicationData::new();
+            app_data.add_master_password_data(b"forgotten_password").unwrap();
+            let mek = app_data.decrypt_mek_data(b"forgotten_password").unwrap();
+            let shares = app_data.create_secret_shares(b"forgotten_password", 3, 5).unwrap();
+
+            app_data.recover_with_shares(&shares[2..], b"new_password").unwrap();
+
+            assert!(!app_data.verify_master_password(b"forgotten_password"));
+            assert_eq!(app_data.decrypt_mek_data(b"new_password").unwrap(), mek);
+            assert!(app_data.verify_hashes());
+        }
+    }
+
+    mod duress_tests {
+        use crate::error_handling::CryptoError;
+        use crate::hidden_vault::{HiddenVault, HiddenVaultKey};
+        use crate::password_entry::PasswordEntry;
+        use crate::storage::ApplicationData;
+
+        fn vault_with_entry(password: &[u8]) -> ApplicationData {
+            let mut app_data = ApplicationData::new();
+            app_data.add_master_password_data(password).unwrap();
+            let mek = app_data.decrypt_mek_data(password).unwrap();
+            let entry = PasswordEntry::new(
+                "Bank".to_string(),
+                Some("me".to_string()),
+                None,
+                None,
+                None,
+                None,
+                None,
+                Some(&mek),
+            )
+            .unwrap();
+            app_data.add_password_entry(entry).unwrap();
+            app_data
+        }
+
+        #[test]
+        fn test_duress_password_opens_decoy() {
+            let mut app_data = vault_with_entry(b"real_password");
+            let mek = app_data.decrypt_mek_data(b"real_password").unwrap();
+
+            app_data.enable_duress_password(b"real_password", b"duress_password").unwrap();
+
+            // the visible vault is now the empty decoy
+            assert!(app_data.verify_hashes());
+            assert!(app_data.verify_master_password(b"duress_password"));
+            assert!(!app_data.verify_master_password(b"real_password"));
+            assert!(app_data.password_entries.is_empty());
+            assert!(app_data.open_hidden_vault(b"duress_password").unwrap().is_none());
+
+            let (real, _) = app_data.open_hidden_vault(b"real_password").unwrap().unwrap();
+            assert_eq!(real.password_entries.len(), 1);
+            assert_eq!(real.decrypt_mek_data(b"real_password").unwrap(), mek);
+        }
+
+        #[test]
+        fn test_filler_looks_like_a_hidden_vault() {
+            let mut plain = vault_with_entry(b"real_password");
+            let mut decoyed = vault_with_entry(b"real_password");
+            decoyed.enable_duress_password(b"real_password", b"duress_password").unwrap();
+
+            let filler = plain.hidden_vault.as_ref().unwrap();
+            let sealed = decoyed.hidden_vault.as_ref().unwrap();
+            assert_eq!(filler.sealed.encrypted_data.len(), sealed.sealed.encrypted_data.len());
+            assert_eq!(filler.salt.len(), sealed.salt.len());
+            assert_eq!(filler.sealed.salt.len(), sealed.sealed.salt.len());
+            assert!(plain.open_hidden_vault(b"real_password").unwrap().is_none());
+
+            let plain_fields: Vec<String> = field_names(&plain);
+            assert_eq!(plain_fields, field_names(&decoyed));
+
+            // neither side can be switched off without a hidden vault behind it
+            assert!(matches!(
+                plain.disable_duress_password(b"real_password"),
+                Err(CryptoError::HiddenVaultNotFound)
+            ));
+            assert!(decoyed.disable_duress_password(b"duress_password").is_err());
+        }
+
+        #[test]
+        fn test_large_hidden_vault_is_the_size_of_filler() {
+            let plain = vault_with_entry(b"real_password");
+            let mut decoyed = vault_with_entry(b"real_password");
+            // well over the size of an empty vault
+            let entry = PasswordEntry {
+                title: "x".repeat(200 * 1024),
+                ..PasswordEntry::default()
+            };
+            decoyed.add_password_entry(entry).unwrap();
+            decoyed.enable_duress_password(b"real_password", b"duress_password").unwrap();
+
+            let filler = plain.hidden_vault.as_ref().unwrap();
+            let sealed = decoyed.hidden_vault.as_ref().unwrap();
+            assert_eq!(filler.sealed.encrypted_data.len(), sealed.sealed.encrypted_data.len());
+            let (real, _) = decoyed.open_hidden_vault(b"real_password").unwrap().unwrap();
+            assert_eq!(real.password_entries.len(), 2);
+
+            // a vault too big for the section can't be hidden at all
+            let key = HiddenVaultKey::new(b"real_password").unwrap();
+            assert!(matches!(
+                HiddenVault::seal(&vec![b' '; 16 * 64 * 1024], &key),
+                Err(CryptoError::HiddenVaultFull)
+            ));
+        }
+
+        #[test]
+        fn test_recovery_leaves_the_hidden_vault_alone() {
+            let mut app_data = vault_with_entry(b"real_password");
+            app_data.enable_duress_password(b"real_password", b"duress_password").unwrap();
+            let recovery_key = app_data.add_recovery_key(b"duress_password").unwrap();
+            let sealed_before = serde_json::to_string(&app_data.hidden_vault).unwrap();
+
+            // recovery only ever resets the visible vault's master password
+            app_data.recover_with_key(&recovery_key, b"new_duress_password").unwrap();
+            assert!(app_data.verify_hashes());
+            assert!(app_data.verify_master_password(b"new_duress_password"));
+            assert_eq!(serde_json::to_string(&app_data.hidden_vault).unwrap(), sealed_before);
+            let (real, _) = app_data.open_hidden_vault(b"real_password").unwrap().unwrap();
+            assert_eq!(real.password_entries.len(), 1);
+        }
+
+        fn field_names(app_data: &ApplicationData) -> Vec<String> {
+            let value = serde_json::to_value(app_data).unwrap();
+            value.as_object().unwrap().keys().cloned().collect()
+        }
+
+        #[test]
+        fn test_changes_are_sealed_back() {
+            let mut app_data = vault_with_entry(b"real_password");
+            app_data.enable_duress_password(b"real_password", b"duress_password").unwrap();
+
+            let (mut real, key) = app_data.open_hidden_vault(b"real_password").unwrap().unwrap();
+            real.password_entries.clear();
+            real.update_hashes().unwrap();
+            app_data.seal_hidden_vault(&real, &key).unwrap();
+
+            assert!(app_data.verify_hashes());
+            let (reopened, _) = app_data.open_hidden_vault(b"real_password").unwrap().unwrap();
+            assert!(reopened.password_entries.is_empty());
+        }
+
+        #[test]
+        fn test_disable_duress_password_restores_real_vault() {
+            let mut app_data = vault_with_entry(b"real_password");
+            app_data.enable_duress_password(b"real_password", b"duress_password").unwrap();
+
+            // enabling again from the hidden side swaps in a new decoy
+            app_data.enable_duress_password(b"real_password", b"other_duress").unwrap();
+            assert!(app_data.verify_master_password(b"other_duress"));
+
+            app_data.disable_duress_password(b"real_password").unwrap();
+
+            assert!(app_data.verify_hashes());
+            assert!(app_data.verify_master_password(b"real_password"));
+            assert_eq!(app_data.password_entries.len(), 1);
+            assert!(app_data.open_hidden_vault(b"other_duress").unwrap().is_none());
+        }
+
+        #[test]
+        fn test_enable_duress_password_rejects_bad_input() {
+            let mut app_data = vault_with_entry(b"real_password");
+
+            assert!(matches!(
+                app_data.enable_duress_password(b"wrong_password", b"duress_password"),
+                Err(CryptoError::AuthenticationFailed)
+            ));
+            assert!(matches!(
+                app_data.enable_duress_password(b"real_password", b"real_password"),
+                Err(CryptoError::InvalidInput)
+            ));
+            assert!(app_data.verify_master_password(b"real_password"));
+            assert_eq!(app_data.password_entries.len(), 1);
+        }
+    }
+
+    mod import_tests {
+        use crate::custom_field::{CustomFieldType, DecryptedCustomField};
+        use crate::error_handling::CryptoError;
+        use crate::import::bitwarden::import_bitwarden_json;
+        use crate::import::csv::{import_csv, preview_csv, ColumnMapping, CsvImportOptions, CsvPreset};
+        use crate::import::kdbx::import_kdbx;
+        use crate::import::onepux::import_1pux;
+        use crate::import::pass::{import_pass_store, PassDecryptor};
+        use crate::import::SkippedItem;
+        use crate::kdbx::reader::read_kdbx;
+        use crate::kdbx::{composite_key, KdfParameters};
+        use crate::password_entry::PasswordEntry;
+        use crate::url_match::{EntryUrl, UrlMatch};
+
+        const MEK: &[u8] = b"0123456789abcdef0123456789abcdef";
+        const BITWARDEN_EXPORT: &str = include_str!("../fixtures/bitwarden_export.json");
+        const KDBX_ARGON2_CHACHA20: &[u8] = include_bytes!("../fixtures/kdbx4_argon2id_chacha20.kdbx");
+        const KDBX_AESKDF_AES: &[u8] = include_bytes!("../fixtures/kdbx4_aeskdf_aes.kdbx");
+        const KDBX_PASSWORD: &[u8] = b"fixture-password";
+        const CHROME_CSV: &[u8] = include_bytes!("../fixtures/chrome_passwords.csv");
+        const FIREFOX_CSV: &[u8] = include_bytes!("../fixtures/firefox_logins.csv");
+        const LASTPASS_CSV: &[u8] = include_bytes!("../fixtures/lastpass_export.csv");
+        const GENERIC_UTF16_CSV: &[u8] = include_bytes!("../fixtures/generic_utf16.csv");
+        const ONEPUX_EXPORT: &[u8] = include_bytes!("../fixtures/onepassword_export.1pux");
+
+        // Stands in for gpg: files hold their plaintext, and ones starting with "BAD" fail
+        struct FakeDecryptor;
+
+        impl PassDecryptor for FakeDecryptor {
+            fn decrypt(&self, path: &std::path::Path) -> Result<zeroize::Zeroizing<Vec<u8>>, CryptoError> {
+                let content = std::fs::read(path)?;
+                if content.starts_with(b"BAD") {
+                    return Err(CryptoError::ImportFailed("gpg: decryption failed: No secret key".into()));
+                }
+                Ok(zeroize::Zeroizing::new(content))
+            }
+        }
+
+        fn csv_options(preset: CsvPreset) -> CsvImportOptions {
+            CsvImportOptions { preset, encoding: None }
+        }
+
+        fn find<'a>(entries: &'a [PasswordEntry], title: &str) -> &'a PasswordEntry {
+            entries.iter().find(|e| e.title == title).unwrap()
+        }
+
+        #[test]
+        fn test_import_bitwarden_logins_and_notes() {
+            let outcome = import_bitwarden_json(BITWARDEN_EXPORT, MEK).unwrap();
+            assert_eq!(outcome.report.imported, 3);
+            assert_eq!(outcome.entries.len(), 3);
+
+            let gitlab = find(&outcome.entries, "GitLab").to_decrypted(MEK).unwrap();
+            assert_eq!(gitlab.username.as_deref(), Some("jdoe"));
+            assert_eq!(gitlab.password.as_deref(), Some("correct horse battery staple"));
+            assert_eq!(
+                gitlab.urls,
+                vec![
+                    EntryUrl::new("https://gitlab.example.com"),
+                    EntryUrl::with_match("https://gitlab-backup.example.com", UrlMatch::Host),
+                ]
+            );
+            assert_eq!(gitlab.folder.as_deref(), Some("Work"));
+            assert!(gitlab.favorite);
+            assert_eq!(gitlab.created.to_string(), "2023-03-14 09:26:53.589 UTC");
+            let notes = gitlab.notes.as_deref().unwrap();
+            assert!(notes.starts_with("SSO via company account"));
+            assert!(!notes.contains("gitlab-backup"));
+            assert!(!notes.contains("Team") && !notes.contains("4821"));
+            assert_eq!(
+                gitlab.custom_fields,
+                vec![
+                    DecryptedCustomField::new("Team", CustomFieldType::Text, "Platform"),
+                    DecryptedCustomField::new("Recovery PIN", CustomFieldType::Hidden, "4821"),
+                    DecryptedCustomField::new("Admin", CustomFieldType::Boolean, "true"),
+                ]
+            );
+            // hidden values are sealed rather than stored as text
+            let stored = find(&outcome.entries, "GitLab");
+            assert!(stored.custom_fields[1].value.is_none() && stored.custom_fields[1].protected_value.is_some());
+            assert_eq!(stored.custom_fields[0].value.as_deref(), Some("Platform"));
+
+            let wifi = find(&outcome.entries, "Wi-Fi").to_decrypted(MEK).unwrap();
+            assert!(wifi.password.is_none());
+            assert_eq!(wifi.folder, None);
+            assert_eq!(wifi.notes.as_deref(), Some("Guest network: hunter2"));
+
+            let forum = find(&outcome.entries, "Forum");
+            assert!(forum.password.is_none());
+            assert_eq!(forum.folder.as_deref(), Some("Personal"));
+        }
+
+        #[test]
+        fn test_import_bitwarden_reports_skipped() {
+            let outcome = import_bitwarden_json(BITWARDEN_EXPORT, MEK).unwrap();
+            let skipped: Vec<&str> = outcome.report.skipped.iter().map(|s| s.name.as_str()).collect();
+
+            assert_eq!(skipped, vec!["GitLab / TOTP", "Visa"]);
+            assert!(outcome.report.skipped.contains(&SkippedItem {
+                name: "Visa".to_string(),
+                reason: "only logins and secure notes can be imported".to_string(),
+            }));
+        }
+
+        #[test]
+        fn test_import_bitwarden_rejects_encrypted_export() {
+            let encrypted = r#"{"encrypted": true, "passwordProtected": true, "data": "2.abc"}"#;
+            assert!(matches!(
+                import_bitwarden_json(encrypted, MEK),
+                Err(CryptoError::ImportFailed(_))
+            ));
+            assert!(matches!(import_bitwarden_json("not json", MEK), Err(CryptoError::Serde(_))));
+        }
+
+        #[test]
+        fn test_read_kdbx_both_cipher_suites() {
+            for fixture in [KDBX_ARGON2_CHACHA20, KDBX_AESKDF_AES] {
+                let database = read_kdbx(fixture, KDBX_PASSWORD).unwrap();
+                assert_eq!(database.name.as_deref(), Some("Fixture"));
+
+                let titles: Vec<&str> = database.entries.iter().map(|e| e.title.as_deref().unwrap()).collect();
+                assert_eq!(titles, vec!["Root Entry", "GitLab", "Prod DB", "Deleted"]);
+                assert_eq!(database.entries[0].uuid, Some([2u8; 16]));
+
+                // protected values after the history entry only decrypt if the keystream stayed in step
+                let passwords: Vec<&str> = database.entries.iter().map(|e| e.password.as_deref().unwrap()).collect();
+                assert_eq!(passwords, vec!["rootpw", "correct horse battery staple", "s3cret-ü", "gone"]);
+                assert!(database.entries[3].in_recycle_bin);
+            }
+        }
+
+        #[test]
+        fn test_import_kdbx_entries() {
+            let outcome = import_kdbx(KDBX_ARGON2_CHACHA20, KDBX_PASSWORD, MEK).unwrap();
+            assert_eq!(outcome.report.imported, 3);
+
+            let root = find(&outcome.entries, "Root Entry");
+            assert_eq!(root.folder, None);
+            assert!(root.notes.is_none());
+
+            let gitlab = find(&outcome.entries, "GitLab").to_decrypted(MEK).unwrap();
+            assert_eq!(gitlab.folder.as_deref(), Some("Work"));
+            assert_eq!(gitlab.password.as_deref(), Some("correct horse battery staple"));
+            assert_eq!(gitlab.primary_url(), Some("https://gitlab.example.com"));
+            assert!(gitlab.favorite);
+            assert_eq!(gitlab.created.to_string(), "2023-03-14 09:26:53 UTC");
+            assert_eq!(gitlab.notes.as_deref(), Some("SSO & 2FA"));
+            assert_eq!(gitlab.tags, vec!["dev"]);
+            assert_eq!(
+                gitlab.custom_fields,
+                vec![
+                    DecryptedCustomField::new("Team", CustomFieldType::Text, "Platform"),
+                    DecryptedCustomField::new("PIN", CustomFieldType::Hidden, "4821"),
+                ]
+            );
+
+            let prod = find(&outcome.entries, "Prod DB");
+            assert_eq!(prod.folder.as_deref(), Some("Work/Servers"));
+            assert!(!prod.favorite);
+
+            let skipped: Vec<&str> = outcome.report.skipped.iter().map(|s| s.name.as_str()).collect();
+            assert_eq!(skipped, vec!["GitLab / id_rsa", "Deleted"]);
+        }
+
+        #[test]
+        fn test_import_kdbx_wrong_password() {
+            assert!(matches!(
+                import_kdbx(KDBX_AESKDF_AES, b"wrong-password", MEK),
+                Err(CryptoError::AuthenticationFailed)
+            ));
+            assert!(matches!(
+                import_kdbx(BITWARDEN_EXPORT.as_bytes(), KDBX_PASSWORD, MEK),
+                Err(CryptoError::ImportFailed(_))
+            ));
+
+            let mut corrupted = KDBX_AESKDF_AES.to_vec();
+            let last = corrupted.len() - 40;
+            corrupted[last] ^= 1;
+            assert!(matches!(
+                import_kdbx(&corrupted, KDBX_PASSWORD, MEK),
+                Err(CryptoError::ImportFailed(_))
+            ));
+        }
+
+        #[test]
+        fn test_kdbx_kdf_limits() {
+            let key = composite_key(KDBX_PASSWORD);
+            let aes = KdfParameters::Aes { rounds: 2_000_000_000, seed: vec![0; 32] };
+            assert!(matches!(aes.transform(&key), Err(CryptoError::ImportFailed(_))));
+            let argon2 = KdfParameters::Argon2 {
+                algorithm: argon2::Algorithm::Argon2id,
+                salt: vec![0; 32],
+                iterations: 1,
+                memory_bytes: 4 * 1024 * 1024 * 1024,
+                parallelism: 1,
+            };
+            assert!(matches!(argon2.transform(&key), Err(CryptoError::ImportFailed(_))));
+        }
+
+        #[test]
+        fn test_import_csv_chrome_quoting_and_multiline_notes() {
+            let outcome = import_csv(CHROME_CSV, &csv_options(CsvPreset::Chrome), MEK).unwrap();
+            assert_eq!(outcome.report.imported, 2);
+
+            let github = find(&outcome.entries, "GitHub").to_decrypted(MEK).unwrap();
+            assert_eq!(github.password.as_deref(), Some("pa,ss\"word"));
+            assert_eq!(github.notes, None);
+            let bank = find(&outcome.entries, "Bank").to_decrypted(MEK).unwrap();
+            assert_eq!(bank.notes.as_deref(), Some("Security questions:\r\nfirst pet: Rex"));
+
+            assert!(matches!(
+                import_csv(FIREFOX_CSV, &csv_options(CsvPreset::Chrome), MEK),
+                Err(CryptoError::ImportFailed(_))
+            ));
+        }
+
+        #[test]
+        fn test_import_csv_firefox_and_lastpass_presets() {
+            let firefox = import_csv(FIREFOX_CSV, &csv_options(CsvPreset::Firefox), MEK).unwrap();
+            let alice = find(&firefox.entries, "accounts.example.org").to_decrypted(MEK).unwrap();
+            assert_eq!(alice.username.as_deref(), Some("alice"));
+            assert_eq!(alice.created.to_string(), "2023-03-14 09:26:53.589 UTC");
+
+            // windows-1252 is picked up without being told
+            let lastpass = import_csv(LASTPASS_CSV, &csv_options(CsvPreset::LastPass), MEK).unwrap();
+            let cafe = find(&lastpass.entries, "Café").to_decrypted(MEK).unwrap();
+            assert_eq!(cafe.username.as_deref(), Some("renée"));
+            assert_eq!(cafe.password.as_deref(), Some("crème"));
+            assert_eq!(cafe.folder.as_deref(), Some("Social/Food"));
+            assert!(cafe.favorite);
+            let note = find(&lastpass.entries, "DB server").to_decrypted(MEK).unwrap();
+            assert!(note.urls.is_empty());
+            assert!(note.password.is_none());
+            assert_eq!(note.notes.as_deref(), Some("NoteType:Server\nHostname:db1"));
+            assert_eq!(lastpass.report.skipped.len(), 1);
+            assert_eq!(lastpass.report.skipped[0].name, "Café / TOTP");
+        }
+
+        #[test]
+        fn test_preview_csv_custom_mapping_utf16() {
+            let options = csv_options(CsvPreset::Custom(ColumnMapping {
+                title: Some("site".into()),
+                username: Some("LOGIN".into()),
+                password: Some("Secret".into()),
+                ..ColumnMapping::default()
+            }));
+            let preview = preview_csv(GENERIC_UTF16_CSV, &options).unwrap();
+            assert_eq!(preview.headers, vec!["Site", "Login", "Secret"]);
+            assert_eq!(preview.total_rows, 1);
+            assert_eq!(preview.rows[0].title, "Intranet");
+            assert!(preview.rows[0].has_password);
+
+            let outcome = import_csv(GENERIC_UTF16_CSV, &options, MEK).unwrap();
+            let intranet = find(&outcome.entries, "Intranet").to_decrypted(MEK).unwrap();
+            assert_eq!(intranet.password.as_deref(), Some("pwß"));
+
+            let missing = csv_options(CsvPreset::Custom(ColumnMapping {
+                url: Some("address".into()),
+                ..ColumnMapping::default()
+            }));
+            assert!(matches!(preview_csv(GENERIC_UTF16_CSV, &missing), Err(CryptoError::ImportFailed(_))));
+        }
+
+        #[test]
+        fn test_import_1pux() {
+            let outcome = import_1pux(ONEPUX_EXPORT, MEK).unwrap();
+            assert_eq!(outcome.report.imported, 3);
+
+            let github = find(&outcome.entries, "GitHub").to_decrypted(MEK).unwrap();
+            assert_eq!(github.username.as_deref(), Some("jdoe"));
+            assert_eq!(github.password.as_deref(), Some("correct horse battery staple"));
+            assert_eq!(
+                github.urls,
+                vec![EntryUrl::new("https://github.com/login"), EntryUrl::new("https://gist.github.com")]
+            );
+            assert_eq!(github.folder.as_deref(), Some("Personal"));
+            assert!(github.favorite);
+            assert_eq!(github.created.to_string(), "2023-03-14 09:26:53 UTC");
+            assert_eq!(
+                github.notes.as_deref(),
+                Some("Main account")
+            );
+            assert_eq!(github.tags, vec!["dev", "work"]);
+            assert_eq!(
+                github.custom_fields,
+                vec![
+                    DecryptedCustomField::new("Recovery email", CustomFieldType::Text, "backup@example.com"),
+                    DecryptedCustomField::new("PIN", CustomFieldType::Hidden, "4821"),
+                    DecryptedCustomField::new("Plan", CustomFieldType::Text, "Pro"),
+                ]
+            );
+
+            let office = find(&outcome.entries, "Office");
+            assert!(office.password.is_none());
+            assert_eq!(office.folder.as_deref(), Some("Shared"));
+            let router = find(&outcome.entries, "Router").to_decrypted(MEK).unwrap();
+            assert_eq!(router.password.as_deref(), Some("router-admin-pw"));
+
+            let skipped: Vec<(&str, &str)> = outcome
+                .report
+                .skipped
+                .iter()
+                .map(|s| (s.name.as_str(), s.reason.as_str()))
+                .collect();
+            assert_eq!(skipped[0].0, "GitHub / one-time password");
+            assert_eq!(skipped[1], ("Visa", "1Password credit card items aren't supported"));
+
+            assert!(matches!(import_1pux(CHROME_CSV, MEK), Err(CryptoError::ImportFailed(_))));
+        }
+
+        #[test]
+        fn test_import_pass_store() {
+            let store = tempfile::tempdir().unwrap();
+            let root = store.path();
+            std::fs::create_dir_all(root.join("work/servers")).unwrap();
+            std::fs::create_dir_all(root.join(".git")).unwrap();
+            std::fs::write(root.join(".gpg-id"), "ABCDEF12\n").unwrap();
+            std::fs::write(root.join(".git/config.gpg"), "not an entry").unwrap();
+            std::fs::write(
+                root.join("github.com.gpg"),
+                "hunter2\nlogin: octo\nurl: https://github.com\nRecovery PIN: 4821\nkeep offline\nhttps://github.com/settings\notpauth://totp/x?secret=AAAA\n",
+            )
+            .unwrap();
+            std::fs::write(root.join("work/servers/db1.gpg"), "s3cret\nport: 5432\n").unwrap();
+            std::fs::write(root.join("work/broken.gpg"), "BAD").unwrap();
+            std::fs::write(root.join("work/readme.txt"), "ignored").unwrap();
+
+            let outcome = import_pass_store(root, &FakeDecryptor, MEK).unwrap();
+            assert_eq!(outcome.report.imported, 2);
+
+            let github = find(&outcome.entries, "github.com").to_decrypted(MEK).unwrap();
+            assert_eq!(github.password.as_deref(), Some("hunter2"));
+            assert_eq!(github.username.as_deref(), Some("octo"));
+            assert_eq!(github.primary_url(), Some("https://github.com"));
+            assert_eq!(github.notes.as_deref(), Some("keep offline\nhttps://github.com/settings"));
+            assert_eq!(github.folder, None);
+            assert_eq!(github.custom_fields.len(), 1);
+            assert_eq!(github.custom_fields[0].name, "Recovery PIN");
+            assert_eq!(github.custom_fields[0].field_type, CustomFieldType::Hidden);
+            assert_eq!(github.custom_fields[0].value, "4821");
+
+            let db = find(&outcome.entries, "db1").to_decrypted(MEK).unwrap();
+            assert_eq!(db.password.as_deref(), Some("s3cret"));
+            assert_eq!(db.folder.as_deref(), Some("work/servers"));
+            assert_eq!(db.notes, None);
+            assert_eq!(db.custom_fields[0].name, "port");
+            assert_eq!(db.custom_fields[0].field_type, CustomFieldType::Text);
+            assert_eq!(db.custom_fields[0].value, "5432");
+
+            let skipped: Vec<&str> = outcome.report.skipped.iter().map(|s| s.name.as_str()).collect();
+            assert_eq!(skipped, vec!["github.com / otpauth", "work/broken"]);
+            assert!(outcome.report.skipped[1].reason.contains("No secret key"));
+
+            assert!(matches!(
+                import_pass_store(&root.join("missing"), &FakeDecryptor, MEK),
+                Err(CryptoError::ImportFailed(_))
+            ));
+        }
+    }
+
+    mod export_tests {
+        use super::login;
+        use crate::error_handling::CryptoError;
+        use crate::export::kdbx::export_kdbx_with_settings;
+        use crate::export::plaintext::{export_plaintext_to_file, write_plaintext, PlaintextFormat};
+        use crate::export::portable::export_portable;
+        use crate::import::kdbx::import_kdbx;
+        use crate::import::portable::read_portable;
+        use crate::kdbx::Argon2Settings;
+        use crate::password_entry::DecryptedPasswordEntry;
+        use crate::password_history::{record_password, PasswordHistoryItem};
+        use crate::url_match::EntryUrl;
+
+        const MEK: &[u8] = b"0123456789abcdef0123456789abcdef";
+        const FAST_ARGON2: Argon2Settings = Argon2Settings { memory_kib: 1024, iterations: 1, parallelism: 1 };
+
+        // Notes and URL carry the characters each format has to escape
+        fn entry(title: &str, password: Option<&str>, folder: Option<&str>, favorite: bool) -> DecryptedPasswordEntry {
+            let mut entry = login(title, "user@example.com", password, Some("https://example.com/login?a=1&b=2"));
+            entry.notes = Some("line one\n<b>line two</b> & \"three\"".to_string());
+            entry.folder = folder.map(str::to_string);
+            entry.favorite = favorite;
+            entry
+        }
+
+        #[test]
+        fn test_kdbx_export_round_trip() {
+            let mut entries = vec![
+                entry("Bank", Some("pässwörd <&>"), Some("Finance"), true),
+                entry("Prod DB", Some("s3cret"), Some("Work/Servers"), false),
+                entry("Wi-Fi", None, None, false),
+            ];
+            // the extra URLs go through KeePassXC's KP2A_URL fields
+            entries[1].urls.push(EntryUrl::new("https://db.example.co.uk"));
+            entries[1].urls.push(EntryUrl::new("db.internal:5432"));
+            // KeePass has expiry dates but no rotation intervals
+            entries[0].expires = Some(entries[0].created + chrono::Duration::days(30));
+            entries[1].rotation_days = Some(90);
+
+            let exported = export_kdbx_with_settings(&entries, b"export-password", FAST_ARGON2).unwrap();
+            let outcome = import_kdbx(&exported, b"export-password", MEK).unwrap();
+
+            assert_eq!(outcome.report.imported, 3);
+            assert!(outcome.report.skipped.is_empty());
+            for original in &entries {
+                let imported = outcome
+                    .entries
+                    .iter()
+                    .find(|e| e.title == original.title)
+                    .unwrap()
+                    .to_decrypted(MEK)
+                    .unwrap();
+                assert_eq!(imported.username, original.username);
+                assert_eq!(imported.password, original.password);
+                assert_eq!(imported.urls, original.urls);
+                assert!(imported.custom_fields.is_empty());
+                assert_eq!(imported.notes, original.notes);
+                assert_eq!(imported.folder, original.folder);
+                assert_eq!(imported.favorite, original.favorite);
+                // KDBX keeps whole seconds
+                assert_eq!(imported.created.timestamp(), original.created.timestamp());
+                assert_eq!(
+                    imported.expires.map(|expires| expires.timestamp()),
+                    original.expiry_due().map(|due| due.timestamp())
+                );
+                assert_eq!(imported.rotation_days, None);
+            }
+        }
+
+        #[test]
+        fn test_kdbx_export_needs_password() {
+            let entries = vec![entry("Bank", Some("password"), None, false)];
+            assert!(matches!(
+                export_kdbx_with_settings(&entries, b"", FAST_ARGON2),
+                Err(CryptoError::InvalidInput)
+            ));
+
+            let exported = export_kdbx_with_settings(&entries, b"export-password", FAST_ARGON2).unwrap();
+            assert!(matches!(
+                import_kdbx(&exported, b"wrong", MEK),
+                Err(CryptoError::AuthenticationFailed)
+            ));
+        }
+
+        #[test]
+        fn test_portable_export_round_trip() {
+            let entries = vec![
+                entry("Bank", Some("pässwörd <&>"), Some("Finance"), true),
+                entry("Wi-Fi", None, None, false),
+            ];
+            let exported = export_portable(&entries, b"export passphrase").unwrap();
+            assert!(!String::from_utf8_lossy(&exported).contains("Bank"));
+
+            let imported = read_portable(&exported, b"export passphrase").unwrap();
+            assert_eq!(imported.len(), 2);
+            assert_eq!(imported[0].id, entries[0].id);
+            assert_eq!(imported[0].password.as_deref(), Some("pässwörd <&>"));
+            assert_eq!(imported[1].password, None);
+
+            assert!(matches!(
+                read_portable(&exported, b"wrong passphrase"),
+                Err(CryptoError::AuthenticationFailed)
+            ));
+            assert!(matches!(read_portable(b"{}", b"export passphrase"), Err(CryptoError::ImportFailed(_))));
+            assert!(matches!(export_portable(&entries, b""), Err(CryptoError::InvalidInput)));
+        }
+
+        #[test]
+        fn test_plaintext_export_json_and_csv() {
+            let mut entries = vec![
+                entry("Bank", Some("pa,ss\"word"), Some("Finance"), true),
+                entry("Wi-Fi", None, None, false),
+            ];
+            record_password(&mut entries[0].password_history, "older".to_string());
+
+            let mut json: Vec<u8> = Vec::new();
+            write_plaintext(&entries, PlaintextFormat::Json, &mut json).unwrap();
+            let parsed: Vec<DecryptedPasswordEntry> = serde_json::from_slice(&json).unwrap();
+            assert_eq!(parsed.len(), 2);
+            assert_eq!(parsed[0].id, entries[0].id);
+            assert_eq!(parsed[0].password.as_deref(), Some("pa,ss\"word"));
+            assert_eq!(parsed[0].password_history, entries[0].password_history);
+            assert_eq!(parsed[1].password, None);
+            let objects: Vec<serde_json::Map<String, serde_json::Value>> = serde_json::from_slice(&json).unwrap();
+            let mut json_keys: Vec<&str> = objects[0].keys().map(String::as_str).collect();
+
+            let mut csv: Vec<u8> = Vec::new();
+            write_plaintext(&entries, PlaintextFormat::Csv, &mut csv).unwrap();
+            let mut reader = ::csv::Reader::from_reader(csv.as_slice());
+            assert_eq!(
+                reader.headers().unwrap(),
+                vec![
+                    "id", "title", "username", "password", "urls", "notes", "created", "modified", "passwordChanged",
+                    "lastUsed", "expires", "rotationDays", "folder", "tags", "favorite", "kind", "details",
+                    "customFields", "passwordHistory"
+                ]
+            );
+            // the JSON objects have exactly the CSV columns
+            let mut csv_keys: Vec<&str> = reader.headers().unwrap().iter().collect();
+            csv_keys.sort_unstable();
+            json_keys.sort_unstable();
+            assert_eq!(json_keys, csv_keys);
+            let rows: Vec<::csv::StringRecord> = reader.records().map(Result::unwrap).collect();
+            assert_eq!(rows.len(), 2);
+            assert_eq!(&rows[0][3], "pa,ss\"word");
+            let urls: Vec<EntryUrl> = serde_json::from_str(&rows[0][4]).unwrap();
+            assert_eq!(urls, entries[0].urls);
+            assert_eq!(&rows[0][5], "line one\n<b>line two</b> & \"three\"");
+            assert_eq!(&rows[0][6], entries[0].created.to_rfc3339());
+            assert_eq!(&rows[0][7], "");
+            assert_eq!((&rows[0][10], &rows[0][11]), ("", ""));
+            assert_eq!(&rows[0][13], "");
+            assert_eq!(&rows[0][14], "true");
+            assert_eq!(&rows[0][15], "login");
+            assert_eq!(&rows[0][16], "");
+            let history: Vec<PasswordHistoryItem> = serde_json::from_str(&rows[0][18]).unwrap();
+            assert_eq!(history, entries[0].password_history);
+            assert_eq!(&rows[1][3], "");
+            assert_eq!(&rows[1][18], "");
+        }
+
+        #[test]
+        fn test_plaintext_export_to_file() {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("export.csv");
+            export_plaintext_to_file(&[entry("Bank", Some("pw"), None, false)], PlaintextFormat::Csv, &path).unwrap();
+            assert!(std::fs::read_to_string(&path).unwrap().contains("Bank"));
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+            }
+        }
+    }
+
+    mod dedupe_tests {
+        use super::login;
+        use crate::attachment::DecryptedAttachment;
+        use crate::import::dedupe::{find_conflicts, merge_entries, normalize_url, MergeMode, MergeOptions};
+        use crate::password_history::record_password;
+
+        #[test]
+        fn test_normalize_url() {
+            assert_eq!(normalize_url("https://www.Example.com/login/").as_deref(), Some("example.com/login"));
+            assert_eq!(normalize_url("http://example.com:80/login?next=/#top").as_deref(), Some("example.com/login"));
+            assert_eq!(normalize_url("example.com").as_deref(), Some("example.com"));
+            assert_eq!(normalize_url("https://user@example.com:8443").as_deref(), Some("example.com:8443"));
+            assert_eq!(normalize_url("  "), None);
+        }
+
+        #[test]
+        fn test_find_conflicts_by_url_and_username() {
+            let vault = vec![login("GitHub", "octo", Some("old"), Some("https://github.com/login"))];
+            let incoming = vec![
+                login("github.com", "Octo", Some("old"), Some("http://www.github.com/login/")),
+                login("GitHub (work)", "octo-work", Some("w"), Some("https://github.com/login")),
+                login("GitHub again", "octo-work", Some("w2"), Some("https://github.com/login?x=1")),
+            ];
+            let conflicts = find_conflicts(&vault, &incoming);
+            assert_eq!(conflicts.len(), 2);
+            assert_eq!(conflicts[0].incoming_id, incoming[0].id);
+            assert_eq!(conflicts[0].existing_id, vault[0].id);
+            assert!(conflicts[0].same_password);
+            // duplicates within one import are reported against the earlier copy
+            assert_eq!(conflicts[1].incoming_id, incoming[2].id);
+            assert_eq!(conflicts[1].existing_id, incoming[1].id);
+            assert!(!conflicts[1].same_password);
+        }
+
+        #[test]
+        fn test_overwrite_keeps_history_attachments_and_trash_state() {
+            let mut bank = login("Bank", "jdoe", Some("current"), Some("https://bank.example.com"));
+            record_password(&mut bank.password_history, "oldest".to_string());
+            bank.attachments.push(DecryptedAttachment {
+                id: "6f1c1b8e-4d3a-4f2e-9b7a-2f8d5c3e1a90".to_string(),
+                name: "statement.pdf".to_string(),
+                size: 1000,
+                added: bank.created,
+            });
+            bank.move_to_trash();
+            let trashed_at = bank.deleted;
+            let imported = login("Bank (import)", "jdoe", Some("imported"), Some("https://bank.example.com/"));
+            assert_eq!(imported.modified, None);
+
+            let mut vault = vec![bank.clone()];
+            let report = merge_entries(&mut vault, vec![imported], &MergeOptions::with_default(MergeMode::Overwrite));
+            assert_eq!(report.replaced, 1);
+            let merged = &vault[0];
+            assert_eq!(merged.id, bank.id);
+            assert_eq!(merged.title, "Bank (import)");
+            assert_eq!(merged.password.as_deref(), Some("imported"));
+            let history: Vec<&str> = merged.password_history.iter().map(|item| item.password.as_str()).collect();
+            assert_eq!(history, vec!["current", "oldest"]);
+            assert_eq!(merged.attachments, bank.attachments);
+            assert_eq!(merged.deleted, trashed_at);
+            assert!(merged.modified_at() >= bank.modified_at());
+            assert!(merged.password_changed.is_some());
+        }
+
+        #[test]
+        fn test_merge_per_entry_choices() {
+            let bank = login("Bank", "jdoe", Some("old"), Some("https://bank.example.com"));
+            let mail = login("Mail", "jdoe", Some("m"), Some("https://mail.example.com"));
+            let vault = vec![bank.clone(), mail.clone()];
+
+            let mut same_id = bank.clone();
+            same_id.password = Some("new".to_string());
+            let mail_copy = login("Webmail", "JDOE", Some("m2"), Some("https://mail.example.com/"));
+            let new_entry = login("Shop", "jdoe", Some("s"), Some("https://shop.example.com"));
+            let incoming = vec![same_id.clone(), mail_copy.clone(), new_entry];
+
+            let mut skipped = vault.clone();
+            let report = merge_entries(&mut skipped, incoming.clone(), &MergeOptions::default());
+            assert_eq!((report.imported, report.replaced, report.skipped.len()), (1, 0, 2));
+            assert_eq!(report.skipped[1].reason, "duplicate of 'Mail'");
+
+            let mut options = MergeOptions::with_default(MergeMode::Overwrite);
+            options.per_entry.insert(mail_copy.id.clone(), MergeMode::KeepBoth);
+            let mut merged = vault.clone();
+            let report = merge_entries(&mut merged, incoming.clone(), &options);
+            assert_eq!((report.imported, report.replaced, report.skipped.len()), (2, 1, 0));
+            assert_eq!(merged.len(), 4);
+            assert_eq!(merged[0].password.as_deref(), Some("new"));
+            assert_eq!(merged[2].id, mail_copy.id);
+
+            let mut overwritten = vault.clone();
+            merge_entries(&mut overwritten, vec![mail_copy], &MergeOptions::with_default(MergeMode::Overwrite));
+            assert_eq!(overwritten.len(), 2);
+            assert_eq!(overwritten[1].id, mail.id);
+            assert_eq!(overwritten[1].created, mail.created);
+            assert_eq!(overwritten[1].title, "Webmail");
+
+            let mut both = vault.clone();
+            merge_entries(&mut both, vec![same_id], &MergeOptions::with_default(MergeMode::KeepBoth));
+            assert_eq!(both.len(), 3);
+            assert_ne!(both[2].id, bank.id);
+        }
+    }
+
+    mod vault_merge_tests {
+        use super::{date, login};
+        use crate::attachment::DecryptedAttachment;
+        use crate::password_entry::DecryptedPasswordEntry;
+        use crate::storage::ApplicationData;
+        use crate::vault_merge::{merge_decrypted_entries, merge_vault_files, merge_vaults, MergeSide};
+
+        fn vault(password: &[u8], entries: &[DecryptedPasswordEntry]) -> ApplicationData {
+            let mut app_data = ApplicationData::new();
+            app_data.add_master_password_data(password).unwrap();
+            let mek = app_data.decrypt_mek_data(password).unwrap();
+            for entry in entries {
+                app_data.password_entries.push(entry.convert_to_encrypted(&mek).unwrap());
+            }
+            app_data.update_hashes().unwrap();
+            app_data
+        }
+
+        #[test]
+        fn test_merge_decrypted_by_modification_time() {
+            let shared = login("Shared", "me", Some("same"), None);
+            let bank = login("Bank", "me", Some("old"), None);
+            let mut mail = login("Mail", "me", Some("ours"), None);
+            mail.modified = Some(date("2024-03-01 12:00:00.5 UTC"));
+
+            let mut newer_bank = bank.clone();
+            newer_bank.password = Some("new".to_string());
+            newer_bank.modified = Some(date("2024-02-01 00:00:00 UTC"));
+            let mut older_mail = mail.clone();
+            older_mail.password = Some("theirs".to_string());
+            older_mail.modified = Some(date("2024-03-01 12:00:00.25 UTC"));
+            let only_theirs = login("Shop", "me", Some("s"), None);
+
+            let mut ours = vec![shared.clone(), bank.clone(), mail.clone()];
+            let theirs = vec![shared, newer_bank, older_mail, only_theirs.clone()];
+            let report = merge_decrypted_entries(&mut ours, theirs);
+
+            assert_eq!((report.added, report.updated, report.unchanged), (1, 1, 2));
+            assert_eq!(report.conflicts.len(), 2);
+            assert_eq!(report.conflicts[0].id, bank.id);
+            assert_eq!(report.conflicts[0].kept, MergeSide::Theirs);
+            assert_eq!(report.conflicts[1].id, mail.id);
+            assert_eq!(report.conflicts[1].kept, MergeSide::Ours);
+
+            assert_eq!(ours.len(), 4);
+            assert_eq!(ours[1].password.as_deref(), Some("new"));
+            assert_eq!(ours[2].password.as_deref(), Some("ours"));
+            assert_eq!(ours[3].id, only_theirs.id);
+        }
+
+        #[test]
+        fn test_merge_leaves_out_their_attachments() {
+            let attachment = |id: &str, name: &str| DecryptedAttachment {
+                id: id.to_string(),
+                name: name.to_string(),
+                size: 10,
+                added: date("2024-01-01 00:00:00 UTC"),
+            };
+            let mut bank = login("Bank", "me", Some("b"), None);
+            bank.attachments.push(attachment("6f1c1b8e-4d3a-4f2e-9b7a-2f8d5c3e1a90", "ours.pdf"));
+            let mut newer_bank = bank.clone();
+            newer_bank.modified = Some(date("2024-02-01 00:00:00 UTC"));
+            newer_bank.attachments.push(attachment("0b6f2a44-6a8e-4f3c-8d51-7c2e9a1b3d40", "theirs.pdf"));
+            let mut shop = login("Shop", "me", Some("s"), None);
+            shop.attachments.push(attachment("9d2e4c61-1f7b-4a0e-b3c8-5e6f7a8b9c01", "receipt.pdf"));
+
+            let mut ours = vec![bank.clone()];
+            let report = merge_decrypted_entries(&mut ours, vec![newer_bank, shop.clone()]);
+
+            assert_eq!((report.added, report.updated), (1, 1));
+            assert_eq!(ours[0].attachments, bank.attachments);
+            assert!(ours[1].attachments.is_empty());
+            let left_out: Vec<(&str, &str)> = report
+                .left_out_attachments
+                .iter()
+                .map(|a| (a.entry_title.as_str(), a.name.as_str()))
+                .collect();
+            assert_eq!(left_out, vec![("Bank", "theirs.pdf"), ("Shop", "receipt.pdf")]);
+            assert_eq!(report.left_out_attachments[1].entry_id, shop.id);
+        }
+
+        #[test]
+        fn test_merge_keeps_the_later_trash_state() {
+            let bank = login("Bank", "me", Some("b"), None);
+            let mail = login("Mail", "me", Some("m"), None);
+
+            let mut trashed_bank = bank.clone();
+            trashed_bank.deleted = Some(date("2024-02-01 00:00:00 UTC"));
+            trashed_bank.modified = trashed_bank.deleted;
+            let mut trashed_mail = mail.clone();
+            trashed_mail.deleted = Some(date("2024-02-01 00:00:00 UTC"));
+            trashed_mail.modified = trashed_mail.deleted;
+            let mut restored_mail = trashed_mail.clone();
+            restored_mail.deleted = None;
+            restored_mail.modified = Some(date("2024-03-01 00:00:00 UTC"));
+
+            let mut ours = vec![bank.clone(), trashed_mail];
+            let report = merge_decrypted_entries(&mut ours, vec![trashed_bank, restored_mail]);
+
+            assert_eq!((report.added, report.updated, report.unchanged), (0, 2, 0));
+            assert_eq!(ours[0].deleted, Some(date("2024-02-01 00:00:00 UTC")));
+            assert_eq!(ours[1].deleted, None);
+
+            let mut stale = vec![ours[0].clone()];
+            let report = merge_decrypted_entries(&mut stale, vec![bank]);
+            assert_eq!(report.conflicts[0].kept, MergeSide::Ours);
+            assert!(stale[0].deleted.is_some());
+        }
+
+        #[tokio::test]
+        async fn test_merge_vault_files() {
+            let bank = login("Bank", "me", Some("old"), None);
+            let mut newer_bank = bank.clone();
+            newer_bank.password = Some("new".to_string());
+            newer_bank.modified = Some(date("2024-02-01 00:00:00 UTC"));
+            let laptop = vault(b"laptop password", &[bank.clone(), login("Mail", "me", Some("m"), None)]);
+            let desktop = vault(b"desktop password", &[newer_bank, login("Shop", "me", Some("s"), None)]);
+            let mail_ciphertext = laptop.password_entries[1].password.as_ref().unwrap().encrypted_data.clone();
+
+            let dir = tempfile::tempdir().unwrap();
+            let (laptop_path, desktop_path) = (dir.path().join("laptop.json"), dir.path().join("desktop.json"));
+            laptop.save_to_file(&laptop_path).await.unwrap();
+            desktop.save_to_file(&desktop_path).await.unwrap();
+
+            assert!(merge_vault_files(&laptop_path, b"wrong", &desktop_path, b"desktop password", &laptop_path)
+                .await
+                .is_err());
+            let report = merge_vault_files(&laptop_path, b"laptop password", &desktop_path, b"desktop password", &laptop_path)
+                .await
+                .unwrap();
+            assert_eq!((report.added, report.updated, report.unchanged), (1, 1, 0));
+
+            let merged = ApplicationData::load_from_file(&laptop_path).await.unwrap().unwrap();
+            assert!(merged.verify_hashes());
+            assert!(merged.verify_master_password(b"laptop password"));
+            let mek = merged.decrypt_mek_data(b"laptop password").unwrap();
+            let entries: Vec<DecryptedPasswordEntry> =
+                merged.password_entries.iter().map(|e| e.to_decrypted(&mek).unwrap()).collect();
+            let titles: Vec<&str> = entries.iter().map(|e| e.title.as_str()).collect();
+            assert_eq!(titles, vec!["Bank", "Mail", "Shop"]);
+            assert_eq!(entries[0].password.as_deref(), Some("new"));
+            assert_eq!(entries[2].password.as_deref(), Some("s"));
+            // untouched entries aren't re-encrypted
+            assert_eq!(merged.password_entries[1].password.as_ref().unwrap().encrypted_data, mail_ciphertext);
+
+            let (_, report) = merge_vaults(merged, b"laptop password", &desktop, b"desktop password").unwrap();
+            assert_eq!((report.added, report.updated), (0, 0));
+        }
+    }
+
+    mod entry_kind_tests {
+        use crate::entry_kind::{BankAccountDetails, EntryDetails, EntryKind, IdentityDetails, PaymentCardDetails, SecureNoteDetails};
+        use crate::error_handling::CryptoError;
+        use crate::export::kdbx::export_kdbx_with_settings;
+        use crate::kdbx::reader::read_kdbx;
+        use crate::kdbx::Argon2Settings;
+        use crate::password_entry::{search_entries, DecryptedPasswordEntry};
+
+        const MEK: &[u8] = b"0123456789abcdef0123456789abcdef";
+
+        fn typed(title: &str, details: EntryDetails) -> DecryptedPasswordEntry {
+            DecryptedPasswordEntry::new(title.to_string(), None, None, None, None, None, None)
+                .unwrap()
+                .with_details(details)
+        }
+
+        fn card() -> DecryptedPasswordEntry {
+            typed(
+                "Visa",
+                EntryDetails::PaymentCard(PaymentCardDetails {
+                    cardholder_name: Some("Jane Doe".to_string()),
+                    brand: Some("Visa".to_string()),
+                    number: Some("4111 1111 1111 1234".to_string()),
+                    expiry_month: Some(7),
+                    expiry_year: Some(2029),
+                    cvv: Some("987".to_string()),
+                }),
+            )
+        }
+
+        #[test]
+        fn test_details_are_encrypted_and_restored() {
+            let card = card();
+            let encrypted = card.convert_to_encrypted(MEK).unwrap();
+            assert_eq!(encrypted.kind, EntryKind::PaymentCard);
+            let stored = serde_json::to_string(&encrypted).unwrap();
+            assert!(!stored.contains("1234") && !stored.contains("Jane"));
+
+            let decrypted = encrypted.to_decrypted(MEK).unwrap();
+            assert!(decrypted.same_content(&card));
+            assert_eq!(decrypted.details, card.details);
+
+            let mut mismatched = card.clone();
+            mismatched.kind = EntryKind::Identity;
+            assert!(matches!(mismatched.convert_to_encrypted(MEK), Err(CryptoError::InvalidInput)));
+
+            // entries written before kinds existed
+            let old: DecryptedPasswordEntry = serde_json::from_str(
+                r#"{"id":"1","title":"Bank","username":null,"password":null,"url":null,"notes":null,
+                    "creationDate":"2024-01-01 00:00:00 UTC","category":null,"favorite":false}"#,
+            )
+            .unwrap();
+            assert_eq!(old.kind, EntryKind::Login);
+            assert_eq!(old.details, None);
+        }
+
+        #[test]
+        fn test_type_aware_search() {
+            let mut login = DecryptedPasswordEntry::new(
+                "GitHub".to_string(),
+                Some("jane".to_string()),
+                Some("1234".to_string()),
+                Some("https://github.com".to_string()),
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+            login.notes = Some("work account".to_string());
+            let identity = typed(
+                "Me",
+                EntryDetails::Identity(IdentityDetails {
+                    full_name: Some("Jane Doe".to_string()),
+                    city: Some("Lisbon".to_string()),
+                    ..IdentityDetails::default()
+                }),
+            );
+            let account = typed(
+                "Savings",
+                EntryDetails::BankAccount(BankAccountDetails {
+                    bank_name: Some("First Bank".to_string()),
+                    account_number: Some("000123455678".to_string()),
+                    ..BankAccountDetails::default()
+                }),
+            );
+            let note = typed("Wi-Fi", EntryDetails::SecureNote(SecureNoteDetails { text: "guest network: pawpass".to_string() }));
+            let entries = vec![login, card(), identity, account, note];
+            let titles = |query: &str, kind: Option<EntryKind>| -> Vec<String> {
+                search_entries(&entries, query, kind).iter().map(|e| e.title.clone()).collect()
+            };
+
+            assert_eq!(titles("jane", None), vec!["GitHub", "Visa", "Me"]);
+            assert_eq!(titles("jane", Some(EntryKind::Identity)), vec!["Me"]);
+            assert_eq!(titles("lisbon", None), vec!["Me"]);
+            assert_eq!(titles("guest", None), vec!["Wi-Fi"]);
+            assert_eq!(titles("work account", None), vec!["GitHub"]);
+            // numbers match on their last four digits only, CVVs and passwords never
+            assert_eq!(titles("1234", None), vec!["Visa"]);
+            assert_eq!(titles("5678", None), vec!["Savings"]);
+            assert!(titles("4111", None).is_empty());
+            assert!(titles("987", None).is_empty());
+            assert_eq!(titles("", Some(EntryKind::BankAccount)), vec!["Savings"]);
+        }
+
+        #[test]
+        fn test_kdbx_export_keeps_details() {
+            let fast = Argon2Settings { memory_kib: 1024, iterations: 1, parallelism: 1 };
+            let exported = export_kdbx_with_settings(&[card()], b"pw", fast).unwrap();
+            let database = read_kdbx(&exported, b"pw").unwrap();
+            let fields: Vec<(&str, &str, bool)> = database.entries[0]
+                .custom_fields
+                .iter()
+                .map(|f| (f.key.as_str(), f.value.as_str(), f.protected))
+                .collect();
+            assert_eq!(
+                fields,
+                vec![
+                    ("Cardholder name", "Jane Doe", false),
+                    ("Brand", "Visa", false),
+                    ("Card number", "4111 1111 1111 1234", true),
+                    ("Expiry", "07/2029", false),
+                    ("CVV", "987", true),
+                ]
+            );
+        }
+    }
+
+    mod custom_field_tests {
+        use crate::custom_field::{CustomFieldType, DecryptedCustomField};
+        use crate::error_handling::CryptoError;
+        use crate::export::kdbx::export_kdbx_with_settings;
+        use crate::kdbx::reader::read_kdbx;
+        use crate::kdbx::Argon2Settings;
+        use crate::password_entry::{search_entries, DecryptedPasswordEntry};
+
+        const MEK: &[u8] = b"0123456789abcdef0123456789abcdef";
+
+        fn with_fields() -> DecryptedPasswordEntry {
+            let mut entry =
+                DecryptedPasswordEntry::new("AWS".to_string(), Some("root".to_string()), None, None, None, None, None)
+                    .unwrap();
+            entry.custom_fields = vec![
+                DecryptedCustomField::new("Access key", CustomFieldType::Text, "AKIAEXAMPLE"),
+                DecryptedCustomField::new("Secret key", CustomFieldType::Hidden, "wJalrXUtnFEMI"),
+                DecryptedCustomField::new("Console", CustomFieldType::Url, "https://console.aws.amazon.com"),
+                DecryptedCustomField::new("MFA enabled", CustomFieldType::Boolean, "true"),
+            ];
+            entry
+        }
+
+        #[test]
+        fn test_hidden_fields_are_encrypted_and_order_is_kept() {
+            let entry = with_fields();
+            let encrypted = entry.convert_to_encrypted(MEK).unwrap();
+            let stored = serde_json::to_string(&encrypted).unwrap();
+            assert!(stored.contains("AKIAEXAMPLE"));
+            assert!(!stored.contains("wJalrXUtnFEMI"));
+
+            let decrypted = encrypted.to_decrypted(MEK).unwrap();
+            assert_eq!(decrypted.custom_fields, entry.custom_fields);
+            assert!(decrypted.same_content(&entry));
+
+            let mut edited = entry.clone();
+            edited.custom_fields.swap(0, 1);
+            assert!(!edited.same_content(&entry));
+        }
+
+        #[test]
+        fn test_hidden_values_stay_with_their_entry_and_field() {
+            let mut entry = with_fields();
+            entry.custom_fields.push(DecryptedCustomField::new("Root password", CustomFieldType::Hidden, "hunter2"));
+            let encrypted = entry.convert_to_encrypted(MEK).unwrap();
+
+            let mut swapped = encrypted.clone();
+            swapped.custom_fields[1].protected_value = encrypted.custom_fields[4].protected_value.clone();
+            assert!(matches!(swapped.to_decrypted(MEK), Err(CryptoError::DecryptionFailed)));
+            let mut other = with_fields().convert_to_encrypted(MEK).unwrap();
+            other.custom_fields = encrypted.custom_fields.clone();
+            assert!(matches!(other.to_decrypted(MEK), Err(CryptoError::DecryptionFailed)));
+        }
+
+        #[test]
+        fn test_invalid_fields_are_rejected() {
+            let mut entry = with_fields();
+            entry.custom_fields[3].value = "yes".to_string();
+            assert!(matches!(entry.convert_to_encrypted(MEK), Err(CryptoError::InvalidInput)));
+
+            let mut entry = with_fields();
+            entry.custom_fields[0].name = "  ".to_string();
+            assert!(matches!(entry.convert_to_encrypted(MEK), Err(CryptoError::InvalidInput)));
+        }
+
+        #[test]
+        fn test_search_skips_hidden_values() {
+            let entries = vec![with_fields()];
+            assert_eq!(search_entries(&entries, "akiaexample", None).len(), 1);
+            assert_eq!(search_entries(&entries, "console.aws", None).len(), 1);
+            assert_eq!(search_entries(&entries, "secret key", None).len(), 1);
+            assert!(search_entries(&entries, "wJalr", None).is_empty());
+        }
+
+        #[test]
+        fn test_kdbx_export_protects_hidden_fields() {
+            let mut entry = with_fields();
+            entry.custom_fields.push(DecryptedCustomField::new("Password", CustomFieldType::Hidden, "old"));
+            entry.custom_fields.push(DecryptedCustomField::new("Access key", CustomFieldType::Text, "second"));
+            let fast = Argon2Settings { memory_kib: 1024, iterations: 1, parallelism: 1 };
+            let exported = export_kdbx_with_settings(&[entry], b"pw", fast).unwrap();
+            let database = read_kdbx(&exported, b"pw").unwrap();
+            let fields: Vec<(&str, &str, bool)> = database.entries[0]
+                .custom_fields
+                .iter()
+                .map(|f| (f.key.as_str(), f.value.as_str(), f.protected))
+                .collect();
+            assert_eq!(
+                fields,
+                vec![
+                    ("Access key", "AKIAEXAMPLE", false),
+                    ("Secret key", "wJalrXUtnFEMI", true),
+                    ("Console", "https://console.aws.amazon.com", false),
+                    ("MFA enabled", "true", false),
+                    ("Password (2)", "old", true),
+                    ("Access key (2)", "second", false),
+                ]
+            );
+        }
+    }
+
+    mod attachment_tests {
+        use crate::attachment::{
+            add_attachment, decrypt_attachment, delete_attachment, encrypt_attachment, export_attachment,
+            ATTACHMENTS_DIR, MAX_ATTACHMENT_SIZE,
+        };
+        use crate::error_handling::CryptoError;
+        use crate::password_entry::{DecryptedPasswordEntry, PasswordEntry};
+        use crate::storage::ApplicationData;
+        use std::io::Read;
+
+        const MEK: &[u8] = b"0123456789abcdef0123456789abcdef";
+        const ID: &str = "6f1c1b8e-4d3a-4f2e-9b7a-2f8d5c3e1a90";
+
+        fn sample(len: usize) -> Vec<u8> {
+            (0..len).map(|i| (i * 31 % 251) as u8).collect()
+        }
+
+        fn encrypt(data: &[u8]) -> Vec<u8> {
+            let mut blob: Vec<u8> = Vec::new();
+            assert_eq!(encrypt_attachment(data, &mut blob, ID, MEK).unwrap(), data.len() as u64);
+            blob
+        }
+
+        fn decrypt(blob: &[u8], id: &str, mek: &[u8]) -> Result<Vec<u8>, CryptoError> {
+            let mut data: Vec<u8> = Vec::new();
+            decrypt_attachment(blob, &mut data, id, mek)?;
+            Ok(data)
+        }
+
+        #[test]
+        fn test_stream_round_trip_across_chunk_boundaries() {
+            // empty, exactly one chunk, and several chunks with a partial last one
+            for len in [0, 64 * 1024, 200_000] {
+                let data = sample(len);
+                let blob = encrypt(&data);
+                assert_eq!(decrypt(&blob, ID, MEK).unwrap(), data);
+            }
+        }
+
+        #[test]
+        fn test_tampered_or_misplaced_blobs_fail() {
+            let blob = encrypt(&sample(200_000));
+            assert!(matches!(decrypt(&blob, ID, b"another key, 32 bytes long......"), Err(CryptoError::DecryptionFailed)));
+            assert!(matches!(decrypt(&blob, "another-id", MEK), Err(CryptoError::DecryptionFailed)));
+
+            let mut flipped = blob.clone();
+            flipped[1000] ^= 1;
+            assert!(matches!(decrypt(&flipped, ID, MEK), Err(CryptoError::DecryptionFailed)));
+
+            // dropping whole chunks off the end is caught by the last-chunk flag
+            let header = 8 + 32;
+            let truncated = &blob[..header + 2 * (64 * 1024 + 16)];
+            assert!(matches!(decrypt(truncated, ID, MEK), Err(CryptoError::DecryptionFailed)));
+            assert!(matches!(decrypt(b"not a blob", ID, MEK), Err(CryptoError::InvalidInput)));
+        }
+
+        #[test]
+        fn test_size_limit() {
+            let too_large = std::io::repeat(0).take(MAX_ATTACHMENT_SIZE + 1);
+            assert!(matches!(
+                encrypt_attachment(too_large, std::io::sink(), ID, MEK),
+                Err(CryptoError::AttachmentTooLarge)
+            ));
+        }
+
+        #[test]
+        fn test_add_export_and_delete_files() {
+            let dir = tempfile::tempdir().unwrap();
+            let attachments = dir.path().join("attachments");
+            let source = dir.path().join("recovery-codes.txt");
+            std::fs::write(&source, b"1234-5678\n8765-4321\n").unwrap();
+
+            let attachment = add_attachment(&attachments, &source, MEK).unwrap();
+            assert_eq!(attachment.name, "recovery-codes.txt");
+            assert_eq!(attachment.size, 20);
+            let blobs: Vec<_> = std::fs::read_dir(&attachments).unwrap().map(|e| e.unwrap().path()).collect();
+            assert_eq!(blobs.len(), 1);
+            let blob = std::fs::read(&blobs[0]).unwrap();
+            assert!(!blob.windows(9).any(|window| window == b"1234-5678"));
+
+            let exported = dir.path().join("exported.txt");
+            export_attachment(&attachments, &attachment.id, &exported, MEK).unwrap();
+            assert_eq!(std::fs::read(&exported).unwrap(), b"1234-5678\n8765-4321\n");
+
+            // a failed export leaves nothing behind
+            let failed = dir.path().join("failed.txt");
+            assert!(export_attachment(&attachments, &attachment.id, &failed, b"another key, 32 bytes long......").is_err());
+            assert!(!failed.exists());
+
+            // the name is encrypted along with the entry
+            let mut entry = DecryptedPasswordEntry::default();
+            entry.attachments.push(attachment.clone());
+            let encrypted = entry.convert_to_encrypted(MEK).unwrap();
+            assert!(!serde_json::to_string(&encrypted).unwrap().contains("recovery-codes"));
+            assert_eq!(encrypted.to_decrypted(MEK).unwrap().attachments, vec![attachment.clone()]);
+
+            delete_attachment(&attachments, &attachment.id).unwrap();
+            assert!(std::fs::read_dir(&attachments).unwrap().next().is_none());
+            delete_attachment(&attachments, &attachment.id).unwrap();
+            assert!(matches!(
+                export_attachment(&attachments, &attachment.id, &exported, MEK),
+                Err(CryptoError::AttachmentNotFound)
+            ));
+            assert!(matches!(delete_attachment(&attachments, "../vault"), Err(CryptoError::InvalidInput)));
+        }
+
+        #[test]
+        fn test_hiding_a_vault_leaves_its_blobs_in_place() {
+            let dir = tempfile::tempdir().unwrap();
+            let attachments = dir.path().join(ATTACHMENTS_DIR);
+            let source = dir.path().join("scan.pdf");
+            std::fs::write(&source, sample(1000)).unwrap();
+
+            let mut app_data = ApplicationData::new();
+            app_data.add_master_password_data(b"real_password").unwrap();
+            let mek = app_data.decrypt_mek_data(b"real_password").unwrap();
+            let added = add_attachment(&attachments, &source, &mek).unwrap();
+            let mut entry = PasswordEntry::default();
+            entry.attachments.push(added.encrypt(&mek).unwrap());
+            app_data.add_password_entry(entry).unwrap();
+            app_data.enable_duress_password(b"real_password", b"duress_password").unwrap();
+
+            // the decoy references no blobs, and the hidden vault's one is where it was
+            assert!(app_data.attachment_ids().is_empty());
+            let (real, _) = app_data.open_hidden_vault(b"real_password").unwrap().unwrap();
+            assert_eq!(real.attachment_ids(), vec![added.id.clone()]);
+            assert_eq!(std::fs::read_dir(&attachments).unwrap().count(), 1);
+            let exported = dir.path().join("exported.pdf");
+            export_attachment(&attachments, &added.id, &exported, &mek).unwrap();
+            assert_eq!(std::fs::read(&exported).unwrap(), sample(1000));
+        }
+    }
+
+    mod password_history_tests {
+        use crate::error_handling::CryptoError;
+        use crate::password_entry::{DecryptedPasswordEntry, PasswordEntry};
+        use crate::password_history::PASSWORD_HISTORY_LIMIT;
+        use crate::storage::ApplicationData;
+
+        const MEK: &[u8] = b"0123456789abcdef0123456789abcdef";
+
+        fn with_password(entry: &DecryptedPasswordEntry, password: &str) -> DecryptedPasswordEntry {
+            let mut edited = entry.clone();
+            edited.password = Some(password.to_string());
+            edited.keep_history_from(entry);
+            edited
+        }
+
+        fn history(entry: &DecryptedPasswordEntry) -> Vec<&str> {
+            entry.password_history.iter().map(|item| item.password.as_str()).collect()
+        }
+
+        #[test]
+        fn test_edits_keep_previous_passwords_newest_first() {
+            let mut entry = DecryptedPasswordEntry::default();
+            entry.password = Some("first".to_string());
+            let entry = with_password(&entry, "second");
+            let mut entry = with_password(&entry, "third");
+            assert_eq!(history(&entry), vec!["second", "first"]);
+
+            // the frontend doesn't get to rewrite history, and unchanged passwords aren't recorded
+            let mut renamed = entry.clone();
+            renamed.title = "Renamed".to_string();
+            renamed.password_history.clear();
+            renamed.keep_history_from(&entry);
+            assert_eq!(history(&renamed), vec!["second", "first"]);
+
+            for n in 0..PASSWORD_HISTORY_LIMIT {
+                entry = with_password(&entry, &format!("password {}", n));
+            }
+            assert_eq!(entry.password_history.len(), PASSWORD_HISTORY_LIMIT);
+            assert_eq!(entry.password_history[0].password, format!("password {}", PASSWORD_HISTORY_LIMIT - 2));
+            assert!(!history(&entry).contains(&"first"));
+        }
+
+        #[test]
+        fn test_restore_password() {
+            let mut entry = DecryptedPasswordEntry::default();
+            entry.password = Some("first".to_string());
+            let mut entry = with_password(&entry, "second");
+
+            entry.restore_password(0).unwrap();
+            assert_eq!(entry.password.as_deref(), Some("first"));
+            assert_eq!(history(&entry), vec!["second"]);
+            assert!(entry.modified.is_some());
+            assert!(entry.password_changed.is_some());
+            assert!(matches!(entry.restore_password(1), Err(CryptoError::InvalidInput)));
+        }
+
+        #[test]
+        fn test_history_is_encrypted_with_the_entry() {
+            let mut entry = DecryptedPasswordEntry::default();
+            assert!(entry.convert_to_encrypted(MEK).unwrap().password_history.is_none());
+            entry.password = Some("old secret".to_string());
+            let entry = with_password(&entry, "new secret");
+
+            let encrypted = entry.convert_to_encrypted(MEK).unwrap();
+            assert!(!serde_json::to_string(&encrypted).unwrap().contains("old secret"));
+            let decrypted = encrypted.to_decrypted(MEK).unwrap();
+            assert_eq!(decrypted.password_history, entry.password_history);
+
+            // replacing the password on the stored entry works without decrypting the rest of it
+            let replaced = encrypted.add_password(b"newest secret", MEK).unwrap().to_decrypted(MEK).unwrap();
+            assert_eq!(history(&replaced), vec!["new secret", "old secret"]);
+        }
+
+        #[test]
+        fn test_storage_update_records_changed_passwords_only() {
+            let mut app_data = ApplicationData::new();
+            let entry = PasswordEntry::new("Bank".into(), None, Some(b"first"), None, None, None, None, Some(MEK)).unwrap();
+            app_data.add_password_entry(entry.clone()).unwrap();
+
+            // same password, encrypted again: nothing to record
+            let mut same = entry.to_decrypted(MEK).unwrap();
+            same.title = "Bank (old)".into();
+            app_data.update_password_entry(&same.convert_to_encrypted(MEK).unwrap(), MEK).unwrap();
+            assert!(app_data.password_entries[0].password_history.is_none());
+
+            let mut changed = entry.to_decrypted(MEK).unwrap();
+            changed.password = Some("second".into());
+            app_data.update_password_entry(&changed.convert_to_encrypted(MEK).unwrap(), MEK).unwrap();
+            let stored = app_data.password_entries[0].to_decrypted(MEK).unwrap();
+            assert_eq!(stored.password.as_deref(), Some("second"));
+            assert_eq!(history(&stored), vec!["first"]);
+            assert!(app_data.verify_hashes());
+        }
+    }
+
+    mod entry_date_tests {
+        use super::date;
+        use crate::entry_date::parse_entry_date;
+        use crate::hidden_vault::HiddenVault;
+        use crate::password_entry::{DecryptedPasswordEntry, PasswordEntry};
+        use crate::storage::ApplicationData;
+        use chrono::{DateTime, Utc};
+        use serde_json::json;
+
+        const MEK: &[u8] = b"0123456789abcdef0123456789abcdef";
+
+        #[test]
+        fn test_parse_legacy_and_rfc3339_dates() {
+            let expected = DateTime::from_timestamp_micros(1_709_288_100_123_456).unwrap();
+            assert_eq!(parse_entry_date("2024-03-01 10:15:00.123456 UTC"), Some(expected));
+            assert_eq!(parse_entry_date("2024-03-01T10:15:00.123456Z"), Some(expected));
+            assert_eq!(parse_entry_date("2024-03-01T11:15:00.123456+01:00"), Some(expected));
+            assert_eq!(parse_entry_date(""), None);
+            assert_eq!(parse_entry_date("yesterday"), None);
+        }
+
+        #[test]
+        fn test_legacy_entry_dates_deserialize() {
+            let entry: DecryptedPasswordEntry = serde_json::from_value(json!({
+                "id": "1", "title": "Old", "username": null, "password": null, "url": null, "notes": null,
+                "creationDate": "2024-01-01 00:00:00 UTC", "lastModified": "2024-02-01 08:30:00.5 UTC",
+                "category": null, "favorite": false
+            }))
+            .unwrap();
+            assert_eq!(entry.created, date("2024-01-01T00:00:00Z"));
+            assert_eq!(entry.modified, Some(date("2024-02-01T08:30:00.5Z")));
+            assert_eq!((entry.password_changed, entry.last_used), (None, None));
+
+            // unreadable dates from old imports don't stop a vault from loading
+            let entry: DecryptedPasswordEntry = serde_json::from_value(json!({
+                "id": "2", "title": "Imported", "username": null, "password": null, "url": null, "notes": null,
+                "creationDate": "", "lastModified": "not a date", "category": null, "favorite": false
+            }))
+            .unwrap();
+            assert_eq!(entry.created, DateTime::UNIX_EPOCH);
+            assert_eq!(entry.modified, None);
+
+            let written = serde_json::to_value(&entry).unwrap();
+            assert_eq!(written["created"], json!("1970-01-01T00:00:00Z"));
+            assert!(written.get("creationDate").is_none());
+        }
+
+        #[test]
+        fn test_session_edits_keep_backend_dates() {
+            let mut stored = DecryptedPasswordEntry::default();
+            stored.created = date("2024-01-01T00:00:00Z");
+            stored.password = Some("first".to_string());
+            stored.mark_used();
+
+            // the frontend can't move the dates; an edit that leaves the password alone keeps its date
+            let mut renamed = stored.clone();
+            renamed.title = "Renamed".to_string();
+            renamed.created = Utc::now();
+            renamed.last_used = None;
+            renamed.keep_dates_from(&stored);
+            assert_eq!(renamed.created, stored.created);
+            assert_eq!(renamed.last_used, stored.last_used);
+            assert_eq!(renamed.password_changed, None);
+
+            let mut changed = renamed.clone();
+            changed.password = Some("second".to_string());
+            changed.keep_dates_from(&renamed);
+            assert!(changed.password_changed.is_some());
+            assert_eq!(changed.created, stored.created);
+        }
+
+        #[test]
+        fn test_storage_update_sets_dates() {
+            let mut app_data = ApplicationData::new();
+            let mut entry = PasswordEntry::new("Bank".into(), None, Some(b"first"), None, None, None, None, Some(MEK)).unwrap();
+            entry.created = date("2024-01-01T00:00:00Z");
+            app_data.add_password_entry(entry.clone()).unwrap();
+
+            let mut renamed = entry.to_decrypted(MEK).unwrap();
+            renamed.title = "Bank (old)".into();
+            renamed.created = Utc::now();
+            app_data.update_password_entry(&renamed.convert_to_encrypted(MEK).unwrap(), MEK).unwrap();
+            let stored = &app_data.password_entries[0];
+            assert_eq!(stored.created, entry.created);
+            assert!(stored.modified.is_some());
+            assert_eq!(stored.password_changed, None);
+
+            let replaced = app_data.password_entries[0].add_password(b"second", MEK).unwrap();
+            assert!(replaced.password_changed.is_some());
+            assert_eq!(replaced.modified, replaced.password_changed);
+        }
+
+        #[test]
+        fn test_hidden_vault_with_legacy_dates_opens() {
+            let mut app_data = ApplicationData::new();
+            app_data.add_master_password_data(b"real_password").unwrap();
+            app_data.add_password_entry(PasswordEntry::default()).unwrap();
+            app_data.enable_duress_password(b"real_password", b"duress_password").unwrap();
+            let (real, key) = app_data.open_hidden_vault(b"real_password").unwrap().unwrap();
+
+            // reseal the real vault the way an older version wrote it
+            let mut value = serde_json::to_value(&real).unwrap();
+            let old_entry = value["password_entries"][0].as_object_mut().unwrap();
+            for field in ["created", "modified", "passwordChanged", "lastUsed"] {
+                old_entry.remove(field);
+            }
+            old_entry.insert("creationDate".to_string(), json!("2024-01-01 00:00:00 UTC"));
+            for section in ["master_password_data", "key_slots", "password_entries", "hidden_vault"] {
+                let serialized = serde_json::to_string(&value[section]).unwrap();
+                value[format!("{}_hash", section)] = json!(ApplicationData::generate_hash(serialized.as_bytes()));
+            }
+            app_data.hidden_vault = Some(HiddenVault::seal(&serde_json::to_vec(&value).unwrap(), &key).unwrap());
+            app_data.update_hashes().unwrap();
+
+            let (reopened, _) = app_data.open_hidden_vault(b"real_password").unwrap().unwrap();
+            assert!(reopened.verify_hashes());
+            assert!(reopened.hidden_vault.is_none());
+            assert_eq!(reopened.password_entries[0].created, date("2024-01-01T00:00:00Z"));
+        }
+    }
+
+    mod trash_tests {
+        use super::titled;
+        use crate::config::UserSettings;
+        use crate::error_handling::CryptoError;
+        use crate::export::plaintext::{write_plaintext, PlaintextFormat};
+        use crate::password_entry::{search_entries, DecryptedPasswordEntry};
+        use crate::storage::ApplicationData;
+        use crate::trash::{is_expired, take_expired, DEFAULT_TRASH_RETENTION_DAYS};
+        use chrono::{Duration, Utc};
+
+        #[test]
+        fn test_expiry_uses_whole_retention_period() {
+            let now = Utc::now();
+            assert!(!is_expired(None, 0, now));
+            assert!(is_expired(Some(now), 0, now));
+            assert!(!is_expired(Some(now - Duration::days(30) + Duration::seconds(1)), 30, now));
+            assert!(is_expired(Some(now - Duration::days(30)), 30, now));
+
+            let mut deleted = vec![Some(now - Duration::days(2)), None, Some(now)];
+            let expired = take_expired(&mut deleted, |deleted| *deleted, 1, now);
+            assert_eq!(expired, vec![Some(now - Duration::days(2))]);
+            assert_eq!(deleted, vec![None, Some(now)]);
+        }
+
+        #[test]
+        fn test_storage_trash_restore_and_purge() {
+            let mut app_data = ApplicationData::new();
+            let (bank, mail) = (titled("Bank"), titled("Mail"));
+            app_data.add_password_entry(bank.clone()).unwrap();
+            app_data.add_password_entry(mail.clone()).unwrap();
+
+            app_data.trash_password_entry(&bank.id).unwrap();
+            assert!(app_data.verify_hashes());
+            assert!(app_data.search_password_entries("Bank").is_empty());
+            assert_eq!(app_data.trashed_entries().count(), 1);
+            assert!(matches!(app_data.trash_password_entry(&bank.id), Err(CryptoError::PasswordEntryNotFound)));
+            assert!(matches!(app_data.restore_password_entry(&mail.id), Err(CryptoError::PasswordEntryNotFound)));
+
+            app_data.restore_password_entry(&bank.id).unwrap();
+            assert_eq!(app_data.search_password_entries("Bank").len(), 1);
+            assert!(app_data.password_entries[0].modified.is_some());
+
+            // nothing is purged before the retention period is up
+            app_data.trash_password_entry(&bank.id).unwrap();
+            assert!(app_data.purge_trash().unwrap().is_empty());
+            let later = Utc::now() + Duration::days(DEFAULT_TRASH_RETENTION_DAYS.into());
+            let purged = app_data.purge_trash_at(later).unwrap();
+            assert_eq!(purged.len(), 1);
+            assert_eq!(purged[0].id, bank.id);
+            assert_eq!(app_data.password_entries.len(), 1);
+            assert!(app_data.verify_hashes());
+
+            app_data.user_settings.trash_retention_days = 0;
+            app_data.trash_password_entry(&mail.id).unwrap();
+            assert_eq!(app_data.purge_trash().unwrap().len(), 1);
+            assert!(app_data.password_entries.is_empty());
+        }
+
+        #[test]
+        fn test_settings_from_before_the_trash_get_default_retention() {
+            let mut value = serde_json::to_value(UserSettings::default()).unwrap();
+            value.as_object_mut().unwrap().remove("trashRetentionDays");
+            let settings: UserSettings = serde_json::from_value(value).unwrap();
+            assert_eq!(settings.trash_retention_days, DEFAULT_TRASH_RETENTION_DAYS);
+        }
+
+        #[test]
+        fn test_trashed_entries_are_not_searched_or_exported() {
+            let mut trashed = DecryptedPasswordEntry::default();
+            trashed.title = "Old bank".to_string();
+            trashed.move_to_trash();
+            assert!(trashed.is_trashed() && trashed.modified.is_some());
+            let mut kept = DecryptedPasswordEntry::default();
+            kept.title = "New bank".to_string();
+            let entries = vec![trashed.clone(), kept.clone()];
+
+            let found = search_entries(&entries, "bank", None);
+            assert_eq!(found.len(), 1);
+            assert_eq!(found[0].id, kept.id);
+
+            let mut json: Vec<u8> = Vec::new();
+            write_plaintext(&entries, PlaintextFormat::Json, &mut json).unwrap();
+            let exported: Vec<DecryptedPasswordEntry> = serde_json::from_slice(&json).unwrap();
+            assert_eq!(exported.len(), 1);
+
+            // the frontend can't take an entry out of the trash by editing it
+            let mut edited = trashed.clone();
+            edited.deleted = None;
+            edited.keep_dates_from(&trashed);
+            assert!(edited.is_trashed());
+            trashed.restore_from_trash();
+            assert!(!trashed.is_trashed());
+        }
+    }
+
+    mod folder_tests {
+        use crate::error_handling::CryptoError;
+        use crate::folders::{
+            delete_folder, delete_tag, folder_tree, move_folder, normalize_folder, normalize_tags, rename_folder,
+            rename_tag, Folder, TagCount,
+        };
+        use crate::password_entry::{search_entries, DecryptedPasswordEntry, PasswordEntry};
+        use crate::storage::ApplicationData;
+
+        fn filed(title: &str, folder: Option<&str>, tags: &[&str]) -> PasswordEntry {
+            PasswordEntry {
+                title: title.to_string(),
+                folder: folder.map(str::to_string),
+                tags: tags.iter().map(|tag| tag.to_string()).collect(),
+                ..PasswordEntry::default()
+            }
+        }
+
+        fn folders_of(entries: &[PasswordEntry]) -> Vec<Option<&str>> {
+            entries.iter().map(|entry| entry.folder.as_deref()).collect()
+        }
+
+        #[test]
+        fn test_normalize_folder_and_tags() {
+            assert_eq!(normalize_folder(" Work//Servers/ ").as_deref(), Some("Work/Servers"));
+            assert_eq!(normalize_folder(" / "), None);
+            let tags: Vec<String> = [" dev", "", "work", "dev "].iter().map(|tag| tag.to_string()).collect();
+            assert_eq!(normalize_tags(&tags), vec!["dev", "work"]);
+        }
+
+        #[test]
+        fn test_folder_tree_includes_parents() {
+            let entries = vec![
+                filed("Prod", Some("Work/Servers"), &[]),
+                filed("Mail", Some("Work"), &[]),
+                filed("Bank", Some("Personal"), &[]),
+                filed("Loose", None, &[]),
+            ];
+            let leaf = |name: &str, path: &str| Folder {
+                name: name.to_string(),
+                path: path.to_string(),
+                entry_count: 1,
+                children: Vec::new(),
+            };
+            assert_eq!(
+                folder_tree(&entries),
+                vec![
+                    leaf("Personal", "Personal"),
+                    Folder {
+                        children: vec![leaf("Servers", "Work/Servers")],
+                        ..leaf("Work", "Work")
+                    },
+                ]
+            );
+            // a folder only exists through what's filed below it
+            assert_eq!(folder_tree(&entries[..1])[0].entry_count, 0);
+        }
+
+        #[test]
+        fn test_rename_move_and_delete_folders() {
+            let mut entries = vec![
+                filed("Prod", Some("Work/Servers"), &[]),
+                filed("Mail", Some("Work"), &[]),
+                filed("Network", Some("Workshop"), &[]),
+            ];
+
+            assert_eq!(rename_folder(&mut entries, "Work", "Job").unwrap(), 2);
+            assert_eq!(folders_of(&entries), vec![Some("Job/Servers"), Some("Job"), Some("Workshop")]);
+            assert!(entries[0].modified.is_some() && entries[2].modified.is_none());
+            assert!(matches!(rename_folder(&mut entries, "Job", "A/B"), Err(CryptoError::InvalidInput)));
+
+            assert_eq!(move_folder(&mut entries, "Job/Servers", Some("Workshop")).unwrap(), 1);
+            assert_eq!(folders_of(&entries), vec![Some("Workshop/Servers"), Some("Job"), Some("Workshop")]);
+            assert!(matches!(
+                move_folder(&mut entries, "Workshop", Some("Workshop/Servers")),
+                Err(CryptoError::InvalidInput)
+            ));
+            assert_eq!(move_folder(&mut entries, "Workshop/Servers", None).unwrap(), 1);
+            assert_eq!(folders_of(&entries), vec![Some("Servers"), Some("Job"), Some("Workshop")]);
+
+            // the contents move up a level rather than being deleted
+            move_folder(&mut entries, "Servers", Some("Job")).unwrap();
+            assert_eq!(delete_folder(&mut entries, "Job").unwrap(), 2);
+            assert_eq!(folders_of(&entries), vec![Some("Servers"), None, Some("Workshop")]);
+            assert_eq!(delete_folder(&mut entries, "Missing").unwrap(), 0);
+        }
+
+        #[test]
+        fn test_rename_and_delete_tags() {
+            let mut entries = vec![filed("A", None, &["dev", "work"]), filed("B", None, &["work"]), filed("C", None, &[])];
+
+            // renaming onto a tag the entry already has merges them
+            assert_eq!(rename_tag(&mut entries, "dev", "work").unwrap(), 1);
+            assert_eq!(entries[0].tags, vec!["work"]);
+            assert_eq!(rename_tag(&mut entries, "work", "job").unwrap(), 2);
+            assert_eq!(entries[1].tags, vec!["job"]);
+            assert!(matches!(rename_tag(&mut entries, "job", " "), Err(CryptoError::InvalidInput)));
+
+            assert_eq!(delete_tag(&mut entries, "job"), 2);
+            assert!(entries.iter().all(|entry| entry.tags.is_empty()));
+            assert!(entries[2].modified.is_none());
+        }
+
+        #[test]
+        fn test_storage_folder_operations_keep_hashes_valid() {
+            let mut app_data = ApplicationData::new();
+            let mut trashed = filed("Old", Some("Work"), &["dev"]);
+            trashed.deleted = Some(chrono::Utc::now());
+            app_data.add_password_entry(filed("Mail", Some("Work"), &["dev"])).unwrap();
+            app_data.add_password_entry(trashed).unwrap();
+
+            assert_eq!(app_data.folders()[0].entry_count, 1);
+            assert_eq!(app_data.tags(), vec![TagCount { tag: "dev".to_string(), entry_count: 1 }]);
+
+            // trashed entries follow their folder, so restoring puts them back next to the others
+            assert_eq!(app_data.rename_folder("Work", "Job").unwrap(), 2);
+            assert!(app_data.verify_hashes());
+            assert_eq!(app_data.delete_tag("dev").unwrap(), 2);
+            assert!(app_data.verify_hashes());
+            assert!(app_data.password_entries.iter().all(|entry| entry.folder.as_deref() == Some("Job")));
+        }
+
+        #[test]
+        fn test_old_categories_and_new_fields_in_entries() {
+            let json = r#"{"id":"1","title":"Mail","username":null,"password":null,"url":null,"notes":null,
+                "creationDate":"2024-01-01 00:00:00 UTC","category":"Work","favorite":false}"#;
+            let mut entry: DecryptedPasswordEntry = serde_json::from_str(json).unwrap();
+            assert!(entry.migrate_category());
+            assert_eq!(entry.folder.as_deref(), Some("Work"));
+            assert!(!entry.migrate_category());
+            assert!(!serde_json::to_string(&entry).unwrap().contains("category"));
+
+            entry.tags = vec!["Banking".to_string()];
+            let entries = vec![entry];
+            assert_eq!(search_entries(&entries, "work", None).len(), 1);
+            assert_eq!(search_entries(&entries, "bank", None).len(), 1);
+        }
+    }
+
+    mod url_match_tests {
+        use crate::password_entry::{DecryptedPasswordEntry, PasswordEntry};
+        use crate::storage::ApplicationData;
+        use crate::url_match::{EntryUrl, UrlMatch};
+
+        fn rule(url: &str, match_mode: UrlMatch) -> EntryUrl {
+            EntryUrl::with_match(url, match_mode)
+        }
+
+        #[test]
+        fn test_base_domain_and_host_rules() {
+            let base = rule("https://login.example.com/signin", UrlMatch::BaseDomain);
+            assert!(base.matches("https://www.example.com/account"));
+            assert!(base.matches("http://example.com"));
+            assert!(!base.matches("https://example.org"));
+            assert!(!base.matches("https://example.com.evil.net"));
+
+            // country suffixes like co.uk aren't the registered domain
+            let uk = rule("example.co.uk", UrlMatch::BaseDomain);
+            assert!(uk.matches("https://shop.example.co.uk/cart"));
+            assert!(!uk.matches("https://other.co.uk"));
+            assert!(rule("https://example.com.au", UrlMatch::BaseDomain).matches("https://my.example.com.au"));
+
+            // private suffixes from the public suffix list: each tenant is its own base domain
+            let pages = rule("https://alice.github.io/project", UrlMatch::BaseDomain);
+            assert!(pages.matches("https://alice.github.io/other"));
+            assert!(!pages.matches("https://evil.github.io"));
+            assert!(!rule("myapp.herokuapp.com", UrlMatch::BaseDomain).matches("https://evil.herokuapp.com"));
+            assert!(!rule("alice.blogspot.com", UrlMatch::BaseDomain).matches("https://bob.blogspot.com"));
+            assert!(!rule("github.io", UrlMatch::BaseDomain).matches("https://evil.github.io"));
+            assert!(rule("http://192.168.1.1/admin", UrlMatch::BaseDomain).matches("https://192.168.1.1"));
+            assert!(!rule("", UrlMatch::BaseDomain).matches("https://example.com"));
+
+            let host = rule("https://login.example.com", UrlMatch::Host);
+            assert!(host.matches("https://login.example.com/other/path"));
+            assert!(!host.matches("https://www.example.com"));
+            assert!(!host.matches("https://login.example.com:8443"));
+            assert!(rule("localhost:8080", UrlMatch::Host).matches("http://localhost:8080/"));
+        }
+
+        #[test]
+        fn test_string_and_regex_rules() {
+            let starts = rule("https://example.com/app/", UrlMatch::StartsWith);
+            assert!(starts.matches("https://example.com/app/settings"));
+            assert!(!starts.matches("https://example.com/other"));
+            // the origin has to be the same, not just the text
+            let site = rule("https://example.com", UrlMatch::StartsWith);
+            assert!(site.matches("https://example.com/login"));
+            assert!(!site.matches("https://example.com.evil.io/login"));
+            assert!(!site.matches("https://example.com@evil.io/"));
+            assert!(!site.matches("http://example.com/login"));
+
+            let exact = rule("https://example.com/login", UrlMatch::Exact);
+            assert!(exact.matches("https://example.com/login"));
+            assert!(!exact.matches("https://example.com/login?next=/"));
+
+            // the whole URL has to match, not just part of it
+            let regex = rule(r"https://(eu|us)\.example\.com/.*", UrlMatch::Regex);
+            assert!(regex.matches("https://eu.example.com/login"));
+            assert!(!regex.matches("https://evil.net/?https://eu.example.com/"));
+            assert!(!rule("(unclosed", UrlMatch::Regex).matches("(unclosed"));
+
+            assert!(!rule("https://example.com", UrlMatch::Never).matches("https://example.com"));
+        }
+
+        #[test]
+        fn test_entries_with_a_single_url_still_load() {
+            let json = r#"{"id":"1","title":"Mail","username":null,"password":null,"url":"https://mail.example.com",
+                "notes":null,"creationDate":"2024-01-01 00:00:00 UTC","favorite":false}"#;
+            let entry: DecryptedPasswordEntry = serde_json::from_str(json).unwrap();
+            assert_eq!(entry.urls, vec![EntryUrl::new("https://mail.example.com")]);
+
+            let without: DecryptedPasswordEntry = serde_json::from_str(&json.replace(r#""https://mail.example.com""#, "null")).unwrap();
+            assert!(without.urls.is_empty());
+
+            let serialized = serde_json::to_value(&entry).unwrap();
+            assert_eq!(serialized["urls"][0]["matchMode"], "baseDomain");
+            assert!(serialized.get("url").is_none());
+        }
+
+        #[test]
+        fn test_find_entries_for_url() {
+            let mut app_data = ApplicationData::new();
+            let mut shop = PasswordEntry {
+                title: "Shop".to_string(),
+                ..PasswordEntry::default()
+            };
+            shop.urls = vec![
+                rule("login.example.com", UrlMatch::BaseDomain),
+                rule("https://example.co.uk/", UrlMatch::StartsWith),
+            ];
+            let mut old = shop.clone();
+            old.id = "old".to_string();
+            old.deleted = Some(chrono::Utc::now());
+            let mut hidden = shop.clone();
+            hidden.id = "hidden".to_string();
+            hidden.urls = vec![rule("example.com", UrlMatch::Never)];
+            app_data.add_password_entry(shop.clone()).unwrap();
+            app_data.add_password_entry(old).unwrap();
+            app_data.add_password_entry(hidden).unwrap();
+
+            let ids = |page: &str| -> Vec<String> {
+                app_data.find_entries_for_url(page).into_iter().map(|e| e.id.clone()).collect()
+            };
+            assert_eq!(ids("https://www.example.com/"), vec![shop.id.clone()]);
+            assert_eq!(ids("https://example.co.uk/basket"), vec![shop.id.clone()]);
+            assert!(ids("https://www.example.co.uk/basket").is_empty());
+        }
+    }
+
+    mod expiry_tests {
+        use super::titled;
+        use crate::config::UserSettings;
+        use crate::error_handling::CryptoError;
+        use crate::expiry::{self, ExpiryStatus, DEFAULT_EXPIRY_WARNING_DAYS};
+        use crate::password_entry::PasswordEntry;
+        use crate::storage::ApplicationData;
+        use chrono::{DateTime, Duration, TimeZone, Utc};
+
+        const MEK: &[u8] = b"0123456789abcdef0123456789abcdef";
+
+        // Noon on the given day of January 2024, so january(1) is when `titled` entries were created
+        fn january(day: u32) -> DateTime<Utc> {
+            Utc.with_ymd_and_hms(2024, 1, day, 12, 0, 0).unwrap()
+        }
+
+        #[test]
+        fn test_due_date() {
+            assert_eq!(expiry::due_date(None, None, january(1)), None);
+            assert_eq!(expiry::due_date(Some(january(20)), None, january(1)), Some(january(20)));
+            assert_eq!(expiry::due_date(None, Some(10), january(1)), Some(january(11)));
+            // whichever comes first
+            assert_eq!(expiry::due_date(Some(january(20)), Some(10), january(1)), Some(january(11)));
+            assert_eq!(expiry::due_date(Some(january(5)), Some(10), january(1)), Some(january(5)));
+            assert_eq!(expiry::due_date(None, Some(0), january(1)), None);
+
+            assert_eq!(expiry::status(january(10), 14, january(10)), Some(ExpiryStatus::Expired));
+            assert_eq!(expiry::status(january(24), 14, january(10)), Some(ExpiryStatus::ExpiringSoon));
+            assert_eq!(expiry::status(january(25), 14, january(10)), None);
+        }
+
+        #[test]
+        fn test_reminders_skip_trash_and_sort_by_due_date() {
+            let mut app_data = ApplicationData::new();
+            let mut rotated = titled("Rotated");
+            rotated.rotation_days = Some(90);
+            rotated.password_changed = Some(january(1) - Duration::days(85));
+            let mut expired = titled("Expired");
+            expired.expires = Some(january(2));
+            let mut later = titled("Later");
+            later.expires = Some(january(31));
+            let mut trashed = expired.clone();
+            trashed.id = "Trashed".to_string();
+            trashed.deleted = Some(january(3));
+            for entry in [titled("Never"), rotated, expired, later, trashed] {
+                app_data.add_password_entry(entry).unwrap();
+            }
+
+            let reminders = app_data.expiring_entries_at(january(10));
+            let found: Vec<(&str, ExpiryStatus)> =
+                reminders.iter().map(|reminder| (reminder.id.as_str(), reminder.status)).collect();
+            assert_eq!(found, vec![("Expired", ExpiryStatus::Expired), ("Rotated", ExpiryStatus::Expired)]);
+            assert_eq!(reminders[1].due, january(6));
+
+            app_data.user_settings.expiry_warning_days = 30;
+            assert_eq!(app_data.expiring_entries_at(january(10)).len(), 3);
+        }
+
+        #[test]
+        fn test_changing_the_password_restarts_rotation() {
+            let mut app_data = ApplicationData::new();
+            let mut stored = PasswordEntry::new("Server".into(), None, Some(b"old"), None, None, None, None, Some(MEK)).unwrap();
+            stored.created = Utc::now() - Duration::days(100);
+            stored.rotation_days = Some(90);
+            app_data.add_password_entry(stored).unwrap();
+            assert_eq!(app_data.expiring_entries().len(), 1);
+
+            let mut changed = app_data.password_entries[0].to_decrypted(MEK).unwrap();
+            changed.password = Some("new".to_string());
+            app_data.update_password_entry(&changed.convert_to_encrypted(MEK).unwrap(), MEK).unwrap();
+            assert!(app_data.expiring_entries().is_empty());
+        }
+
+        #[test]
+        fn test_out_of_range_days_dont_panic() {
+            let latest = DateTime::<Utc>::MAX_UTC;
+            assert_eq!(expiry::due_date(None, Some(u32::MAX), january(1)), None);
+            assert_eq!(expiry::due_date(Some(january(20)), Some(1), latest), Some(january(20)));
+            assert_eq!(expiry::status(january(20), u32::MAX, january(10)), Some(ExpiryStatus::ExpiringSoon));
+            assert_eq!(expiry::status(latest, 1, latest - Duration::hours(1)), Some(ExpiryStatus::ExpiringSoon));
+
+            // values like these are refused when they're set, but a vault that has them still opens
+            let mut app_data = ApplicationData::new();
+            let mut entry = titled("Huge");
+            entry.rotation_days = Some(100_000_000);
+            assert!(matches!(app_data.add_password_entry(entry.clone()), Err(CryptoError::InvalidInput)));
+            let settings = UserSettings {
+                expiry_warning_days: 100_000_000,
+                ..UserSettings::default()
+            };
+            assert!(matches!(app_data.update_user_settings(settings.clone()), Err(CryptoError::InvalidInput)));
+
+            app_data.password_entries.push(entry);
+            app_data.user_settings = settings;
+            assert!(app_data.expiring_entries_at(january(10)).is_empty());
+        }
+
+        #[test]
+        fn test_settings_without_warning_days_use_the_default() {
+            let mut settings = serde_json::to_value(UserSettings::default()).unwrap();
+            settings.as_object_mut().unwrap().remove("expiryWarningDays");
+            let settings: UserSettings = serde_json::from_value(settings).unwrap();
+            assert_eq!(settings.expiry_warning_days, DEFAULT_EXPIRY_WARNING_DAYS);
+        }
+    }
+}
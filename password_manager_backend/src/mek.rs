@@ -6,11 +6,19 @@ use serde::{Deserialize, Serialize};
 use std::num::NonZeroU32;
 
 const KEY_SIZE: usize = 32; // aes-256-gcm key size
+pub const DEFAULT_KDF_ITERATIONS: u32 = 100_000;
+pub const MIN_KDF_ITERATIONS: u32 = 100_000; // new wrappings can't be made weaker than this
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct MekData {
     pub(crate) encrypted_mek: SecureData, // Contains the encrypted MEK, nonce, and salt for the MEK encryption
     pub(crate) mek_salt: Vec<u8>, // Salt used to derive the key for MEK encryption/decryption
+    #[serde(default = "default_kdf_iterations")]
+    pub(crate) kdf_iterations: u32, // PBKDF2 iterations for this wrapping; older files predate the field
+}
+
+fn default_kdf_iterations() -> u32 {
+    DEFAULT_KDF_ITERATIONS
 }
 
 impl MekData {
@@ -25,13 +33,37 @@ impl MekData {
         Ok(Self {
             encrypted_mek,
             mek_salt,
+            kdf_iterations: DEFAULT_KDF_ITERATIONS,
+        })
+    }
+
+    // Wraps an already existing MEK under a different secret (e.g. a recovery key) with its own salt
+    pub fn wrap_mek(secret: &[u8], mek: &[u8]) -> Result<Self, CryptoError> {
+        MekData::wrap_mek_with_iterations(secret, mek, DEFAULT_KDF_ITERATIONS)
+    }
+
+    pub fn wrap_mek_with_iterations(secret: &[u8], mek: &[u8], kdf_iterations: u32) -> Result<Self, CryptoError> {
+        if kdf_iterations < MIN_KDF_ITERATIONS {
+            return Err(CryptoError::InvalidInput);
+        }
+        let crypto_bub: Cryptographer = Cryptographer::new(None);
+        let mek_salt: Vec<u8> = Cryptographer::generate_salt()?;
+        let derived_key: [u8; KEY_SIZE] =
+            MekData::derive_mek_key_with_iterations(secret, &mek_salt, kdf_iterations)?;
+
+        let encrypted_mek: SecureData = Cryptographer::encrypt(&crypto_bub, mek, &derived_key)?;
+
+        Ok(Self {
+            encrypted_mek,
+            mek_salt,
+            kdf_iterations,
         })
     }
 
     // Decrypts the MEK and returns it
     pub fn decrypt_mek(&self, master_password: &[u8]) -> Result<Vec<u8>, CryptoError> {
         let cryptographer: Cryptographer = Cryptographer::new(None);
-        let derived_key: [u8; KEY_SIZE] = MekData::derive_mek_key(master_password, &self.mek_salt);
+        let derived_key: [u8; KEY_SIZE] = self.derive_key(master_password)?;
         let mek: Vec<u8> = cryptographer.decrypt(self.encrypted_mek.clone(), &derived_key)?;
         Ok(mek)
     }
@@ -39,11 +71,12 @@ impl MekData {
     // meant to encrypt the mek back using the same master key, not the same as update_mek
     pub fn reencrypt_mek(&self, master_password: &[u8], mek: &[u8]) -> Result<Self, CryptoError> {
         let cryptographer: Cryptographer = Cryptographer::new(None);
-        let derived_key: [u8; KEY_SIZE] = MekData::derive_mek_key(master_password, &self.mek_salt);
+        let derived_key: [u8; KEY_SIZE] = self.derive_key(master_password)?;
         let encrypted_mek: SecureData = cryptographer.encrypt(mek, &derived_key)?;
         Ok(MekData {
             encrypted_mek,
             mek_salt: self.mek_salt.clone(),
+            kdf_iterations: self.kdf_iterations,
         })
     }
 
@@ -51,11 +84,11 @@ impl MekData {
         let cryptographer: Cryptographer = Cryptographer::new(None);
         
         // Derive the old key and decrypt the existing MEK
-        let old_key: [u8; KEY_SIZE] = MekData::derive_mek_key(old_master_password, &self.mek_salt);
+        let old_key: [u8; KEY_SIZE] = self.derive_key(old_master_password)?;
         let mek: Vec<u8> = cryptographer.decrypt(self.encrypted_mek.clone(), &old_key)?;
     
         // Derive the new key from the new master password
-        let new_key: [u8; KEY_SIZE] = MekData::derive_mek_key(new_master_password, &self.mek_salt);
+        let new_key: [u8; KEY_SIZE] = self.derive_key(new_master_password)?;
     
         // Re-encrypt the MEK with the new key
         self.encrypted_mek = cryptographer.encrypt(&mek, &new_key)?;
@@ -65,14 +98,22 @@ impl MekData {
 
     // generate derived key from master password and salt
     pub fn derive_mek_key(master_password: &[u8], salt: &[u8]) -> [u8; KEY_SIZE] {
-        let mut key: [u8; KEY_SIZE] = [0u8; KEY_SIZE];
-        pbkdf2::derive(
-            pbkdf2::PBKDF2_HMAC_SHA256,
-            NonZeroU32::new(100_000).unwrap(),
-            salt,
-            master_password,
-            &mut key,
-        );
-        key
+        pbkdf2_key(master_password, salt, NonZeroU32::new(DEFAULT_KDF_ITERATIONS).expect("default iterations are non-zero"))
+    }
+
+    // A stored count of 0 is rejected rather than replaced, so a damaged slot fails to open
+    pub fn derive_mek_key_with_iterations(secret: &[u8], salt: &[u8], kdf_iterations: u32) -> Result<[u8; KEY_SIZE], CryptoError> {
+        let kdf_iterations: NonZeroU32 = NonZeroU32::new(kdf_iterations).ok_or(CryptoError::InvalidInput)?;
+        Ok(pbkdf2_key(secret, salt, kdf_iterations))
     }
-}
\ No newline at end of file
+
+    fn derive_key(&self, secret: &[u8]) -> Result<[u8; KEY_SIZE], CryptoError> {
+        MekData::derive_mek_key_with_iterations(secret, &self.mek_salt, self.kdf_iterations)
+    }
+}
+
+fn pbkdf2_key(secret: &[u8], salt: &[u8], iterations: NonZeroU32) -> [u8; KEY_SIZE] {
+    let mut key: [u8; KEY_SIZE] = [0u8; KEY_SIZE];
+    pbkdf2::derive(pbkdf2::PBKDF2_HMAC_SHA256, iterations, salt, secret, &mut key);
+    key
+}
@@ -0,0 +1,99 @@
+use crate::error_handling::CryptoError;
+use crate::hidden_vault::HiddenVault;
+use crate::key_slot::{KeySlot, KeySlotKind, MASTER_PASSWORD_LABEL};
+use crate::mek::MekData;
+use crate::storage::ApplicationData;
+use serde_json::value::RawValue;
+use std::collections::HashMap;
+
+const HASH_SUFFIX: &str = "_hash";
+
+// Every section that has ever been covered by an integrity hash, current or legacy
+const HASHED_SECTIONS: [&str; 5] = [
+    "master_password_data",
+    "mek_data",
+    "key_slots",
+    "password_entries",
+    "hidden_vault",
+];
+const REQUIRED_SECTIONS: [&str; 2] = ["master_password_data", "password_entries"];
+
+type RawSections = HashMap<String, Box<RawValue>>;
+
+// Brings a vault written by an older version up to the current layout. Older files can't pass
+// verify_hashes once their sections deserialize into newer shapes, so we check the stored hashes
+// against the raw JSON text instead (it was written by serde_json::to_string, byte for byte) and
+// only rehash when that holds. Tampered files keep their stale hashes and still fail verification.
+pub(crate) fn upgrade(app_data: &mut ApplicationData, content: &str) -> Result<(), CryptoError> {
+    upgrade_sections(app_data, content, true)
+}
+
+// The same for a vault sealed inside a hidden vault section, which never gets one of its own
+pub(crate) fn upgrade_hidden(app_data: &mut ApplicationData, content: &str) -> Result<(), CryptoError> {
+    upgrade_sections(app_data, content, false)
+}
+
+fn upgrade_sections(app_data: &mut ApplicationData, content: &str, add_hidden_vault: bool) -> Result<(), CryptoError> {
+    if app_data.verify_hashes() {
+        return Ok(());
+    }
+
+    let sections: RawSections = serde_json::from_str(content)?;
+    if !sections_intact(&sections) {
+        return Ok(());
+    }
+
+    if app_data.key_slots.is_empty() {
+        app_data.key_slots = legacy_key_slots(&sections)?;
+    }
+    // older files have no hidden vault section; they get filler so they look like every other vault
+    if add_hidden_vault && app_data.hidden_vault.is_none() && app_data.master_password_data.is_some() {
+        app_data.hidden_vault = Some(HiddenVault::filler()?);
+    }
+    // entries from before folders and tags are filed under their old category
+    for entry in app_data.password_entries.iter_mut() {
+        entry.migrate_category();
+    }
+    app_data.update_hashes()
+}
+
+fn sections_intact(sections: &RawSections) -> bool {
+    let has_key_material = sections.contains_key("mek_data") || sections.contains_key("key_slots");
+
+    has_key_material
+        && HASHED_SECTIONS.iter().all(|section| {
+            let raw = sections.get(*section);
+            let stored_hash = sections
+                .get(&format!("{}{}", section, HASH_SUFFIX))
+                .and_then(|hash| serde_json::from_str::<Option<String>>(hash.get()).ok().flatten());
+
+            match (raw, stored_hash) {
+                (None, None) => !REQUIRED_SECTIONS.contains(section),
+                (Some(raw), Some(stored_hash)) => {
+                    ApplicationData::generate_hash(raw.get().as_bytes()) == stored_hash
+                }
+                _ => false,
+            }
+        })
+}
+
+// The single `mek_data` becomes the master password slot
+fn legacy_key_slots(sections: &RawSections) -> Result<Vec<KeySlot>, CryptoError> {
+    let mut key_slots: Vec<KeySlot> = Vec::new();
+
+    if let Some(mek_data) = parse_legacy_mek_data(sections, "mek_data")? {
+        key_slots.push(KeySlot::new(
+            KeySlotKind::MasterPassword,
+            MASTER_PASSWORD_LABEL.to_string(),
+            mek_data,
+        ));
+    }
+    Ok(key_slots)
+}
+
+fn parse_legacy_mek_data(sections: &RawSections, section: &str) -> Result<Option<MekData>, CryptoError> {
+    match sections.get(section) {
+        Some(raw) => Ok(serde_json::from_str(raw.get())?),
+        None => Ok(None),
+    }
+}
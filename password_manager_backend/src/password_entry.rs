@@ -1,24 +1,102 @@
-use crate::crypto::{Cryptographer, SecureData};
+use crate::attachment::{Attachment, DecryptedAttachment, ATTACHMENT_NAME_FIELD};
+use crate::crypto::{Cryptographer, FieldKey, SealedData, SecureData};
+use crate::custom_field::{encrypt_custom_fields, CustomField, DecryptedCustomField};
+use crate::entry_date;
+use crate::entry_kind::{EntryDetails, EntryKind};
 use crate::error_handling::CryptoError;
-use chrono::Utc;
+use crate::expiry::{self, Expiring};
+use crate::folders::{migrate_category, normalize_folder, normalize_tags, Filed};
+use crate::password_history::{decrypt_history, encrypt_history, record_password, PasswordHistoryItem};
+use crate::url_match::{self, EntryUrl};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use zeroize::Zeroize;
+use zeroize::{Zeroize, Zeroizing};
 
+// Usernames and notes were stored in plaintext before they were encrypted. Vaults from then
+// keep the plain strings until they're unlocked and `encrypt_plaintext_fields` runs; new
+// values are always sealed with the field key.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum ProtectedText {
+    Sealed(SealedData),
+    Plain(String),
+}
+
+impl ProtectedText {
+    // `owner` is the id of the entry (or attachment) the text belongs to, see FieldKey
+    pub fn seal(text: &str, key: &FieldKey, owner: &str, field: &str) -> Result<Self, CryptoError> {
+        Ok(ProtectedText::Sealed(key.seal(text.as_bytes(), owner, field)?))
+    }
+
+    pub fn open(&self, key: &FieldKey, owner: &str, field: &str) -> Result<String, CryptoError> {
+        match self {
+            ProtectedText::Sealed(sealed) => String::from_utf8(key.open(sealed, owner, field)?).map_err(|e| {
+                let mut bytes: Vec<u8> = e.into_bytes();
+                bytes.zeroize();
+                CryptoError::InvalidInput
+            }),
+            ProtectedText::Plain(text) => Ok(text.clone()),
+        }
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        matches!(self, ProtectedText::Sealed(_))
+    }
+}
 
+const USERNAME_FIELD: &str = "username";
+const NOTES_FIELD: &str = "notes";
+
+fn encrypt_text(text: &Option<String>, key: &FieldKey, owner: &str, field: &str) -> Result<Option<ProtectedText>, CryptoError> {
+    text.as_deref().map(|text| ProtectedText::seal(text, key, owner, field)).transpose()
+}
+
+fn decrypt_text(text: &Option<ProtectedText>, key: &FieldKey, owner: &str, field: &str) -> Result<Option<String>, CryptoError> {
+    text.as_ref().map(|text| text.open(key, owner, field)).transpose()
+}
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct PasswordEntry {
     pub id: String,
     pub title: String,
-    pub username: Option<String>,
+    pub username: Option<ProtectedText>,
     pub password: Option<SecureData>,
-    pub url: Option<String>,
-    pub notes: Option<String>,
-    pub creation_date: String,
-    pub category: Option<String>,
+    #[serde(default, alias = "url", deserialize_with = "url_match::deserialize_urls")]
+    pub urls: Vec<EntryUrl>, // the first one is shown, and exported where there's room for only one
+    pub notes: Option<ProtectedText>,
+    #[serde(alias = "creationDate", deserialize_with = "entry_date::deserialize")]
+    pub created: DateTime<Utc>,
+    #[serde(default, alias = "lastModified", deserialize_with = "entry_date::deserialize_option")]
+    pub modified: Option<DateTime<Utc>>, // None until the entry is first edited
+    #[serde(default, deserialize_with = "entry_date::deserialize_option")]
+    pub password_changed: Option<DateTime<Utc>>, // None until the first password is replaced
+    #[serde(default, deserialize_with = "entry_date::deserialize_option")]
+    pub last_used: Option<DateTime<Utc>>, // when the password was last copied or filled
+    #[serde(default, deserialize_with = "entry_date::deserialize_option")]
+    pub deleted: Option<DateTime<Utc>>, // set while the entry is in the trash
+    #[serde(default, deserialize_with = "entry_date::deserialize_option")]
+    pub expires: Option<DateTime<Utc>>, // the password has to be changed by then, see expiry.rs
+    #[serde(default)]
+    pub rotation_days: Option<u32>, // or this many days after it was last changed
+    #[serde(default)]
+    pub folder: Option<String>, // a "/"-separated path like "Work/Servers", see folders.rs
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>, // only read from older vaults and exports, see migrate_category
     pub favorite: bool,
+    #[serde(default)]
+    pub kind: EntryKind,
+    #[serde(default)]
+    pub details: Option<SealedData>, // sealed EntryDetails, for every kind but logins
+    #[serde(default)]
+    pub custom_fields: Vec<CustomField>, // in the order the user added them
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+    #[serde(default)]
+    pub password_history: Option<SealedData>, // sealed Vec<PasswordHistoryItem>, None while empty
 }
 
 impl PasswordEntry {
@@ -28,63 +106,210 @@ impl PasswordEntry {
         password_: Option<&[u8]>,
         url_: Option<String>,
         notes_: Option<String>,
-        category_: Option<String>,
+        folder_: Option<String>,
         favorite_: Option<bool>,
         mek: Option<&[u8]>,
     ) -> Result<Self, CryptoError> {
         let crypto_bub: Cryptographer = Cryptographer::new(None);
+        let id: String = Uuid::new_v4().to_string();
 
-        let result = match (password_, mek) {
-            (Some(password_), Some(mek)) => {
-                Some(Cryptographer::encrypt(&crypto_bub, password_, mek)?)
+        // the password, username and notes can't be stored without a key to encrypt them with
+        let (result, username, notes) = match mek {
+            Some(mek) => {
+                let field_key: FieldKey = FieldKey::new(mek)?;
+                (
+                    password_.map(|password_| Cryptographer::encrypt(&crypto_bub, password_, mek)).transpose()?,
+                    encrypt_text(&username_, &field_key, &id, USERNAME_FIELD)?,
+                    encrypt_text(&notes_, &field_key, &id, NOTES_FIELD)?,
+                )
+            }
+            None if password_.is_some() || username_.is_some() || notes_.is_some() => {
+                return Err(CryptoError::MekDataNotPresent)
             }
-            _ => None,
+            None => (None, None, None),
         };
 
         Ok(Self {
-            id: Uuid::new_v4().to_string(),
+            id,
             title: title_,
-            username: username_,
+            username,
             password: result,
-            url: url_,
-            notes: notes_,
-            creation_date: Utc::now().to_string(),
-            category: category_,
+            urls: url_.as_deref().filter(|url| !url.trim().is_empty()).map(EntryUrl::new).into_iter().collect(),
+            notes,
+            created: Utc::now(),
+            modified: None,
+            password_changed: None,
+            last_used: None,
+            deleted: None,
+            expires: None,
+            rotation_days: None,
+            folder: folder_.as_deref().and_then(normalize_folder),
+            tags: Vec::new(),
+            category: None,
             favorite: favorite_.unwrap_or(false),
+            kind: EntryKind::Login,
+            details: None,
+            custom_fields: Vec::new(),
+            attachments: Vec::new(),
+            password_history: None,
         })
     }
 
+    // Appends fields to the ones the entry already has, encrypting the hidden ones
+    pub fn add_custom_fields(&mut self, fields: &[DecryptedCustomField], mek: &[u8]) -> Result<(), CryptoError> {
+        let field_key: FieldKey = FieldKey::new(mek)?;
+        self.custom_fields.extend(encrypt_custom_fields(fields, &field_key, &self.id)?);
+        Ok(())
+    }
+
+    pub fn touch(&mut self) {
+        self.modified = Some(Utc::now());
+    }
+
+    // See folders::migrate_category
+    pub fn migrate_category(&mut self) -> bool {
+        migrate_category(&mut self.category, &mut self.folder)
+    }
+
+    pub fn primary_url(&self) -> Option<&str> {
+        self.urls.first().map(|url| url.url.as_str())
+    }
+
+    // When the password is due for changing, if the entry has an expiry date or rotation interval
+    pub fn expiry_due(&self) -> Option<DateTime<Utc>> {
+        expiry::due_date(self.expires, self.rotation_days, self.password_changed.unwrap_or(self.created))
+    }
+
+    // Whether any of the entry's URLs matches the page, by its own rule
+    pub fn matches_url(&self, page_url: &str) -> bool {
+        url_match::any_matches(&self.urls, page_url)
+    }
+
     pub fn to_decrypted(&self, mek: &[u8]) -> Result<DecryptedPasswordEntry, CryptoError> {
         DecryptedPasswordEntry::new_from_password_entry(self.clone(), mek)
     }
 
-    pub fn display_name(&self) -> [String; 3] {
-        [
-            self.title.clone(),
-            self.username.as_deref().unwrap_or_default().to_string(),
-            self.url.as_deref().unwrap_or_default().to_string(),
-        ]
+    pub fn is_trashed(&self) -> bool {
+        self.deleted.is_some()
+    }
+
+    // Encrypts a username, notes or attachment name still stored in plaintext; true if anything changed
+    pub fn encrypt_plaintext_fields(&mut self, mek: &[u8]) -> Result<bool, CryptoError> {
+        let field_key: FieldKey = FieldKey::new(mek)?;
+        let mut changed: bool = false;
+        let fields = [(self.username.as_mut(), USERNAME_FIELD), (self.notes.as_mut(), NOTES_FIELD)]
+            .into_iter()
+            .filter_map(|(field, name)| field.map(|field| (field, self.id.as_str(), name)))
+            .chain(
+                self.attachments
+                    .iter_mut()
+                    .map(|attachment| (&mut attachment.name, attachment.id.as_str(), ATTACHMENT_NAME_FIELD)),
+            );
+        for (field, owner, name) in fields {
+            if let ProtectedText::Plain(text) = field {
+                let encrypted: ProtectedText = ProtectedText::seal(text, &field_key, owner, name)?;
+                text.zeroize();
+                *field = encrypted;
+                changed = true;
+            }
+        }
+        Ok(changed)
+    }
+
+    // History as it should be once the current password is replaced by `new_password`, and
+    // whether that replacement changes the password at all
+    pub fn history_after_replacing(&self, new_password: &[u8], mek: &[u8]) -> Result<(Option<SealedData>, bool), CryptoError> {
+        let current: Zeroizing<Vec<u8>> = match self.password {
+            Some(ref password) => Zeroizing::new(Cryptographer::new(None).decrypt(password.clone(), mek)?),
+            None => Zeroizing::new(Vec::new()),
+        };
+        if current.as_slice() == new_password {
+            return Ok((self.password_history.clone(), false));
+        }
+        let field_key: FieldKey = FieldKey::new(mek)?;
+        let mut history: Vec<PasswordHistoryItem> = decrypt_history(&self.password_history, &field_key, &self.id)?;
+        if !current.is_empty() {
+            record_password(&mut history, String::from_utf8_lossy(&current).into_owned());
+        }
+        Ok((encrypt_history(&history, &field_key, &self.id)?, true))
     }
 
     pub fn add_password(&self, data: &[u8], mek: &[u8]) -> Result<Self, String> {
         let crypto_bub: Cryptographer = Cryptographer::new(None);
+        let (password_history, password_changed) = self
+            .history_after_replacing(data, mek)
+            .map_err(|e| format!("Failed to add encrypted password; {}", e))?;
+        let now: DateTime<Utc> = Utc::now();
         match Cryptographer::encrypt(&crypto_bub, data, mek) {
             Ok(data) => Ok(Self {
                 id: self.id.clone(),
                 title: self.title.clone(),
                 username: self.username.clone(),
                 password: Some(data),
-                url: self.url.clone(),
+                urls: self.urls.clone(),
                 notes: self.notes.clone(),
-                creation_date: self.creation_date.clone(),
+                created: self.created,
+                modified: Some(now),
+                password_changed: if password_changed { Some(now) } else { self.password_changed },
+                last_used: self.last_used,
+                deleted: self.deleted,
+                expires: self.expires,
+                rotation_days: self.rotation_days,
+                folder: self.folder.clone(),
+                tags: self.tags.clone(),
                 category: self.category.clone(),
                 favorite: self.favorite,
+                kind: self.kind,
+                details: self.details.clone(),
+                custom_fields: self.custom_fields.clone(),
+                attachments: self.attachments.clone(),
+                password_history,
             }),
             Err(e) => Err(format!("Failed to add encrypted password; {}", e)),
         }
     }
 }
 
+impl Expiring for PasswordEntry {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn title(&self) -> &str {
+        &self.title
+    }
+
+    fn is_trashed(&self) -> bool {
+        PasswordEntry::is_trashed(self)
+    }
+
+    fn expiry_due(&self) -> Option<DateTime<Utc>> {
+        PasswordEntry::expiry_due(self)
+    }
+}
+
+impl Filed for PasswordEntry {
+    fn folder(&self) -> Option<&str> {
+        self.folder.as_deref()
+    }
+
+    fn folder_mut(&mut self) -> &mut Option<String> {
+        &mut self.folder
+    }
+
+    fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    fn tags_mut(&mut self) -> &mut Vec<String> {
+        &mut self.tags
+    }
+
+    fn touch(&mut self) {
+        PasswordEntry::touch(self);
+    }
+}
+
 impl PartialEq for PasswordEntry {
     fn eq(&self, other: &Self) -> bool {
         self.id == other.id
@@ -101,11 +326,24 @@ impl Default for PasswordEntry {
             title: String::new(),
             username: None,
             password: None,
-            url: None,
+            urls: Vec::new(),
             notes: None,
-            creation_date: Utc::now().to_string(),
+            created: Utc::now(),
+            modified: None,
+            password_changed: None,
+            last_used: None,
+            deleted: None,
+            expires: None,
+            rotation_days: None,
+            folder: None,
+            tags: Vec::new(),
             category: None,
             favorite: false,
+            kind: EntryKind::Login,
+            details: None,
+            custom_fields: Vec::new(),
+            attachments: Vec::new(),
+            password_history: None,
         }
     }
 }
@@ -117,11 +355,40 @@ pub struct DecryptedPasswordEntry {
     pub title: String,
     pub username: Option<String>,
     pub password: Option<String>,
-    pub url: Option<String>,
+    #[serde(default, alias = "url", deserialize_with = "url_match::deserialize_urls")]
+    pub urls: Vec<EntryUrl>, // the first one is shown, and exported where there's room for only one
     pub notes: Option<String>,
-    pub creation_date: String,
-    pub category: Option<String>,
+    #[serde(alias = "creationDate", deserialize_with = "entry_date::deserialize")]
+    pub created: DateTime<Utc>,
+    #[serde(default, alias = "lastModified", deserialize_with = "entry_date::deserialize_option")]
+    pub modified: Option<DateTime<Utc>>, // None until the entry is first edited
+    #[serde(default, deserialize_with = "entry_date::deserialize_option")]
+    pub password_changed: Option<DateTime<Utc>>, // None until the first password is replaced
+    #[serde(default, deserialize_with = "entry_date::deserialize_option")]
+    pub last_used: Option<DateTime<Utc>>, // when the password was last copied or filled
+    #[serde(default, deserialize_with = "entry_date::deserialize_option")]
+    pub deleted: Option<DateTime<Utc>>, // set while the entry is in the trash
+    #[serde(default, deserialize_with = "entry_date::deserialize_option")]
+    pub expires: Option<DateTime<Utc>>, // the password has to be changed by then, see expiry.rs
+    #[serde(default)]
+    pub rotation_days: Option<u32>, // or this many days after it was last changed
+    #[serde(default)]
+    pub folder: Option<String>, // a "/"-separated path like "Work/Servers", see folders.rs
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>, // only read from older vaults and exports, see migrate_category
     pub favorite: bool,
+    #[serde(default)]
+    pub kind: EntryKind,
+    #[serde(default)]
+    pub details: Option<EntryDetails>,
+    #[serde(default)]
+    pub custom_fields: Vec<DecryptedCustomField>,
+    #[serde(default)]
+    pub attachments: Vec<DecryptedAttachment>,
+    #[serde(default)]
+    pub password_history: Vec<PasswordHistoryItem>, // newest first
 }
 
 impl DecryptedPasswordEntry {
@@ -132,7 +399,7 @@ impl DecryptedPasswordEntry {
         password_: Option<String>,
         url_: Option<String>,
         notes_: Option<String>,
-        category_: Option<String>,
+        folder_: Option<String>,
         favorite_: Option<bool>,
     ) -> Result<Self, CryptoError> {
 
@@ -142,17 +409,31 @@ impl DecryptedPasswordEntry {
             title: title_,
             username: username_,
             password: match password_ { Some(password) => Some(password), None => None },
-            url: url_,
+            urls: url_.as_deref().filter(|url| !url.trim().is_empty()).map(EntryUrl::new).into_iter().collect(),
             notes: notes_,
-            creation_date: Utc::now().to_string(),
-            category: category_,
+            created: Utc::now(),
+            modified: None,
+            password_changed: None,
+            last_used: None,
+            deleted: None,
+            expires: None,
+            rotation_days: None,
+            folder: folder_.as_deref().and_then(normalize_folder),
+            tags: Vec::new(),
+            category: None,
             favorite: favorite_.unwrap_or(false),
+            kind: EntryKind::Login,
+            details: None,
+            custom_fields: Vec::new(),
+            attachments: Vec::new(),
+            password_history: Vec::new(),
         })
     }
     
     
     pub fn new_from_password_entry(entry: PasswordEntry, mek: &[u8]) -> Result<Self, CryptoError> {
         let cryptographer = Cryptographer::new(None);
+        let field_key: FieldKey = FieldKey::new(mek)?;
         let decrypted_password = match entry.password {
             Some(ref password) => {
                 let result = cryptographer.decrypt(password.clone(), mek);
@@ -160,43 +441,245 @@ impl DecryptedPasswordEntry {
             },
             None => None,
         };
+        let details: Option<EntryDetails> = match entry.details {
+            Some(ref details) => Some(EntryDetails::decrypt(details, &field_key, &entry.id)?),
+            None => None,
+        };
+        let custom_fields: Vec<DecryptedCustomField> = entry
+            .custom_fields
+            .iter()
+            .map(|field| field.decrypt(&field_key, &entry.id))
+            .collect::<Result<_, _>>()?;
+        let attachments: Vec<DecryptedAttachment> = entry
+            .attachments
+            .iter()
+            .map(|attachment| attachment.decrypt(mek))
+            .collect::<Result<_, _>>()?;
+        let password_history: Vec<PasswordHistoryItem> = decrypt_history(&entry.password_history, &field_key, &entry.id)?;
+        let username: Option<String> = decrypt_text(&entry.username, &field_key, &entry.id, USERNAME_FIELD)?;
+        let notes: Option<String> = decrypt_text(&entry.notes, &field_key, &entry.id, NOTES_FIELD)?;
         Ok(Self {
             id: entry.id,
             title: entry.title,
-            username: entry.username,
+            username,
             password: decrypted_password.map(|p| match String::from_utf8(p) { Ok(s) => s, Err(_) => String::new() }),
-            url: entry.url,
-            notes: entry.notes,
-            creation_date: entry.creation_date,
+            urls: entry.urls,
+            notes,
+            created: entry.created,
+            modified: entry.modified,
+            password_changed: entry.password_changed,
+            last_used: entry.last_used,
+            deleted: entry.deleted,
+            expires: entry.expires,
+            rotation_days: entry.rotation_days,
+            folder: entry.folder,
+            tags: entry.tags,
             category: entry.category,
             favorite: entry.favorite,
+            kind: entry.kind,
+            details,
+            custom_fields,
+            attachments,
+            password_history,
         })
     }
 
     pub fn convert_to_encrypted(&self, mek: &[u8]) -> Result<PasswordEntry, CryptoError> {
         let cryptographer = Cryptographer::new(None);
+        let field_key: FieldKey = FieldKey::new(mek)?;
         let encrypted_password = match self.password {
             Some(ref password) => Some(cryptographer.encrypt(password.as_bytes(), mek)?),
             None => None,
         };
+        if self.details.as_ref().is_some_and(|details| details.kind() != self.kind) {
+            return Err(CryptoError::InvalidInput);
+        }
+        let encrypted_details: Option<SealedData> = match self.details {
+            Some(ref details) => Some(details.encrypt(&field_key, &self.id)?),
+            None => None,
+        };
         Ok(PasswordEntry {
             id: self.id.clone(),
             title: self.title.clone(),
-            username: self.username.clone(),
+            username: encrypt_text(&self.username, &field_key, &self.id, USERNAME_FIELD)?,
             password: encrypted_password,
-            url: self.url.clone(),
-            notes: self.notes.clone(),
-            creation_date: self.creation_date.clone(),
+            urls: self.urls.clone(),
+            notes: encrypt_text(&self.notes, &field_key, &self.id, NOTES_FIELD)?,
+            created: self.created,
+            modified: self.modified,
+            password_changed: self.password_changed,
+            last_used: self.last_used,
+            deleted: self.deleted,
+            expires: self.expires,
+            rotation_days: self.rotation_days,
+            folder: self.folder.clone(),
+            tags: self.tags.clone(),
             category: self.category.clone(),
             favorite: self.favorite,
+            kind: self.kind,
+            details: encrypted_details,
+            custom_fields: encrypt_custom_fields(&self.custom_fields, &field_key, &self.id)?,
+            attachments: self
+                .attachments
+                .iter()
+                .map(|attachment| attachment.encrypt(mek))
+                .collect::<Result<_, _>>()?,
+            password_history: encrypt_history(&self.password_history, &field_key, &self.id)?,
         })
     }
 
+    // Sets the kind from the details, e.g. for a new card or identity
+    pub fn with_details(mut self, details: EntryDetails) -> Self {
+        self.kind = details.kind();
+        self.details = Some(details);
+        self
+    }
+
+    // When the entry last changed: the last edit, or its creation if it was never edited
+    pub fn modified_at(&self) -> DateTime<Utc> {
+        self.modified.unwrap_or(self.created)
+    }
+
+    pub fn touch(&mut self) {
+        self.modified = Some(Utc::now());
+    }
+
+    // See folders::migrate_category
+    pub fn migrate_category(&mut self) -> bool {
+        migrate_category(&mut self.category, &mut self.folder)
+    }
+
+    pub fn primary_url(&self) -> Option<&str> {
+        self.urls.first().map(|url| url.url.as_str())
+    }
+
+    // When the password is due for changing, if the entry has an expiry date or rotation interval
+    pub fn expiry_due(&self) -> Option<DateTime<Utc>> {
+        expiry::due_date(self.expires, self.rotation_days, self.password_changed.unwrap_or(self.created))
+    }
+
+    // Whether any of the entry's URLs matches the page, by its own rule
+    pub fn matches_url(&self, page_url: &str) -> bool {
+        url_match::any_matches(&self.urls, page_url)
+    }
+
+    // Normalises the folder and tags the frontend sent
+    pub fn tidy_folder_and_tags(&mut self) {
+        self.folder = self.folder.as_deref().and_then(normalize_folder);
+        self.tags = normalize_tags(&self.tags);
+    }
+
+    pub fn is_trashed(&self) -> bool {
+        self.deleted.is_some()
+    }
+
+    pub fn move_to_trash(&mut self) {
+        self.deleted = Some(Utc::now());
+        self.touch();
+    }
+
+    pub fn restore_from_trash(&mut self) {
+        self.deleted = None;
+        self.touch();
+    }
+
+    // The password was copied or filled somewhere
+    pub fn mark_used(&mut self) {
+        self.last_used = Some(Utc::now());
+    }
+
+    // Takes over the dates of the version this entry replaces, since only the backend sets them,
+    // and marks the password as changed if it differs from that version's
+    pub fn keep_dates_from(&mut self, previous: &DecryptedPasswordEntry) {
+        self.created = previous.created;
+        self.modified = previous.modified;
+        self.last_used = previous.last_used;
+        self.deleted = previous.deleted;
+        self.password_changed = if self.password != previous.password {
+            Some(Utc::now())
+        } else {
+            previous.password_changed
+        };
+    }
+
+    // Takes over the history of the version this entry replaces, adding that version's password
+    // if it changed. Whatever history `self` came with is ignored; only the backend writes it.
+    pub fn keep_history_from(&mut self, previous: &DecryptedPasswordEntry) {
+        self.password_history = previous.password_history.clone();
+        if let Some(previous_password) = previous.password.as_ref().filter(|password| !password.is_empty()) {
+            if self.password.as_ref() != Some(previous_password) {
+                record_password(&mut self.password_history, previous_password.clone());
+            }
+        }
+    }
+
+    // Makes a password from the history current again; the password it replaces goes into the
+    // history, so a restore can itself be undone
+    pub fn restore_password(&mut self, index: usize) -> Result<(), CryptoError> {
+        if index >= self.password_history.len() {
+            return Err(CryptoError::InvalidInput);
+        }
+        let restored: PasswordHistoryItem = self.password_history.remove(index);
+        let replaced: Option<String> = self.password.replace(restored.password.clone());
+        if let Some(replaced) = replaced.filter(|password| !password.is_empty()) {
+            record_password(&mut self.password_history, replaced);
+        }
+        self.password_changed = Some(Utc::now());
+        self.touch();
+        Ok(())
+    }
+
+    // Same values in every field the user sees and the same trash state; id, the other dates and
+    // history aren't compared
+    pub fn same_content(&self, other: &Self) -> bool {
+        self.title == other.title
+            && self.username == other.username
+            && self.password == other.password
+            && self.urls == other.urls
+            && self.notes == other.notes
+            && self.folder == other.folder
+            && self.tags == other.tags
+            && self.expires == other.expires
+            && self.rotation_days == other.rotation_days
+            && self.favorite == other.favorite
+            && self.kind == other.kind
+            && self.details == other.details
+            && self.custom_fields == other.custom_fields
+            && self.attachments == other.attachments
+            && self.deleted == other.deleted
+    }
+
+    // Case-insensitive match on the title, username, URLs, folder, tags and notes, the details of
+    // the entry's kind and custom fields. Card and account numbers only match on their last four
+    // digits; hidden custom fields only by name.
+    pub fn matches_query(&self, query: &str) -> bool {
+        let query: String = query.trim().to_lowercase();
+        if query.is_empty() {
+            return true;
+        }
+        let matches = |value: &str| value.to_lowercase().contains(&query);
+        [&self.username, &self.folder, &self.notes]
+            .into_iter()
+            .flatten()
+            .chain(std::iter::once(&self.title))
+            .chain(self.urls.iter().map(|url| &url.url))
+            .chain(&self.tags)
+            .any(|value| matches(value))
+            || self.details.as_ref().is_some_and(|details| {
+                details.fields().iter().any(|field| !field.sensitive && matches(&field.value))
+                    || details.last_four().is_some_and(|last_four| last_four == query)
+            })
+            || self
+                .custom_fields
+                .iter()
+                .any(|field| matches(&field.name) || (!field.is_hidden() && matches(&field.value)))
+    }
+
     pub fn display_name(&self) -> [String; 3] {
         [
             self.title.clone(),
             self.username.as_deref().unwrap_or_default().to_string(),
-            self.url.as_deref().unwrap_or_default().to_string(),
+            self.primary_url().unwrap_or_default().to_string(),
         ]
     }
 }
@@ -209,16 +692,69 @@ impl Default for DecryptedPasswordEntry {
             title: String::new(),
             username: None,
             password: None,
-            url: None,
+            urls: Vec::new(),
             notes: None,
-            creation_date: Utc::now().to_string(),
+            created: Utc::now(),
+            modified: None,
+            password_changed: None,
+            last_used: None,
+            deleted: None,
+            expires: None,
+            rotation_days: None,
+            folder: None,
+            tags: Vec::new(),
             category: None,
             favorite: false,
+            kind: EntryKind::Login,
+            details: None,
+            custom_fields: Vec::new(),
+            attachments: Vec::new(),
+            password_history: Vec::new(),
         }
     }
 }
 
 
+impl Expiring for DecryptedPasswordEntry {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn title(&self) -> &str {
+        &self.title
+    }
+
+    fn is_trashed(&self) -> bool {
+        DecryptedPasswordEntry::is_trashed(self)
+    }
+
+    fn expiry_due(&self) -> Option<DateTime<Utc>> {
+        DecryptedPasswordEntry::expiry_due(self)
+    }
+}
+
+impl Filed for DecryptedPasswordEntry {
+    fn folder(&self) -> Option<&str> {
+        self.folder.as_deref()
+    }
+
+    fn folder_mut(&mut self) -> &mut Option<String> {
+        &mut self.folder
+    }
+
+    fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    fn tags_mut(&mut self) -> &mut Vec<String> {
+        &mut self.tags
+    }
+
+    fn touch(&mut self) {
+        DecryptedPasswordEntry::touch(self);
+    }
+}
+
 impl PartialEq for DecryptedPasswordEntry {
     fn eq(&self, other: &Self) -> bool {
         self.id == other.id
@@ -230,6 +766,14 @@ impl Eq for DecryptedPasswordEntry {}
 impl Zeroize for DecryptedPasswordEntry {
     fn zeroize(&mut self) {
         self.password.zeroize();
+        self.username.zeroize();
+        self.notes.zeroize();
+        if let Some(details) = self.details.as_mut() {
+            details.zeroize();
+        }
+        self.custom_fields.zeroize();
+        self.attachments.zeroize();
+        self.password_history.zeroize();
     }
 }
 
@@ -237,4 +781,19 @@ impl Drop for DecryptedPasswordEntry {
     fn drop(&mut self) {
         self.zeroize();
     }
-}
\ No newline at end of file
+}
+
+// Entries matching `query` (see matches_query), optionally only those of one kind. Entries in
+// the trash are left out.
+pub fn search_entries<'a>(
+    entries: &'a [DecryptedPasswordEntry],
+    query: &str,
+    kind: Option<EntryKind>,
+) -> Vec<&'a DecryptedPasswordEntry> {
+    entries
+        .iter()
+        .filter(|entry| !entry.is_trashed())
+        .filter(|entry| kind.is_none_or(|kind| entry.kind == kind))
+        .filter(|entry| entry.matches_query(query))
+        .collect()
+}
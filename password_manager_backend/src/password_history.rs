@@ -0,0 +1,63 @@
+use crate::crypto::{FieldKey, SealedData};
+use crate::entry_date;
+use crate::error_handling::CryptoError;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, Zeroizing};
+
+pub const PASSWORD_HISTORY_LIMIT: usize = 10;
+
+// A password an entry used to have. Entries keep these newest first; on disk the whole list is
+// sealed as one value, so an entry costs a single extra encryption however long its history.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct PasswordHistoryItem {
+    pub password: String,
+    #[serde(deserialize_with = "entry_date::deserialize")]
+    pub replaced_at: DateTime<Utc>,
+}
+
+// Adds a replaced password to the front, dropping the oldest past the limit
+pub fn record_password(history: &mut Vec<PasswordHistoryItem>, password: String) {
+    history.insert(
+        0,
+        PasswordHistoryItem {
+            password,
+            replaced_at: Utc::now(),
+        },
+    );
+    history.truncate(PASSWORD_HISTORY_LIMIT);
+}
+
+const HISTORY_FIELD: &str = "password_history";
+
+// `owner` is the id of the entry the history belongs to
+pub fn encrypt_history(history: &[PasswordHistoryItem], field_key: &FieldKey, owner: &str) -> Result<Option<SealedData>, CryptoError> {
+    if history.is_empty() {
+        return Ok(None);
+    }
+    let serialized: Zeroizing<Vec<u8>> = Zeroizing::new(serde_json::to_vec(history)?);
+    Ok(Some(field_key.seal(&serialized, owner, HISTORY_FIELD)?))
+}
+
+pub fn decrypt_history(history: &Option<SealedData>, field_key: &FieldKey, owner: &str) -> Result<Vec<PasswordHistoryItem>, CryptoError> {
+    match history {
+        Some(sealed) => {
+            let serialized: Zeroizing<Vec<u8>> = Zeroizing::new(field_key.open(sealed, owner, HISTORY_FIELD)?);
+            Ok(serde_json::from_slice(&serialized)?)
+        }
+        None => Ok(Vec::new()),
+    }
+}
+
+impl Zeroize for PasswordHistoryItem {
+    fn zeroize(&mut self) {
+        self.password.zeroize();
+    }
+}
+
+impl Drop for PasswordHistoryItem {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
@@ -0,0 +1,69 @@
+use crate::error_handling::CryptoError;
+use rand::{rngs::OsRng, RngCore};
+
+const RECOVERY_KEY_BYTES: usize = 20; // 160 bits -> exactly 32 base32 characters
+const RECOVERY_KEY_CHARS: usize = 32;
+const GROUP_LEN: usize = 4;
+// Crockford base32, no I/L/O/U so the printed code can't be misread
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+// Generates a printable recovery key, e.g. "7K3M-Q9XD-...". Only the normalized
+// form (see normalize_recovery_key) is ever fed into the KDF.
+pub fn generate_recovery_key() -> String {
+    let mut bytes: [u8; RECOVERY_KEY_BYTES] = [0u8; RECOVERY_KEY_BYTES];
+    OsRng.fill_bytes(&mut bytes);
+
+    let encoded: String = encode_base32(&bytes);
+    bytes.fill(0);
+
+    encoded
+        .as_bytes()
+        .chunks(GROUP_LEN)
+        .map(|group| String::from_utf8_lossy(group).into_owned())
+        .collect::<Vec<String>>()
+        .join("-")
+}
+
+// Strips separators/whitespace, uppercases and maps the characters people commonly
+// confuse (O -> 0, I/L -> 1) so a hand-typed key still matches the printed one.
+pub fn normalize_recovery_key(input: &str) -> Result<String, CryptoError> {
+    let mut normalized: String = String::with_capacity(RECOVERY_KEY_CHARS);
+    for c in input.chars() {
+        if c == '-' || c.is_whitespace() {
+            continue;
+        }
+        let c: char = match c.to_ascii_uppercase() {
+            'O' => '0',
+            'I' | 'L' => '1',
+            other => other,
+        };
+        if !c.is_ascii() || !ALPHABET.contains(&(c as u8)) {
+            return Err(CryptoError::InvalidRecoveryKey);
+        }
+        normalized.push(c);
+    }
+
+    if normalized.len() != RECOVERY_KEY_CHARS {
+        return Err(CryptoError::InvalidRecoveryKey);
+    }
+    Ok(normalized)
+}
+
+fn encode_base32(data: &[u8]) -> String {
+    let mut output: String = String::with_capacity((data.len() * 8).div_ceil(5));
+    let mut buffer: u32 = 0;
+    let mut bits: u32 = 0;
+
+    for &byte in data {
+        buffer = (buffer << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            output.push(ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        output.push(ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    output
+}
@@ -0,0 +1,162 @@
+use crate::error_handling::CryptoError;
+use rand::{rngs::OsRng, RngCore};
+use ring::digest::{digest, SHA256};
+use std::collections::HashSet;
+use zeroize::{Zeroize, Zeroizing};
+
+const SHARE_PREFIX: &str = "pawpass-share";
+const CHECKSUM_LEN: usize = 4; // bytes of SHA-256 appended to each printed share to catch typos
+
+// One share of a split secret. `index` is the x coordinate (never 0, that's where the secret sits).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecretShare {
+    pub threshold: u8,
+    pub index: u8,
+    pub(crate) data: Vec<u8>,
+}
+
+impl SecretShare {
+    // Printable form, e.g. "pawpass-share:3:2:<hex>:<checksum>"
+    pub fn to_text(&self) -> String {
+        format!(
+            "{}:{}:{}:{}:{}",
+            SHARE_PREFIX,
+            self.threshold,
+            self.index,
+            hex::encode(&self.data),
+            hex::encode(self.checksum())
+        )
+    }
+
+    pub fn from_text(text: &str) -> Result<Self, CryptoError> {
+        let parts: Vec<&str> = text.trim().split(':').collect();
+        if parts.len() != 5 || parts[0] != SHARE_PREFIX {
+            return Err(CryptoError::InvalidShare);
+        }
+        let threshold: u8 = parts[1].parse().map_err(|_| CryptoError::InvalidShare)?;
+        let index: u8 = parts[2].parse().map_err(|_| CryptoError::InvalidShare)?;
+        let data: Vec<u8> = hex::decode(parts[3]).map_err(|_| CryptoError::InvalidShare)?;
+        let checksum: Vec<u8> = hex::decode(parts[4]).map_err(|_| CryptoError::InvalidShare)?;
+
+        let share: SecretShare = SecretShare { threshold, index, data };
+        if threshold == 0 || index == 0 || share.data.is_empty() || checksum != share.checksum() {
+            return Err(CryptoError::InvalidShare);
+        }
+        Ok(share)
+    }
+
+    fn checksum(&self) -> Vec<u8> {
+        let mut input: Vec<u8> = vec![self.threshold, self.index];
+        input.extend_from_slice(&self.data);
+        let checksum: Vec<u8> = digest(&SHA256, &input).as_ref()[..CHECKSUM_LEN].to_vec();
+        input.zeroize();
+        checksum
+    }
+}
+
+impl Drop for SecretShare {
+    fn drop(&mut self) {
+        self.data.zeroize();
+    }
+}
+
+// Splits `secret` into `share_count` shares so that any `threshold` of them rebuild it and fewer
+// reveal nothing. Every byte gets its own random polynomial of degree threshold - 1 over GF(256).
+pub fn split_secret(secret: &[u8], threshold: u8, share_count: u8) -> Result<Vec<SecretShare>, CryptoError> {
+    if secret.is_empty() || threshold < 2 || threshold > share_count {
+        return Err(CryptoError::InvalidInput);
+    }
+
+    let mut shares: Vec<SecretShare> = (1..=share_count)
+        .map(|index| SecretShare {
+            threshold,
+            index,
+            data: Vec::with_capacity(secret.len()),
+        })
+        .collect();
+
+    let mut coefficients: Vec<u8> = vec![0u8; threshold as usize];
+    for &byte in secret {
+        coefficients[0] = byte;
+        OsRng.fill_bytes(&mut coefficients[1..]);
+        for share in shares.iter_mut() {
+            share.data.push(evaluate(&coefficients, share.index));
+        }
+    }
+    coefficients.zeroize();
+
+    Ok(shares)
+}
+
+// Rebuilds the secret from at least `threshold` distinct shares via Lagrange interpolation at x = 0
+pub fn combine_shares(shares: &[SecretShare]) -> Result<Zeroizing<Vec<u8>>, CryptoError> {
+    let first: &SecretShare = shares.first().ok_or(CryptoError::InsufficientShares)?;
+    let threshold: u8 = first.threshold;
+    let secret_len: usize = first.data.len();
+
+    let mut seen: HashSet<u8> = HashSet::new();
+    for share in shares {
+        if share.threshold != threshold || share.data.len() != secret_len || !seen.insert(share.index) {
+            return Err(CryptoError::InvalidShare);
+        }
+    }
+    if shares.len() < threshold as usize {
+        return Err(CryptoError::InsufficientShares);
+    }
+
+    let used: &[SecretShare] = &shares[..threshold as usize];
+    let mut secret: Zeroizing<Vec<u8>> = Zeroizing::new(vec![0u8; secret_len]);
+    for (i, share_i) in used.iter().enumerate() {
+        // Lagrange basis polynomial for share i, evaluated at 0 (subtraction is xor in GF(256))
+        let mut basis: u8 = 1;
+        for (j, share_j) in used.iter().enumerate() {
+            if i != j {
+                basis = gf_mul(basis, gf_div(share_j.index, share_j.index ^ share_i.index));
+            }
+        }
+        for (byte, &y) in secret.iter_mut().zip(share_i.data.iter()) {
+            *byte ^= gf_mul(y, basis);
+        }
+    }
+    Ok(secret)
+}
+
+// Horner's method, coefficients[0] is the constant term
+fn evaluate(coefficients: &[u8], x: u8) -> u8 {
+    coefficients
+        .iter()
+        .rev()
+        .fold(0u8, |acc, &coefficient| gf_mul(acc, x) ^ coefficient)
+}
+
+// GF(2^8) multiplication with the AES reduction polynomial x^8 + x^4 + x^3 + x + 1.
+// Written without secret-dependent branches or table lookups.
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product: u8 = 0;
+    for _ in 0..8 {
+        product ^= a & 0u8.wrapping_sub(b & 1);
+        let carry: u8 = 0u8.wrapping_sub(a >> 7);
+        a = (a << 1) ^ (0x1b & carry);
+        b >>= 1;
+    }
+    product
+}
+
+// a^254 is a's inverse in GF(2^8)
+fn gf_inverse(a: u8) -> u8 {
+    let mut result: u8 = 1;
+    let mut base: u8 = a;
+    let mut exponent: u8 = 254;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = gf_mul(result, base);
+        }
+        base = gf_mul(base, base);
+        exponent >>= 1;
+    }
+    result
+}
+
+fn gf_div(a: u8, b: u8) -> u8 {
+    gf_mul(a, gf_inverse(b))
+}
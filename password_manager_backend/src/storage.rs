@@ -1,43 +1,69 @@
 use crate::config::UserSettings;
+use crate::crypto::Cryptographer;
 use crate::error_handling::CryptoError;
+use crate::expiry::{self, ExpiryReminder};
+use crate::folders::{self, Folder, TagCount};
+use crate::hidden_vault::{HiddenVault, HiddenVaultKey};
+use crate::key_file;
+use crate::key_slot::{KeySlot, KeySlotInfo, KeySlotKind, MASTER_PASSWORD_LABEL, RECOVERY_KEY_LABEL};
 use crate::master_password::MasterPasswordData;
 use crate::mek::MekData;
+use crate::migration;
 use crate::password_entry::PasswordEntry;
+use crate::recovery;
+use crate::shamir::{self, SecretShare};
+use crate::trash;
+use chrono::{DateTime, Utc};
 use ring::digest::Digest;
 use serde::{Deserialize, Serialize};
 use serde_json;
+use rand::{rngs::OsRng, RngCore};
 use std::path::Path;
 use tokio::fs::File;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use zeroize::{Zeroize, Zeroizing};
+
+const SHARED_SECRET_BYTES: usize = 32;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ApplicationData {
     pub master_password_data: Option<MasterPasswordData>,
-    pub mek_data: Option<MekData>,
+    #[serde(default)]
+    pub key_slots: Vec<KeySlot>, // each slot wraps the same MEK (master password, recovery key, ...)
     pub password_entries: Vec<PasswordEntry>,
     pub user_settings: UserSettings,
     pub master_password_data_hash: Option<String>,
-    pub mek_data_hash: Option<String>,
+    #[serde(default)]
+    pub key_slots_hash: Option<String>,
     pub password_entries_hash: Option<String>,
-    
+    #[serde(default)]
+    pub hidden_vault: Option<HiddenVault>, // filler, or the real vault sealed away behind a decoy
+    #[serde(default)]
+    pub hidden_vault_hash: Option<String>,
 }
 
 impl ApplicationData {
     pub fn new() -> Self {
         Self {
             master_password_data: None,
-            mek_data: None,
+            key_slots: Vec::new(),
             password_entries: Vec::new(),
             user_settings: UserSettings::default(),
             master_password_data_hash: None,
-            mek_data_hash: None,
+            key_slots_hash: None,
             password_entries_hash: None,
+            hidden_vault: None,
+            hidden_vault_hash: None,
         }
     }
 
     pub fn add_mek_data(&mut self, master_password: &[u8]) -> Result<(), CryptoError> {
-        if self.mek_data.is_none() {
-            self.mek_data = Some(MekData::new(master_password)?);
+        if self.key_slots.is_empty() {
+            self.key_slots.push(KeySlot::new(
+                KeySlotKind::MasterPassword,
+                MASTER_PASSWORD_LABEL.to_string(),
+                MekData::new(master_password)?,
+            ));
             self.update_hashes()?;
         } else {
             return Err(CryptoError::MekDataAlreadyExists);
@@ -46,43 +72,133 @@ impl ApplicationData {
     }
 
     pub fn update_mek_data(&mut self, old_master_password: &[u8], new_master_password: &[u8],) -> Result<(), CryptoError> {
-        if let Some(mek_data) = &mut self.mek_data {
-            if mek_data.decrypt_mek(old_master_password).is_ok() {
-                self.mek_data = Some(mek_data.update_mek(old_master_password, new_master_password)?);
+        self.update_mek_data_with_key_file(old_master_password, new_master_password, None)
+    }
+
+    pub fn update_mek_data_with_key_file(
+        &mut self,
+        old_master_password: &[u8],
+        new_master_password: &[u8],
+        key_file_hash: Option<&[u8]>,
+    ) -> Result<(), CryptoError> {
+        let mut updated: bool = false;
+        let mut found: bool = false;
+        for slot in self.key_slots.iter_mut().filter(|s| s.kind == KeySlotKind::MasterPassword) {
+            found = true;
+            let old_secret = match slot.composite_secret(old_master_password, key_file_hash) {
+                Ok(secret) => secret,
+                Err(_) => continue,
+            };
+            if slot.mek_data.decrypt_mek(&old_secret).is_ok() {
+                let new_secret = slot.composite_secret(new_master_password, key_file_hash)?;
+                slot.mek_data = slot.mek_data.update_mek(&old_secret, &new_secret)?;
+                updated = true;
+            }
+        }
+
+        match (found, updated) {
+            (_, true) => {
                 self.update_hashes()?;
                 Ok(())
-            } else {
-                Err(CryptoError::AuthenticationFailed)
             }
-        } else {
-            Err(CryptoError::MekUpdateFailed)
+            (true, false) => Err(CryptoError::AuthenticationFailed),
+            (false, _) => Err(CryptoError::MekUpdateFailed),
         }
     }
 
     pub fn decrypt_mek_data(&self, master_password: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        self.decrypt_mek_data_with_key_file(master_password, None)
+    }
+
+    pub fn decrypt_mek_data_with_key_file(&self, master_password: &[u8], key_file_hash: Option<&[u8]>) -> Result<Vec<u8>, CryptoError> {
         if !self.verify_master_password(master_password) {
             return Err(CryptoError::AuthenticationFailed);
         }
-        if let Some(mek_data) = &self.mek_data {
-            mek_data.decrypt_mek(master_password)
-        } else {
-            Err(CryptoError::MekDecryptionFailed)
+
+        let mut result: Result<Vec<u8>, CryptoError> = Err(CryptoError::MekDataNotPresent);
+        for slot in self.key_slots.iter().filter(|s| s.kind == KeySlotKind::MasterPassword) {
+            result = slot
+                .composite_secret(master_password, key_file_hash)
+                .and_then(|secret| slot.unlock(&secret).map_err(|_| CryptoError::MekDecryptionFailed));
+            if result.is_ok() {
+                break;
+            }
+        }
+        result
+    }
+
+    // Tries every slot of the given kind; the secret only has to open one of them
+    pub fn decrypt_mek_with_slot_kind(&self, kind: KeySlotKind, secret: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        let mut slots = self.key_slots.iter().filter(|s| s.kind == kind).peekable();
+        if slots.peek().is_none() {
+            return Err(CryptoError::MekDataNotPresent);
         }
+        slots
+            .find_map(|slot| slot.unlock(secret).ok())
+            .ok_or(CryptoError::MekDecryptionFailed)
+    }
+
+    pub fn requires_key_file(&self) -> bool {
+        self.key_slots
+            .iter()
+            .any(|s| s.kind == KeySlotKind::MasterPassword && s.requires_key_file)
+    }
+
+    // From now on unlocking needs both the master password and the key file
+    pub fn enable_key_file(&mut self, master_password: &[u8], key_file_hash: &[u8]) -> Result<(), CryptoError> {
+        let mut mek: Vec<u8> = self.decrypt_mek_data_with_key_file(master_password, None)?;
+        let result = self.replace_master_password_slot(master_password, &mek, Some(key_file_hash));
+        mek.zeroize();
+        result
+    }
+
+    // Needs the current key file too, otherwise anyone with just the password could strip the second factor
+    pub fn disable_key_file(&mut self, master_password: &[u8], key_file_hash: &[u8]) -> Result<(), CryptoError> {
+        if !self.requires_key_file() {
+            return Err(CryptoError::InvalidInput);
+        }
+        let mut mek: Vec<u8> = self.decrypt_mek_data_with_key_file(master_password, Some(key_file_hash))?;
+        let result = self.replace_master_password_slot(master_password, &mek, None);
+        mek.zeroize();
+        result
+    }
+
+    // Rewraps the MEK for the master password (optionally with a key file), keeping the first
+    // master password slot's id and label and dropping any others
+    fn replace_master_password_slot(&mut self, master_password: &[u8], mek: &[u8], key_file_hash: Option<&[u8]>) -> Result<(), CryptoError> {
+        let secret: Zeroizing<Vec<u8>> = match key_file_hash {
+            Some(key_file_hash) => key_file::combine_with_password(master_password, key_file_hash),
+            None => Zeroizing::new(master_password.to_vec()),
+        };
+        let mek_data: MekData = MekData::wrap_mek(&secret, mek)?;
+        let mut slot: KeySlot = match self.key_slots.iter().find(|s| s.kind == KeySlotKind::MasterPassword) {
+            Some(existing) => existing.clone(),
+            None => KeySlot::new(KeySlotKind::MasterPassword, MASTER_PASSWORD_LABEL.to_string(), mek_data.clone()),
+        };
+        slot.mek_data = mek_data;
+        slot.requires_key_file = key_file_hash.is_some();
+
+        self.key_slots.retain(|s| s.kind != KeySlotKind::MasterPassword);
+        self.key_slots.insert(0, slot);
+        self.update_hashes()
     }
 
     pub fn verify_hashes(&self) -> bool {
         let master_data_serialized = serde_json::to_string(&self.master_password_data).unwrap();
-        let mek_data_serialized = serde_json::to_string(&self.mek_data).unwrap();
+        let key_slots_serialized = serde_json::to_string(&self.key_slots).unwrap();
         let entries_serialized = serde_json::to_string(&self.password_entries).unwrap();
+        let hidden_vault_serialized = serde_json::to_string(&self.hidden_vault).unwrap();
 
         let master_data_hash = Self::generate_hash(master_data_serialized.as_bytes());
-        let mek_data_hash = Self::generate_hash(mek_data_serialized.as_bytes());
+        let key_slots_hash = Self::generate_hash(key_slots_serialized.as_bytes());
         let entries_hash = Self::generate_hash(entries_serialized.as_bytes());
+        let hidden_vault_hash = Self::generate_hash(hidden_vault_serialized.as_bytes());
 
 
         self.master_password_data_hash == Some(master_data_hash)
-            && self.mek_data_hash == Some(mek_data_hash)
+            && self.key_slots_hash == Some(key_slots_hash)
             && self.password_entries_hash == Some(entries_hash)
+            && self.hidden_vault_hash == Some(hidden_vault_hash)
     }
 
     pub fn add_master_password_data(&mut self, new_password: &[u8]) -> Result<(), CryptoError> {
@@ -90,6 +206,9 @@ impl ApplicationData {
             self.master_password_data =
                 Some(MasterPasswordData::set_new_master_password(new_password)?);
             self.add_mek_data(new_password)?;
+            if self.hidden_vault.is_none() {
+                self.hidden_vault = Some(HiddenVault::filler()?);
+            }
             self.update_hashes()?;
         } else {
             return Err(CryptoError::MasterPasswordDataAlreadyExists);
@@ -98,11 +217,23 @@ impl ApplicationData {
     }
 
     pub fn update_master_password_data(&mut self, password_attempt: &[u8], new_password: &[u8]) -> Result<(), CryptoError> {
+        self.update_master_password_data_with_key_file(password_attempt, new_password, None)
+    }
+
+    pub fn update_master_password_data_with_key_file(
+        &mut self,
+        password_attempt: &[u8],
+        new_password: &[u8],
+        key_file_hash: Option<&[u8]>,
+    ) -> Result<(), CryptoError> {
         if let Some(master_data) = &self.master_password_data {
             if master_data.verify_master_password(password_attempt) {
+                // rewrap first, so a missing or wrong key file leaves the old password in place
+                if self.key_slots.iter().any(|s| s.kind == KeySlotKind::MasterPassword) {
+                    self.update_mek_data_with_key_file(password_attempt, new_password, key_file_hash)?;
+                }
                 self.master_password_data =
                     Some(MasterPasswordData::set_new_master_password(new_password)?);
-                let _ = self.update_mek_data(password_attempt, new_password);
                 self.update_hashes()?;
                 Ok(())
             } else {
@@ -113,7 +244,250 @@ impl ApplicationData {
         }
     }
 
-    
+    pub fn list_key_slots(&self) -> Vec<KeySlotInfo> {
+        self.key_slots.iter().map(KeySlot::info).collect()
+    }
+
+    // Wraps the MEK under another secret. There is only ever one master password, so new
+    // master password slots come from update_master_password_data / recover_with_key instead.
+    pub fn add_key_slot(
+        &mut self,
+        master_password: &[u8],
+        kind: KeySlotKind,
+        label: String,
+        secret: &[u8],
+        kdf_iterations: Option<u32>,
+    ) -> Result<String, CryptoError> {
+        if kind == KeySlotKind::MasterPassword {
+            return Err(CryptoError::InvalidInput);
+        }
+        let mut mek: Vec<u8> = self.decrypt_mek_data(master_password)?;
+        let result = MekData::wrap_mek_with_iterations(
+            secret,
+            &mek,
+            kdf_iterations.unwrap_or(crate::mek::DEFAULT_KDF_ITERATIONS),
+        );
+        mek.zeroize();
+
+        let slot: KeySlot = KeySlot::new(kind, label, result?);
+        let slot_id: String = slot.id.clone();
+        self.key_slots.push(slot);
+        self.update_hashes()?;
+        Ok(slot_id)
+    }
+
+    // Refuses to remove the last slot, and the last master password slot since login depends on it
+    pub fn remove_key_slot(&mut self, master_password: &[u8], slot_id: &str) -> Result<(), CryptoError> {
+        if !self.verify_master_password(master_password) {
+            return Err(CryptoError::AuthenticationFailed);
+        }
+        let index: usize = self
+            .key_slots
+            .iter()
+            .position(|s| s.id == slot_id)
+            .ok_or(CryptoError::KeySlotNotFound)?;
+
+        let kind: KeySlotKind = self.key_slots[index].kind;
+        let remaining_of_kind: usize = self.key_slots.iter().filter(|s| s.kind == kind).count();
+        if self.key_slots.len() == 1
+            || (kind == KeySlotKind::MasterPassword && remaining_of_kind == 1)
+        {
+            return Err(CryptoError::LastKeySlot);
+        }
+
+        self.key_slots.remove(index);
+        self.update_hashes()?;
+        Ok(())
+    }
+
+    // Generates a new recovery key and wraps the MEK under it, replacing any previous one.
+    // The returned key is only ever shown to the user once; we just keep the wrapped MEK.
+    pub fn add_recovery_key(&mut self, master_password: &[u8]) -> Result<String, CryptoError> {
+        let mut mek: Vec<u8> = self.decrypt_mek_data(master_password)?;
+        let recovery_key: String = recovery::generate_recovery_key();
+        let normalized: String = recovery::normalize_recovery_key(&recovery_key)?;
+
+        let result = MekData::wrap_mek(normalized.as_bytes(), &mek);
+        mek.zeroize();
+        self.key_slots.retain(|s| s.kind != KeySlotKind::RecoveryKey);
+        self.key_slots.push(KeySlot::new(
+            KeySlotKind::RecoveryKey,
+            RECOVERY_KEY_LABEL.to_string(),
+            result?,
+        ));
+        self.update_hashes()?;
+        Ok(recovery_key)
+    }
+
+    pub fn remove_recovery_key(&mut self, master_password: &[u8]) -> Result<(), CryptoError> {
+        if !self.verify_master_password(master_password) {
+            return Err(CryptoError::AuthenticationFailed);
+        }
+        if !self.has_recovery_key() {
+            return Err(CryptoError::RecoveryKeyNotFound);
+        }
+        self.key_slots.retain(|s| s.kind != KeySlotKind::RecoveryKey);
+        self.update_hashes()?;
+        Ok(())
+    }
+
+    pub fn has_recovery_key(&self) -> bool {
+        self.key_slots.iter().any(|s| s.kind == KeySlotKind::RecoveryKey)
+    }
+
+    // Unwraps the MEK with the recovery key and sets a new master password for it, so the
+    // existing entries stay readable. The recovery key itself stays valid afterwards. Since a lost
+    // password often comes with a lost key file, the recovered vault no longer requires one.
+    pub fn recover_with_key(&mut self, recovery_key: &str, new_master_password: &[u8]) -> Result<(), CryptoError> {
+        if !self.has_recovery_key() {
+            return Err(CryptoError::RecoveryKeyNotFound);
+        }
+        let normalized: String = recovery::normalize_recovery_key(recovery_key)?;
+        let mut mek: Vec<u8> = self
+            .decrypt_mek_with_slot_kind(KeySlotKind::RecoveryKey, normalized.as_bytes())
+            .map_err(|_| CryptoError::AuthenticationFailed)?;
+
+        let result = self.reset_master_password(&mek, new_master_password);
+        mek.zeroize();
+        result
+    }
+
+    // Wraps the MEK under a fresh random secret and splits that secret into `share_count`
+    // printable shares, any `threshold` of which unlock the vault together. The shares are only
+    // returned here; the vault keeps nothing but the wrapped MEK.
+    pub fn create_secret_shares(&mut self, master_password: &[u8], threshold: u8, share_count: u8) -> Result<Vec<String>, CryptoError> {
+        if threshold < 2 || threshold > share_count {
+            return Err(CryptoError::InvalidInput);
+        }
+        let mut mek: Vec<u8> = self.decrypt_mek_data(master_password)?;
+        let mut secret: Zeroizing<Vec<u8>> = Zeroizing::new(vec![0u8; SHARED_SECRET_BYTES]);
+        OsRng.fill_bytes(&mut secret);
+
+        let result = MekData::wrap_mek(&secret, &mek);
+        mek.zeroize();
+        let shares: Vec<SecretShare> = shamir::split_secret(&secret, threshold, share_count)?;
+
+        self.key_slots.push(KeySlot::new(
+            KeySlotKind::SecretShares,
+            format!("{}-of-{} secret shares", threshold, share_count),
+            result?,
+        ));
+        self.update_hashes()?;
+        Ok(shares.iter().map(SecretShare::to_text).collect())
+    }
+
+    pub fn decrypt_mek_with_shares(&self, shares: &[String]) -> Result<Vec<u8>, CryptoError> {
+        let shares: Vec<SecretShare> = shares
+            .iter()
+            .map(|share| SecretShare::from_text(share))
+            .collect::<Result<Vec<SecretShare>, CryptoError>>()?;
+        let secret: Zeroizing<Vec<u8>> = shamir::combine_shares(&shares)?;
+        self.decrypt_mek_with_slot_kind(KeySlotKind::SecretShares, &secret)
+            .map_err(|_| CryptoError::AuthenticationFailed)
+    }
+
+    // Same as recover_with_key, for when the shareholders reset the master password together
+    pub fn recover_with_shares(&mut self, shares: &[String], new_master_password: &[u8]) -> Result<(), CryptoError> {
+        let mut mek: Vec<u8> = self.decrypt_mek_with_shares(shares)?;
+        let result = self.reset_master_password(&mek, new_master_password);
+        mek.zeroize();
+        result
+    }
+
+    fn reset_master_password(&mut self, mek: &[u8], new_master_password: &[u8]) -> Result<(), CryptoError> {
+        self.replace_master_password_slot(new_master_password, mek, None)?;
+        self.master_password_data =
+            Some(MasterPasswordData::set_new_master_password(new_master_password)?);
+        self.update_hashes()?;
+        Ok(())
+    }
+
+    // Turns the vault the master password opens into a hidden one and puts a fresh, empty decoy
+    // vault in front of it, unlocked by the duress password. Calling it again from the hidden
+    // vault replaces the decoy (and its duress password) with a new one.
+    pub fn enable_duress_password(&mut self, master_password: &[u8], duress_password: &[u8]) -> Result<(), CryptoError> {
+        if duress_password.is_empty() || duress_password == master_password {
+            return Err(CryptoError::InvalidInput);
+        }
+        let real: ApplicationData = if self.verify_master_password(master_password) {
+            let mut real: ApplicationData = std::mem::replace(self, ApplicationData::new());
+            real.hidden_vault = None;
+            real.update_hashes()?;
+            real
+        } else {
+            match self.open_hidden_vault(master_password)? {
+                Some((real, _)) => real,
+                None => return Err(CryptoError::AuthenticationFailed),
+            }
+        };
+
+        let mut decoy: ApplicationData = ApplicationData::new();
+        decoy.user_settings = real.user_settings.clone();
+        decoy.add_master_password_data(duress_password)?;
+        let key: HiddenVaultKey = HiddenVaultKey::new(master_password)?;
+        decoy.seal_hidden_vault(&real, &key)?;
+        *self = decoy;
+        Ok(())
+    }
+
+    // The ids of every attachment on the vault's entries, trashed ones included
+    pub fn attachment_ids(&self) -> Vec<String> {
+        self.password_entries
+            .iter()
+            .flat_map(|entry| entry.attachments.iter().map(|attachment| attachment.id.clone()))
+            .collect()
+    }
+
+    // Brings the hidden vault back to the front and drops the decoy along with its entries
+    pub fn disable_duress_password(&mut self, master_password: &[u8]) -> Result<(), CryptoError> {
+        let (mut real, _) = self
+            .open_hidden_vault(master_password)?
+            .ok_or(CryptoError::HiddenVaultNotFound)?;
+        real.hidden_vault = Some(HiddenVault::filler()?);
+        real.update_hashes()?;
+        *self = real;
+        Ok(())
+    }
+
+    // The vault hidden behind this one, if `password` is its master password. Returns the key
+    // too so changes can be sealed back in without deriving it again.
+    pub fn open_hidden_vault(&self, password: &[u8]) -> Result<Option<(ApplicationData, HiddenVaultKey)>, CryptoError> {
+        let hidden_vault: &HiddenVault = match &self.hidden_vault {
+            Some(hidden_vault) => hidden_vault,
+            None => return Ok(None),
+        };
+        let key: HiddenVaultKey = hidden_vault.derive_key(password);
+        match self.open_hidden_vault_with_key(&key)? {
+            Some(vault) if vault.verify_master_password(password) => Ok(Some((vault, key))),
+            Some(_) => Err(CryptoError::MekHashVerificationFailed),
+            None => Ok(None),
+        }
+    }
+
+    pub fn open_hidden_vault_with_key(&self, key: &HiddenVaultKey) -> Result<Option<ApplicationData>, CryptoError> {
+        let payload: Zeroizing<Vec<u8>> = match self.hidden_vault.as_ref().and_then(|h| h.open(key)) {
+            Some(payload) => payload,
+            None => return Ok(None),
+        };
+
+        let content: &str = std::str::from_utf8(&payload).map_err(|_| CryptoError::InvalidInput)?;
+        let mut vault: ApplicationData = serde_json::from_str(content)?;
+        migration::upgrade_hidden(&mut vault, content)?;
+        if !vault.verify_hashes() {
+            return Err(CryptoError::MekHashVerificationFailed);
+        }
+        Ok(Some(vault))
+    }
+
+    pub fn seal_hidden_vault(&mut self, vault: &ApplicationData, key: &HiddenVaultKey) -> Result<(), CryptoError> {
+        if vault.hidden_vault.is_some() {
+            return Err(CryptoError::InvalidInput);
+        }
+        let payload: Zeroizing<Vec<u8>> = Zeroizing::new(serde_json::to_vec(vault)?);
+        self.hidden_vault = Some(HiddenVault::seal(&payload, key)?);
+        self.update_hashes()
+    }
+
     pub fn verify_master_password(&self, password_attempt: &[u8]) -> bool {
         if let Some(master_data) = &self.master_password_data {
             master_data.verify_master_password(password_attempt)
@@ -127,13 +501,22 @@ impl ApplicationData {
         self.password_entries
             .iter()
             .filter(|e| {
-                e.title.contains(search_term)
-                    || e.url.as_ref().map_or(false, |u| u.contains(search_term))
+                !e.is_trashed()
+                    && (e.title.contains(search_term) || e.urls.iter().any(|u| u.url.contains(search_term)))
             })
             .cloned()
             .collect()
     }
 
+    // Entries outside the trash with a URL whose match rule accepts `page_url`, e.g. to fill a
+    // login on that page
+    pub fn find_entries_for_url(&self, page_url: &str) -> Vec<&PasswordEntry> {
+        self.password_entries
+            .iter()
+            .filter(|e| !e.is_trashed() && e.matches_url(page_url))
+            .collect()
+    }
+
     pub fn find_password_entry(
         &self,
         password_entry: &PasswordEntry,
@@ -149,6 +532,7 @@ impl ApplicationData {
     }
 
     pub fn add_password_entry(&mut self, entry: PasswordEntry) -> Result<(), CryptoError> {
+        expiry::check_days(entry.rotation_days)?;
         if self.find_password_entry(&entry).is_err() {
             self.password_entries.push(entry);
             self.update_hashes()?;
@@ -158,13 +542,43 @@ impl ApplicationData {
         }
     }
 
-    pub fn update_password_entry(&mut self, updated_entry: &PasswordEntry) -> Result<(), CryptoError> {
+    // The entry's password history and dates are kept from the stored version, with the old
+    // password added to the history if this update changes it
+    pub fn update_password_entry(&mut self, updated_entry: &PasswordEntry, mek: &[u8]) -> Result<(), CryptoError> {
+        expiry::check_days(updated_entry.rotation_days)?;
         let index = self.find_password_entry(&updated_entry)?;
-        self.password_entries[index] = updated_entry.clone();
+        let stored: &PasswordEntry = &self.password_entries[index];
+        let mut entry: PasswordEntry = updated_entry.clone();
+        let new_password: Zeroizing<Vec<u8>> = match updated_entry.password {
+            Some(ref password) => Zeroizing::new(Cryptographer::new(None).decrypt(password.clone(), mek)?),
+            None => Zeroizing::new(Vec::new()),
+        };
+        let (password_history, password_changed) = stored.history_after_replacing(&new_password, mek)?;
+        let now: DateTime<Utc> = Utc::now();
+        entry.password_history = password_history;
+        entry.created = stored.created;
+        entry.modified = Some(now);
+        entry.password_changed = if password_changed { Some(now) } else { stored.password_changed };
+        entry.last_used = stored.last_used;
+        self.password_entries[index] = entry;
         self.update_hashes()?;
         Ok(())
     }
 
+    // Older versions stored usernames and notes in plaintext; this runs once the MEK is known and
+    // returns whether anything was encrypted, i.e. whether the vault needs saving
+    pub fn encrypt_plaintext_fields(&mut self, mek: &[u8]) -> Result<bool, CryptoError> {
+        let mut changed: bool = false;
+        for entry in self.password_entries.iter_mut() {
+            changed |= entry.encrypt_plaintext_fields(mek)?;
+        }
+        if changed {
+            self.update_hashes()?;
+        }
+        Ok(changed)
+    }
+
+    // Deletes an entry for good; trash_password_entry is the undoable way
     pub fn remove_password_entry(&mut self, password_entry: PasswordEntry) -> Result<(), CryptoError> {
         let index = self.find_password_entry(&password_entry)?;
         self.password_entries.remove(index);
@@ -172,6 +586,105 @@ impl ApplicationData {
         Ok(())
     }
 
+    pub fn trash_password_entry(&mut self, id: &str) -> Result<(), CryptoError> {
+        let entry: &mut PasswordEntry = self
+            .password_entries
+            .iter_mut()
+            .find(|e| e.id == id && !e.is_trashed())
+            .ok_or(CryptoError::PasswordEntryNotFound)?;
+        let now: DateTime<Utc> = Utc::now();
+        entry.deleted = Some(now);
+        entry.modified = Some(now);
+        self.update_hashes()
+    }
+
+    pub fn restore_password_entry(&mut self, id: &str) -> Result<(), CryptoError> {
+        let entry: &mut PasswordEntry = self
+            .password_entries
+            .iter_mut()
+            .find(|e| e.id == id && e.is_trashed())
+            .ok_or(CryptoError::PasswordEntryNotFound)?;
+        entry.deleted = None;
+        entry.modified = Some(Utc::now());
+        self.update_hashes()
+    }
+
+    pub fn trashed_entries(&self) -> impl Iterator<Item = &PasswordEntry> {
+        self.password_entries.iter().filter(|e| e.is_trashed())
+    }
+
+    // Removes entries that have been in the trash longer than the retention period in the user
+    // settings and returns them; the caller deletes their attachment blobs
+    pub fn purge_trash(&mut self) -> Result<Vec<PasswordEntry>, CryptoError> {
+        self.purge_trash_at(Utc::now())
+    }
+
+    pub(crate) fn purge_trash_at(&mut self, now: DateTime<Utc>) -> Result<Vec<PasswordEntry>, CryptoError> {
+        let purged: Vec<PasswordEntry> = trash::take_expired(
+            &mut self.password_entries,
+            |e| e.deleted,
+            self.user_settings.trash_retention_days,
+            now,
+        );
+        if !purged.is_empty() {
+            self.update_hashes()?;
+        }
+        Ok(purged)
+    }
+
+    // Entries whose password has expired or expires within the warning period in the user
+    // settings, soonest first
+    pub fn expiring_entries(&self) -> Vec<ExpiryReminder> {
+        self.expiring_entries_at(Utc::now())
+    }
+
+    pub(crate) fn expiring_entries_at(&self, now: DateTime<Utc>) -> Vec<ExpiryReminder> {
+        expiry::reminders_at(&self.password_entries, self.user_settings.expiry_warning_days, now)
+    }
+
+    // The folders entries outside the trash are filed in, as a tree
+    pub fn folders(&self) -> Vec<Folder> {
+        folders::folder_tree(self.password_entries.iter().filter(|e| !e.is_trashed()))
+    }
+
+    pub fn tags(&self) -> Vec<TagCount> {
+        folders::tag_counts(self.password_entries.iter().filter(|e| !e.is_trashed()))
+    }
+
+    // The folder and tag operations below change every entry they affect, trashed ones included,
+    // so a restored entry comes back to where its folder is now. They return how many changed.
+    pub fn rename_folder(&mut self, path: &str, new_name: &str) -> Result<usize, CryptoError> {
+        let changed: usize = folders::rename_folder(&mut self.password_entries, path, new_name)?;
+        self.rehash_if_changed(changed)
+    }
+
+    pub fn move_folder(&mut self, path: &str, new_parent: Option<&str>) -> Result<usize, CryptoError> {
+        let changed: usize = folders::move_folder(&mut self.password_entries, path, new_parent)?;
+        self.rehash_if_changed(changed)
+    }
+
+    pub fn delete_folder(&mut self, path: &str) -> Result<usize, CryptoError> {
+        let changed: usize = folders::delete_folder(&mut self.password_entries, path)?;
+        self.rehash_if_changed(changed)
+    }
+
+    pub fn rename_tag(&mut self, tag: &str, new_tag: &str) -> Result<usize, CryptoError> {
+        let changed: usize = folders::rename_tag(&mut self.password_entries, tag, new_tag)?;
+        self.rehash_if_changed(changed)
+    }
+
+    pub fn delete_tag(&mut self, tag: &str) -> Result<usize, CryptoError> {
+        let changed: usize = folders::delete_tag(&mut self.password_entries, tag);
+        self.rehash_if_changed(changed)
+    }
+
+    fn rehash_if_changed(&mut self, changed: usize) -> Result<usize, CryptoError> {
+        if changed > 0 {
+            self.update_hashes()?;
+        }
+        Ok(changed)
+    }
+
 
     pub fn update_hashes(&mut self) -> Result<(), CryptoError> {
         let master_data_serialized =
@@ -179,9 +692,9 @@ impl ApplicationData {
         self.master_password_data_hash =
             Some(Self::generate_hash(master_data_serialized.as_bytes()));
 
-        let mek_data_serialized =
-            serde_json::to_string(&self.mek_data).map_err(CryptoError::Serde)?;
-        self.mek_data_hash = Some(Self::generate_hash(mek_data_serialized.as_bytes()));
+        let key_slots_serialized =
+            serde_json::to_string(&self.key_slots).map_err(CryptoError::Serde)?;
+        self.key_slots_hash = Some(Self::generate_hash(key_slots_serialized.as_bytes()));
 
         let entries_serialized =
             serde_json::to_string(&self.password_entries).map_err(CryptoError::Serde)?;
@@ -189,6 +702,9 @@ impl ApplicationData {
 
         self.password_entries_hash = Some(Self::generate_hash(entries_serialized.as_bytes()));
 
+        let hidden_vault_serialized =
+            serde_json::to_string(&self.hidden_vault).map_err(CryptoError::Serde)?;
+        self.hidden_vault_hash = Some(Self::generate_hash(hidden_vault_serialized.as_bytes()));
 
         Ok(())
     }
@@ -196,7 +712,7 @@ impl ApplicationData {
         
 
 
-    fn generate_hash(data: &[u8]) -> String {
+    pub(crate) fn generate_hash(data: &[u8]) -> String {
         let hash: Digest = ring::digest::digest(&ring::digest::SHA256, data);
         hex::encode(hash.as_ref())
     }
@@ -221,13 +737,15 @@ impl ApplicationData {
         file.read_to_string(&mut content)
             .await
             .map_err(|e| CryptoError::IO(e))?;
-        let deserialized: ApplicationData =
+        let mut deserialized: ApplicationData =
             serde_json::from_str(&content).map_err(|e| CryptoError::Serde(e))?;
+        migration::upgrade(&mut deserialized, &content)?;
 
         Ok(Some(deserialized))
     }
 
     pub fn update_user_settings(&mut self, new_settings: UserSettings) -> Result<(), CryptoError> {
+        expiry::check_days(Some(new_settings.expiry_warning_days))?;
         self.user_settings = new_settings;
         self.update_hashes()?;
         Ok(())
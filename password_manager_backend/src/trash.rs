@@ -0,0 +1,25 @@
+// Removed entries go to the trash first. They stay in the vault with a `deleted` date, can be
+// restored from there, and are purged on unlock once they've been in it longer than the
+// retention period in the user's settings.
+use chrono::{DateTime, Duration, Utc};
+
+pub const DEFAULT_TRASH_RETENTION_DAYS: u32 = 30;
+
+pub fn is_expired(deleted: Option<DateTime<Utc>>, retention_days: u32, now: DateTime<Utc>) -> bool {
+    deleted.is_some_and(|deleted| now - deleted >= Duration::days(retention_days.into()))
+}
+
+// Takes the expired entries out of `entries` and returns them, so their attachments can be
+// deleted too
+pub fn take_expired<T>(
+    entries: &mut Vec<T>,
+    deleted: impl Fn(&T) -> Option<DateTime<Utc>>,
+    retention_days: u32,
+    now: DateTime<Utc>,
+) -> Vec<T> {
+    let (expired, kept): (Vec<T>, Vec<T>) = std::mem::take(entries)
+        .into_iter()
+        .partition(|entry| is_expired(deleted(entry), retention_days, now));
+    *entries = kept;
+    expired
+}
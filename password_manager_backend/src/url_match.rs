@@ -0,0 +1,128 @@
+// Logins can have several URLs, each with a rule for which pages it matches, e.g. a login used on
+// both login.example.com and example.co.uk. Like the other plaintext fields, URLs aren't encrypted,
+// so stored entries can be matched without the MEK.
+use publicsuffix::{List, Psl};
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::sync::OnceLock;
+use url::{Host, Url};
+
+// The Public Suffix List, private domains included, so tenants of hosts like github.io or
+// herokuapp.com each get their own base domain. Refresh it from https://publicsuffix.org/list/.
+const PUBLIC_SUFFIX_LIST: &str = include_str!("../data/public_suffix_list.dat");
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum UrlMatch {
+    #[default]
+    BaseDomain, // same registered domain, so login.example.com matches www.example.com
+    Host,       // same host and port
+    StartsWith, // same origin, and the page's URL starts with this one
+    Exact,      // the page's URL is exactly this one
+    Regex,      // this URL is a regular expression the whole of the page's URL has to match
+    Never,      // kept on the entry but never matched
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct EntryUrl {
+    pub url: String,
+    #[serde(default)]
+    pub match_mode: UrlMatch,
+}
+
+impl EntryUrl {
+    pub fn new(url: &str) -> Self {
+        Self::with_match(url, UrlMatch::default())
+    }
+
+    pub fn with_match(url: &str, match_mode: UrlMatch) -> Self {
+        Self {
+            url: url.trim().to_string(),
+            match_mode,
+        }
+    }
+
+    // Whether a page at `page_url` is one this URL is for. URLs that can't be parsed (or regexes
+    // that don't compile) match nothing.
+    pub fn matches(&self, page_url: &str) -> bool {
+        let page_url: &str = page_url.trim();
+        match self.match_mode {
+            UrlMatch::BaseDomain => match (parse(&self.url), parse(page_url)) {
+                (Some(url), Some(page)) => base_domain(&url).is_some() && base_domain(&url) == base_domain(&page),
+                _ => false,
+            },
+            UrlMatch::Host => match (parse(&self.url), parse(page_url)) {
+                (Some(url), Some(page)) => {
+                    url.host().is_some() && url.host() == page.host() && url.port_or_known_default() == page.port_or_known_default()
+                }
+                _ => false,
+            },
+            // compared parsed, so https://example.com doesn't cover https://example.com.evil.io
+            UrlMatch::StartsWith => match (parse(&self.url), parse(page_url)) {
+                (Some(url), Some(page)) => url.origin() == page.origin() && page.as_str().starts_with(url.as_str()),
+                _ => false,
+            },
+            UrlMatch::Exact => !self.url.is_empty() && page_url == self.url,
+            UrlMatch::Regex => Regex::new(&format!("^(?:{})$", self.url)).is_ok_and(|regex| regex.is_match(page_url)),
+            UrlMatch::Never => false,
+        }
+    }
+}
+
+// Whether any of an entry's URLs matches the page
+pub fn any_matches(urls: &[EntryUrl], page_url: &str) -> bool {
+    urls.iter().any(|url| url.matches(page_url))
+}
+
+// Entries store URLs however the user typed them, so "example.com" counts as "https://example.com"
+fn parse(url: &str) -> Option<Url> {
+    let url: &str = url.trim();
+    if url.is_empty() {
+        return None;
+    }
+    if url.contains("://") {
+        Url::parse(url).ok()
+    } else {
+        Url::parse(&format!("https://{}", url)).ok()
+    }
+}
+
+fn public_suffixes() -> &'static List {
+    static LIST: OnceLock<List> = OnceLock::new();
+    LIST.get_or_init(|| PUBLIC_SUFFIX_LIST.parse().unwrap_or_default())
+}
+
+// The registrable domain of the URL's host: "login.example.co.uk" is "example.co.uk" and
+// "alice.github.io" stays "alice.github.io". IP addresses, single-label hosts such as "localhost"
+// and hosts that are a public suffix themselves are their own base domain.
+fn base_domain(url: &Url) -> Option<String> {
+    let domain: String = match url.host()? {
+        Host::Domain(domain) => domain.trim_end_matches('.').to_ascii_lowercase(),
+        Host::Ipv4(address) => return Some(address.to_string()),
+        Host::Ipv6(address) => return Some(address.to_string()),
+    };
+    let registrable: Option<String> = public_suffixes()
+        .domain(domain.as_bytes())
+        .and_then(|registrable| std::str::from_utf8(registrable.as_bytes()).ok().map(str::to_string));
+    Some(registrable.unwrap_or(domain))
+}
+
+// Entries from before multiple URLs had a single `url` string (or null); they get that one URL
+// with the default rule
+pub fn deserialize_urls<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<EntryUrl>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Urls {
+        List(Vec<EntryUrl>),
+        Single(Option<String>),
+    }
+
+    Ok(match Urls::deserialize(deserializer)? {
+        Urls::List(urls) => urls,
+        Urls::Single(url) => url
+            .filter(|url| !url.trim().is_empty())
+            .map(|url| vec![EntryUrl::new(&url)])
+            .unwrap_or_default(),
+    })
+}
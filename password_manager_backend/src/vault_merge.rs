@@ -0,0 +1,174 @@
+use crate::attachment::DecryptedAttachment;
+use crate::error_handling::CryptoError;
+use crate::password_entry::{DecryptedPasswordEntry, PasswordEntry};
+use crate::storage::ApplicationData;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum MergeSide {
+    Ours,
+    Theirs,
+}
+
+// An entry edited differently in both vaults, and which edit was kept
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct EntryConflict {
+    pub id: String,
+    pub title: String,
+    pub ours_modified: DateTime<Utc>,
+    pub theirs_modified: DateTime<Utc>,
+    pub kept: MergeSide,
+}
+
+// An attachment on an entry taken from `theirs`. Its blob is in the other install's attachments
+// directory, sealed under the other vault's MEK, so the merged entry can't keep it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct LeftOutAttachment {
+    pub entry_id: String,
+    pub entry_title: String,
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct VaultMergeReport {
+    pub added: usize,     // only in theirs
+    pub updated: usize,   // theirs had the newer edit
+    pub unchanged: usize, // in both, and ours was kept: same contents or ours had the newer edit
+    pub conflicts: Vec<EntryConflict>,
+    #[serde(default)]
+    pub left_out_attachments: Vec<LeftOutAttachment>,
+}
+
+// Unions `theirs` into `ours` by id. When both have an entry with different contents the one
+// modified later wins (ours on a tie). Moving an entry to or from the trash counts as an edit, so
+// the later trash state wins the same way. Entries emptied from the trash leave no record, so
+// one deleted for good on one side comes back from the other. An entry taken from `theirs` keeps
+// only the attachments our copy of it has; the rest are listed in `left_out_attachments`.
+pub fn merge_decrypted_entries(
+    ours: &mut Vec<DecryptedPasswordEntry>,
+    theirs: Vec<DecryptedPasswordEntry>,
+) -> VaultMergeReport {
+    let mut report: VaultMergeReport = VaultMergeReport::default();
+    for mut entry in theirs {
+        let Some(existing) = ours.iter_mut().find(|e| e.id == entry.id) else {
+            leave_out_attachments(&mut entry, &[], &mut report);
+            ours.push(entry);
+            report.added += 1;
+            continue;
+        };
+        if existing.same_content(&entry) {
+            report.unchanged += 1;
+            continue;
+        }
+
+        let kept: MergeSide = match existing.modified_at().cmp(&entry.modified_at()) {
+            Ordering::Less => MergeSide::Theirs,
+            _ => MergeSide::Ours,
+        };
+        report.conflicts.push(EntryConflict {
+            id: entry.id.clone(),
+            title: existing.title.clone(),
+            ours_modified: existing.modified_at(),
+            theirs_modified: entry.modified_at(),
+            kept,
+        });
+        match kept {
+            MergeSide::Theirs => {
+                leave_out_attachments(&mut entry, &existing.attachments, &mut report);
+                *existing = entry;
+                report.updated += 1;
+            }
+            MergeSide::Ours => report.unchanged += 1,
+        }
+    }
+    report
+}
+
+// Replaces the attachments of an entry from `theirs` with `ours`, the ones our copy of it has,
+// reporting any of theirs that aren't among them
+fn leave_out_attachments(entry: &mut DecryptedPasswordEntry, ours: &[DecryptedAttachment], report: &mut VaultMergeReport) {
+    let theirs: Vec<DecryptedAttachment> = std::mem::replace(&mut entry.attachments, ours.to_vec());
+    report.left_out_attachments.extend(
+        theirs
+            .into_iter()
+            .filter(|attachment| !ours.iter().any(|ours| ours.id == attachment.id))
+            .map(|attachment| LeftOutAttachment {
+                entry_id: entry.id.clone(),
+                entry_title: entry.title.clone(),
+                name: attachment.name.clone(),
+            }),
+    );
+}
+
+// Merges `theirs` into `ours`. The result keeps everything else from `ours` (master password,
+// key slots, settings), so entries taken from `theirs` are re-encrypted with our MEK; the rest
+// keep their existing ciphertext.
+pub fn merge_vaults(
+    mut ours: ApplicationData,
+    our_password: &[u8],
+    theirs: &ApplicationData,
+    their_password: &[u8],
+) -> Result<(ApplicationData, VaultMergeReport), CryptoError> {
+    let our_mek: Zeroizing<Vec<u8>> = Zeroizing::new(ours.decrypt_mek_data(our_password)?);
+    let their_mek: Zeroizing<Vec<u8>> = Zeroizing::new(theirs.decrypt_mek_data(their_password)?);
+
+    let mut entries: Vec<DecryptedPasswordEntry> = ours
+        .password_entries
+        .iter()
+        .map(|entry| entry.to_decrypted(&our_mek))
+        .collect::<Result<_, _>>()?;
+    let their_entries: Vec<DecryptedPasswordEntry> = theirs
+        .password_entries
+        .iter()
+        .map(|entry| entry.to_decrypted(&their_mek))
+        .collect::<Result<_, _>>()?;
+    let their_ids: HashSet<String> = their_entries.iter().map(|entry| entry.id.clone()).collect();
+    let our_ids: HashSet<String> = entries.iter().map(|entry| entry.id.clone()).collect();
+    let report: VaultMergeReport = merge_decrypted_entries(&mut entries, their_entries);
+
+    let taken_from_theirs: HashSet<&str> = report
+        .conflicts
+        .iter()
+        .filter(|conflict| conflict.kept == MergeSide::Theirs)
+        .map(|conflict| conflict.id.as_str())
+        .collect();
+    let mut originals: Vec<PasswordEntry> = std::mem::take(&mut ours.password_entries);
+    for entry in &entries {
+        let from_theirs: bool = taken_from_theirs.contains(entry.id.as_str())
+            || (their_ids.contains(&entry.id) && !our_ids.contains(&entry.id));
+        let encrypted: PasswordEntry = match originals.iter().position(|original| original.id == entry.id) {
+            Some(index) if !from_theirs => originals.swap_remove(index),
+            _ => entry.convert_to_encrypted(&our_mek)?,
+        };
+        ours.password_entries.push(encrypted);
+    }
+    ours.update_hashes()?;
+    Ok((ours, report))
+}
+
+// Loads both vault files, merges them and writes the result to `output`, which may be either
+// of the inputs
+pub async fn merge_vault_files(
+    ours: &Path,
+    our_password: &[u8],
+    theirs: &Path,
+    their_password: &[u8],
+    output: &Path,
+) -> Result<VaultMergeReport, CryptoError> {
+    let not_found = || CryptoError::IO(std::io::Error::new(std::io::ErrorKind::NotFound, "vault file not found"));
+    let our_vault: ApplicationData = ApplicationData::load_from_file(ours).await?.ok_or_else(not_found)?;
+    let their_vault: ApplicationData = ApplicationData::load_from_file(theirs).await?.ok_or_else(not_found)?;
+
+    let (merged, report) = merge_vaults(our_vault, our_password, &their_vault, their_password)?;
+    merged.save_to_file(output).await?;
+    Ok(report)
+}